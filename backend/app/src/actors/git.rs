@@ -1,21 +1,101 @@
-use git2_ox::ReferenceKindFilter;
+use crate::{events, export, flow, revisions, runner};
+use git2_ox::{CommitProperties, ReferenceKindFilter};
 use hannibal::prelude::*;
-use std::path::Path;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
 
 #[derive(Actor)]
 pub struct GitActor {
     repository: git2_ox::Repository,
+    /// Sender for the events published to all subscribers
+    events: events::EventSender,
+    /// Last known commit ID and branch name of `HEAD`
+    head: Option<(String, Option<String>)>,
+    /// Fingerprint of the last known state of the references
+    refs_fingerprint: u64,
 }
 pub type GitActorAddr = Addr<GitActor>;
 
 impl GitActor {
     pub fn new(repository: git2_ox::Repository) -> Self {
-        Self { repository }
+        let mut actor = Self {
+            repository,
+            events: events::channel(),
+            head: None,
+            refs_fingerprint: 0,
+        };
+        actor.head = actor.head_state();
+        actor.refs_fingerprint = actor.refs_fingerprint();
+        actor
     }
 
     pub fn try_from_path<P: AsRef<Path>>(path: P) -> Result<Self, git2_ox::error::Error> {
         let repository = git2_ox::Repository::try_open(path.as_ref())?;
-        Ok(Self { repository })
+        Ok(Self::new(repository))
+    }
+
+    /// Get the path of the `.git` directory of the repository
+    pub fn git_dir(&self) -> &Path {
+        self.repository.repo().path()
+    }
+
+    /// Remove the temporary worktrees of commands left behind by a process which is not running
+    /// anymore, e.g. after a crash
+    ///
+    /// Worktrees of commands still running in other processes, e.g. another server, are kept.
+    pub fn remove_stale_run_worktrees(&self) {
+        let worktrees = match self.repository.list_worktrees() {
+            Ok(worktrees) => worktrees,
+            Err(e) => {
+                log::warn!("Failed to list worktrees: {e}");
+                return;
+            }
+        };
+        for worktree in worktrees
+            .iter()
+            .filter(|w| w.name().starts_with(RUN_WORKTREE_PREFIX))
+            .filter(|w| !run_worktree_owner(w).is_some_and(is_process_running))
+        {
+            match self.repository.remove_worktree(worktree.name(), true) {
+                Ok(()) => log::info!("Removed stale worktree '{}'", worktree.path().display()),
+                Err(e) => log::warn!(
+                    "Failed to remove stale worktree '{}': {e}",
+                    worktree.path().display()
+                ),
+            }
+        }
+    }
+
+    /// Get the current commit ID and branch name of `HEAD`
+    fn head_state(&self) -> Option<(String, Option<String>)> {
+        let head = self.repository.repo().head().ok()?.target()?;
+        Some((head.to_string(), self.repository.current_branch_name()))
+    }
+
+    /// Get a fingerprint of the names and targets of all references
+    fn refs_fingerprint(&self) -> u64 {
+        let mut refs: Vec<(String, String)> = match self.repository.repo().references() {
+            Ok(refs) => refs
+                .filter_map(Result::ok)
+                .map(|r| {
+                    (
+                        r.name().unwrap_or_default().to_string(),
+                        r.target().map(|t| t.to_string()).unwrap_or_default(),
+                    )
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        refs.sort();
+        let mut hasher = DefaultHasher::new();
+        refs.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Publish an event to all subscribers
+    fn publish(&self, event: events::Event) {
+        // Sending only fails if there are no subscribers
+        let _ = self.events.send(event);
     }
 
     fn filter_commit<CommitLikeT>(filter: &str, commit: &CommitLikeT) -> bool
@@ -53,6 +133,7 @@ impl Handler<GetRevision> for GitActor {
 #[message(response = Result<git2_ox::CommitWithReferences, git2_ox::error::Error>)]
 pub struct CheckoutRevision {
     pub revision: String,
+    pub mode: git2_ox::CheckoutMode,
 }
 
 impl Handler<CheckoutRevision> for GitActor {
@@ -61,15 +142,29 @@ impl Handler<CheckoutRevision> for GitActor {
         _ctx: &mut Context<Self>,
         msg: CheckoutRevision,
     ) -> Result<git2_ox::CommitWithReferences, git2_ox::error::Error> {
-        self.repository.checkout_revision(&msg.revision)
+        self.repository.checkout_revision(&msg.revision, msg.mode)
     }
 }
 
-#[message(response = Result<Vec<git2_ox::CommitWithReferences>, git2_ox::error::Error>)]
+/// Receiver of the commits listed by [`ListCommits`]
+pub type CommitsReceiver =
+    tokio::sync::mpsc::Receiver<Result<git2_ox::CommitWithReferences, git2_ox::error::Error>>;
+
+/// Number of commits buffered until the receiver catches up
+const COMMITS_BUFFER: usize = 64;
+
+/// List the commits of a range matching the filters, starting after the commit `after`
+///
+/// The revisions are resolved by the actor, the commits are walked in a blocking task with its own
+/// handle of the repository to not block the actor for the whole walk. The walk stops once the
+/// receiver is dropped.
+#[message(response = Result<CommitsReceiver, git2_ox::error::Error>)]
 pub struct ListCommits {
     pub base_rev: Option<String>,
     pub head_rev: Option<String>,
     pub filter: Option<String>,
+    pub after: Option<String>,
+    pub commit_filter: git2_ox::CommitFilter,
 }
 
 impl Handler<ListCommits> for GitActor {
@@ -77,20 +172,146 @@ impl Handler<ListCommits> for GitActor {
         &mut self,
         _ctx: &mut Context<Self>,
         msg: ListCommits,
-    ) -> Result<Vec<git2_ox::CommitWithReferences>, git2_ox::error::Error> {
-        let commits_iter = self
-            .repository
-            .iter_commits(msg.base_rev.as_deref(), msg.head_rev.as_deref())?;
+    ) -> Result<CommitsReceiver, git2_ox::error::Error> {
+        // Resolve the revisions now, the range must not change if references move during the walk
+        let resolve = |rev: Option<String>| {
+            rev.map(|r| {
+                self.repository
+                    .get_commit_for_revision(&r)
+                    .map(|c| c.id().to_string())
+            })
+            .transpose()
+        };
+        let base_rev = resolve(msg.base_rev)?;
+        let head_rev = resolve(msg.head_rev.or_else(|| Some("HEAD".to_string())))?;
+        let after = resolve(msg.after)?;
         let filter = msg.filter.unwrap_or_default();
+        let git_dir = self.git_dir().to_path_buf();
 
-        let mut commits = Vec::new();
-        for commit_result in commits_iter {
-            let commit = commit_result?;
-            if filter.is_empty() || Self::filter_commit(&filter, &commit) {
-                commits.push(commit);
+        let (sender, receiver) = tokio::sync::mpsc::channel(COMMITS_BUFFER);
+        tokio::task::spawn_blocking(move || {
+            let walk = || {
+                let repository = git2_ox::Repository::try_open(&git_dir)?;
+                let commits = repository.iter_commits_after(
+                    base_rev.as_deref(),
+                    head_rev.as_deref(),
+                    after.as_deref(),
+                    msg.commit_filter,
+                )?;
+                for commit in commits {
+                    let commit = commit?;
+                    if !filter.is_empty() && !Self::filter_commit(&filter, &commit) {
+                        continue;
+                    }
+                    // Sending fails once the receiver was dropped
+                    if sender.blocking_send(Ok(commit)).is_err() {
+                        break;
+                    }
+                }
+                Ok(())
+            };
+            if let Err(e) = walk() {
+                let _ = sender.blocking_send(Err(e));
             }
-        }
-        Ok(commits)
+        });
+        Ok(receiver)
+    }
+}
+
+#[message(response = Result<Vec<git2_ox::BlameHunk>, git2_ox::error::Error>)]
+pub struct GetBlame {
+    pub path: String,
+    pub revision: String,
+    pub line_range: Option<std::ops::RangeInclusive<usize>>,
+}
+
+impl Handler<GetBlame> for GitActor {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: GetBlame,
+    ) -> Result<Vec<git2_ox::BlameHunk>, git2_ox::error::Error> {
+        self.repository
+            .blame(&msg.path, &msg.revision, msg.line_range)
+    }
+}
+
+#[message(response = Result<Vec<git2_ox::TreeEntry>, git2_ox::error::Error>)]
+pub struct ListTree {
+    pub revision: String,
+    pub path: String,
+}
+
+impl Handler<ListTree> for GitActor {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: ListTree,
+    ) -> Result<Vec<git2_ox::TreeEntry>, git2_ox::error::Error> {
+        self.repository.list_tree(&msg.revision, &msg.path)
+    }
+}
+
+#[message(response = Result<git2_ox::Blob, git2_ox::error::Error>)]
+pub struct ReadBlob {
+    pub revision: String,
+    pub path: String,
+    pub max_size: Option<usize>,
+}
+
+impl Handler<ReadBlob> for GitActor {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: ReadBlob,
+    ) -> Result<git2_ox::Blob, git2_ox::error::Error> {
+        self.repository
+            .read_blob(&msg.revision, &msg.path, msg.max_size)
+    }
+}
+
+/// Lay out a page of the commit graph of a range
+///
+/// The revisions are resolved by the actor, the graph is laid out in a blocking task with its own
+/// handle of the repository to not block the actor while the rows before the page are walked.
+#[message(response = Result<tokio::task::JoinHandle<Result<git2_ox::CommitGraph, git2_ox::error::Error>>, git2_ox::error::Error>)]
+pub struct GetCommitGraph {
+    pub base_rev: Option<String>,
+    pub head_rev: Option<String>,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+impl Handler<GetCommitGraph> for GitActor {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: GetCommitGraph,
+    ) -> Result<
+        tokio::task::JoinHandle<Result<git2_ox::CommitGraph, git2_ox::error::Error>>,
+        git2_ox::error::Error,
+    > {
+        // Resolve the revisions now, the range must not change if references move during the walk
+        let resolve = |rev: Option<String>| {
+            rev.map(|r| {
+                self.repository
+                    .get_commit_for_revision(&r)
+                    .map(|c| c.id().to_string())
+            })
+            .transpose()
+        };
+        let base_rev = resolve(msg.base_rev)?;
+        let head_rev = resolve(msg.head_rev.or_else(|| Some("HEAD".to_string())))?;
+        let git_dir = self.git_dir().to_path_buf();
+
+        Ok(tokio::task::spawn_blocking(move || {
+            git2_ox::Repository::try_open(&git_dir)?.commit_graph(
+                base_rev.as_deref(),
+                head_rev.as_deref(),
+                msg.offset,
+                msg.limit,
+            )
+        }))
     }
 }
 
@@ -98,6 +319,7 @@ impl Handler<ListCommits> for GitActor {
 pub struct GetDiff {
     pub base_rev: Option<String>,
     pub head_rev: Option<String>,
+    pub options: git2_ox::DiffOptions,
 }
 
 impl Handler<GetDiff> for GitActor {
@@ -106,8 +328,27 @@ impl Handler<GetDiff> for GitActor {
         _ctx: &mut Context<Self>,
         msg: GetDiff,
     ) -> Result<git2_ox::Diff, git2_ox::error::Error> {
-        self.repository
-            .diff(msg.base_rev.as_deref(), msg.head_rev.as_deref())
+        self.repository.diff(
+            msg.base_rev.as_deref(),
+            msg.head_rev.as_deref(),
+            msg.options,
+        )
+    }
+}
+
+#[message(response = Result<git2_ox::Diff, git2_ox::error::Error>)]
+pub struct GetWorkingTreeDiff {
+    pub target: git2_ox::WorkingTreeDiff,
+    pub options: git2_ox::DiffOptions,
+}
+
+impl Handler<GetWorkingTreeDiff> for GitActor {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: GetWorkingTreeDiff,
+    ) -> Result<git2_ox::Diff, git2_ox::error::Error> {
+        self.repository.diff_working_tree(msg.target, msg.options)
     }
 }
 
@@ -198,6 +439,7 @@ impl Handler<CreateBranch> for GitActor {
 pub struct RepositoryStatus {
     pub head: git2_ox::CommitWithReferences,
     pub current_branch: Option<String>,
+    pub working_tree: git2_ox::WorkingTreeStatus,
 }
 
 #[message(response = Result<RepositoryStatus, git2_ox::error::Error>)]
@@ -211,9 +453,11 @@ impl Handler<GetRepositoryStatus> for GitActor {
     ) -> Result<RepositoryStatus, git2_ox::error::Error> {
         let head = self.repository.get_commit_for_revision("HEAD")?;
         let current_branch = self.repository.current_branch_name();
+        let working_tree = self.repository.status()?;
         Ok(RepositoryStatus {
             head,
             current_branch,
+            working_tree,
         })
     }
 }
@@ -259,3 +503,527 @@ impl Handler<ListReferences> for GitActor {
         Ok(references)
     }
 }
+
+#[message(response = events::EventReceiver)]
+pub struct Subscribe;
+
+impl Handler<Subscribe> for GitActor {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, _msg: Subscribe) -> events::EventReceiver {
+        self.events.subscribe()
+    }
+}
+
+/// Notify the actor that files in the git directory changed
+#[message]
+pub struct RepositoryChanged;
+
+impl Handler<RepositoryChanged> for GitActor {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, _msg: RepositoryChanged) {
+        let head = self.head_state();
+        if head != self.head {
+            if let Some((head, current_branch)) = &head {
+                self.publish(events::Event::HeadChanged {
+                    head: head.clone(),
+                    current_branch: current_branch.clone(),
+                });
+            }
+            self.head = head;
+        }
+
+        let refs_fingerprint = self.refs_fingerprint();
+        if refs_fingerprint != self.refs_fingerprint {
+            self.refs_fingerprint = refs_fingerprint;
+            self.publish(events::Event::RefsChanged);
+        }
+    }
+}
+
+/// Publish an event to all subscribers
+#[message]
+pub struct PublishEvent {
+    pub event: events::Event,
+}
+
+impl Handler<PublishEvent> for GitActor {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, msg: PublishEvent) {
+        self.publish(msg.event);
+    }
+}
+
+/// Prefix of the names of the temporary worktrees commands are run in
+const RUN_WORKTREE_PREFIX: &str = "debug-flow-run-";
+/// Prefix of the lock reason of temporary worktrees, followed by the ID of the owning process
+const RUN_WORKTREE_LOCK_PREFIX: &str = "debug-flow run, pid ";
+
+/// Get the ID of the process owning the temporary worktree `worktree`
+fn run_worktree_owner(worktree: &git2_ox::Worktree) -> Option<u32> {
+    worktree
+        .lock_reason()?
+        .strip_prefix(RUN_WORKTREE_LOCK_PREFIX)?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Check whether the process `pid` is running
+#[cfg(unix)]
+fn is_process_running(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // Signal 0 only checks whether the process exists and may be signalled
+    // SAFETY: kill with signal 0 does not send a signal
+    let result = unsafe { libc::kill(pid, 0) };
+    result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Check whether the process `pid` is running, assumed to be the case where it cannot be checked
+#[cfg(not(unix))]
+fn is_process_running(_pid: u32) -> bool {
+    true
+}
+
+/// Run a shell command at a revision in a temporary worktree
+///
+/// The revision is resolved by the actor. The worktree is checked out and the command runs in a
+/// separate task with its own handle of the repository to not block the actor. The worktree is
+/// locked by the process while the command runs and removed again once the command finished.
+#[message(response = Result<tokio::task::JoinHandle<std::io::Result<runner::CommandOutcome>>, git2_ox::error::Error>)]
+pub struct RunCommand {
+    pub revision: String,
+    pub command: String,
+    pub timeout: Option<std::time::Duration>,
+}
+
+impl Handler<RunCommand> for GitActor {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: RunCommand,
+    ) -> Result<
+        tokio::task::JoinHandle<std::io::Result<runner::CommandOutcome>>,
+        git2_ox::error::Error,
+    > {
+        let commit = git2_ox::Commit::try_from_revision(self.repository.repo(), &msg.revision)?;
+        let name = format!("{RUN_WORKTREE_PREFIX}{}", uuid::Uuid::new_v4().simple());
+        let path = std::env::temp_dir().join(&name);
+        let git_dir = self.git_dir().to_path_buf();
+
+        Ok(tokio::spawn(async move {
+            let worktree = {
+                let (git_dir, name, path) = (git_dir.clone(), name.clone(), path.clone());
+                let commit_id = commit.id().to_string();
+                tokio::task::spawn_blocking(move || {
+                    let repository = git2_ox::Repository::try_open(&git_dir)?;
+                    repository.add_detached_worktree(&name, &path, &commit_id)?;
+                    let reason = format!("{RUN_WORKTREE_LOCK_PREFIX}{}", std::process::id());
+                    repository.lock_worktree(&name, Some(&reason))
+                })
+                .await
+                .map_err(std::io::Error::other)?
+                .map_err(std::io::Error::other)?
+            };
+            let outcome =
+                runner::run_command(worktree.path(), &msg.command, commit, msg.timeout).await;
+            // Removing the checkout is slow file system I/O as well
+            let removed = tokio::task::spawn_blocking(move || {
+                git2_ox::Repository::try_open(&git_dir)?.remove_worktree(&name, true)
+            })
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|removed| removed.map_err(|e| e.to_string()));
+            if let Err(e) = removed {
+                log::warn!("Failed to remove worktree '{}': {e}", path.display());
+            }
+            outcome
+        }))
+    }
+}
+
+#[message(response = Result<git2_ox::BisectStep, git2_ox::error::Error>)]
+pub struct Bisect {
+    pub bad: String,
+    pub good: Vec<String>,
+    pub skipped: Vec<String>,
+}
+
+impl Handler<Bisect> for GitActor {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: Bisect,
+    ) -> Result<git2_ox::BisectStep, git2_ox::error::Error> {
+        let good: Vec<_> = msg.good.iter().map(String::as_str).collect();
+        let skipped: Vec<_> = msg.skipped.iter().map(String::as_str).collect();
+        self.repository.bisect(&msg.bad, &good, &skipped)
+    }
+}
+
+/// Export a debug flow, looking up the linked revisions in the repository
+#[message(response = String)]
+pub struct ExportFlow {
+    pub data: flow::FlowData,
+    pub format: export::ExportFormat,
+    pub options: export::ExportOptions,
+}
+
+impl Handler<ExportFlow> for GitActor {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, msg: ExportFlow) -> String {
+        export::export(&self.repository, &msg.data, msg.format, msg.options)
+    }
+}
+
+#[message(response = Result<git2_ox::Snapshot, git2_ox::error::Error>)]
+pub struct CreateSnapshot {
+    pub name: String,
+    pub message: String,
+    pub excluded: Vec<String>,
+}
+
+impl Handler<CreateSnapshot> for GitActor {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: CreateSnapshot,
+    ) -> Result<git2_ox::Snapshot, git2_ox::error::Error> {
+        self.repository
+            .create_snapshot(&msg.name, &msg.message, &msg.excluded)
+    }
+}
+
+#[message(response = Result<Vec<git2_ox::Snapshot>, git2_ox::error::Error>)]
+pub struct ListSnapshots {
+    pub prefix: String,
+}
+
+impl Handler<ListSnapshots> for GitActor {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: ListSnapshots,
+    ) -> Result<Vec<git2_ox::Snapshot>, git2_ox::error::Error> {
+        self.repository.list_snapshots(&msg.prefix)
+    }
+}
+
+#[message(response = Result<(), git2_ox::error::Error>)]
+pub struct DeleteSnapshot {
+    pub name: String,
+}
+
+impl Handler<DeleteSnapshot> for GitActor {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: DeleteSnapshot,
+    ) -> Result<(), git2_ox::error::Error> {
+        self.repository.delete_snapshot(&msg.name)
+    }
+}
+
+#[message(response = Result<git2_ox::Snapshot, git2_ox::error::Error>)]
+pub struct RestoreSnapshot {
+    pub name: String,
+    pub mode: git2_ox::CheckoutMode,
+}
+
+impl Handler<RestoreSnapshot> for GitActor {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: RestoreSnapshot,
+    ) -> Result<git2_ox::Snapshot, git2_ox::error::Error> {
+        self.repository.restore_snapshot(&msg.name, msg.mode)
+    }
+}
+
+#[message(response = Result<Vec<git2_ox::Stash>, git2_ox::error::Error>)]
+pub struct ListStashes;
+
+impl Handler<ListStashes> for GitActor {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        _msg: ListStashes,
+    ) -> Result<Vec<git2_ox::Stash>, git2_ox::error::Error> {
+        self.repository.list_stashes()
+    }
+}
+
+/// Find a stash by its position in the stash list or the ID of its commit
+#[message(response = Result<git2_ox::Stash, git2_ox::error::Error>)]
+pub struct GetStash {
+    pub stash: String,
+}
+
+impl Handler<GetStash> for GitActor {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: GetStash,
+    ) -> Result<git2_ox::Stash, git2_ox::error::Error> {
+        self.repository.find_stash(&msg.stash)
+    }
+}
+
+#[message(response = Result<git2_ox::Stash, git2_ox::error::Error>)]
+pub struct SaveStash {
+    pub message: Option<String>,
+    pub include_untracked: bool,
+}
+
+impl Handler<SaveStash> for GitActor {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: SaveStash,
+    ) -> Result<git2_ox::Stash, git2_ox::error::Error> {
+        self.repository
+            .save_stash(msg.message.as_deref(), msg.include_untracked)
+    }
+}
+
+/// Apply a stash, dropping it if `pop` is set
+#[message(response = Result<git2_ox::Stash, git2_ox::error::Error>)]
+pub struct ApplyStash {
+    pub stash: String,
+    pub pop: bool,
+}
+
+impl Handler<ApplyStash> for GitActor {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: ApplyStash,
+    ) -> Result<git2_ox::Stash, git2_ox::error::Error> {
+        let stash = self.repository.find_stash(&msg.stash)?;
+        if msg.pop {
+            self.repository.pop_stash(stash.index())?;
+        } else {
+            self.repository.apply_stash(stash.index())?;
+        }
+        Ok(stash)
+    }
+}
+
+#[message(response = Result<git2_ox::Stash, git2_ox::error::Error>)]
+pub struct DropStash {
+    pub stash: String,
+}
+
+impl Handler<DropStash> for GitActor {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: DropStash,
+    ) -> Result<git2_ox::Stash, git2_ox::error::Error> {
+        let stash = self.repository.find_stash(&msg.stash)?;
+        self.repository.drop_stash(stash.index())?;
+        Ok(stash)
+    }
+}
+
+#[message(response = Result<git2_ox::Diff, git2_ox::error::Error>)]
+pub struct GetStashDiff {
+    pub stash: String,
+    pub options: git2_ox::DiffOptions,
+}
+
+impl Handler<GetStashDiff> for GitActor {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: GetStashDiff,
+    ) -> Result<git2_ox::Diff, git2_ox::error::Error> {
+        let stash = self.repository.find_stash(&msg.stash)?;
+        self.repository.diff_stash(stash.index(), msg.options)
+    }
+}
+
+#[message(response = Result<Vec<git2_ox::Worktree>, git2_ox::error::Error>)]
+pub struct ListWorktrees;
+
+impl Handler<ListWorktrees> for GitActor {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        _msg: ListWorktrees,
+    ) -> Result<Vec<git2_ox::Worktree>, git2_ox::error::Error> {
+        self.repository.list_worktrees()
+    }
+}
+
+#[message(response = Result<git2_ox::Worktree, git2_ox::error::Error>)]
+pub struct GetWorktree {
+    pub name: String,
+}
+
+impl Handler<GetWorktree> for GitActor {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: GetWorktree,
+    ) -> Result<git2_ox::Worktree, git2_ox::error::Error> {
+        self.repository.find_worktree(&msg.name)
+    }
+}
+
+/// Add a worktree at a revision, on a new branch if `branch` is set or with a detached `HEAD`
+#[message(response = Result<git2_ox::Worktree, git2_ox::error::Error>)]
+pub struct AddWorktree {
+    pub name: String,
+    pub path: PathBuf,
+    pub revision: String,
+    pub branch: Option<String>,
+}
+
+impl Handler<AddWorktree> for GitActor {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: AddWorktree,
+    ) -> Result<git2_ox::Worktree, git2_ox::error::Error> {
+        self.repository
+            .add_worktree(&msg.name, &msg.path, &msg.revision, msg.branch.as_deref())
+    }
+}
+
+#[message(response = Result<git2_ox::Worktree, git2_ox::error::Error>)]
+pub struct LockWorktree {
+    pub name: String,
+    pub reason: Option<String>,
+}
+
+impl Handler<LockWorktree> for GitActor {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: LockWorktree,
+    ) -> Result<git2_ox::Worktree, git2_ox::error::Error> {
+        self.repository
+            .lock_worktree(&msg.name, msg.reason.as_deref())
+    }
+}
+
+#[message(response = Result<git2_ox::Worktree, git2_ox::error::Error>)]
+pub struct UnlockWorktree {
+    pub name: String,
+}
+
+impl Handler<UnlockWorktree> for GitActor {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: UnlockWorktree,
+    ) -> Result<git2_ox::Worktree, git2_ox::error::Error> {
+        self.repository.unlock_worktree(&msg.name)
+    }
+}
+
+#[message(response = Result<Vec<git2_ox::Worktree>, git2_ox::error::Error>)]
+pub struct PruneWorktrees;
+
+impl Handler<PruneWorktrees> for GitActor {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        _msg: PruneWorktrees,
+    ) -> Result<Vec<git2_ox::Worktree>, git2_ox::error::Error> {
+        self.repository.prune_worktrees()
+    }
+}
+
+/// Remove a worktree including its working directory, locked worktrees and worktrees with local
+/// changes only if `force` is set
+#[message(response = Result<(), git2_ox::error::Error>)]
+pub struct RemoveWorktree {
+    pub name: String,
+    pub force: bool,
+}
+
+impl Handler<RemoveWorktree> for GitActor {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: RemoveWorktree,
+    ) -> Result<(), git2_ox::error::Error> {
+        self.repository.remove_worktree(&msg.name, msg.force)
+    }
+}
+
+/// Find the nodes of a debug flow linked to revisions which are not reachable anymore
+#[message(response = Result<Vec<revisions::StaleNode>, git2_ox::error::Error>)]
+pub struct CheckRevisions {
+    pub data: flow::FlowData,
+}
+
+impl Handler<CheckRevisions> for GitActor {
+    async fn handle(
+        &mut self,
+        _ctx: &mut Context<Self>,
+        msg: CheckRevisions,
+    ) -> Result<Vec<revisions::StaleNode>, git2_ox::error::Error> {
+        revisions::stale_nodes(&self.repository, &msg.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_command() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init(temp_dir.path()).unwrap();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let commit = repo
+            .commit(Some("HEAD"), &signature, &signature, "Initial", &tree, &[])
+            .unwrap()
+            .to_string();
+
+        // Worktrees of commands left behind by a process which exited are removed, those of
+        // running processes are kept
+        let repository = git2_ox::Repository::try_open(temp_dir.path()).unwrap();
+        let mut exited = std::process::Command::new("true").spawn().unwrap();
+        exited.wait().unwrap();
+        let owners = [("stale", exited.id()), ("running", std::process::id())];
+        for (name, pid) in owners {
+            let name = format!("{RUN_WORKTREE_PREFIX}{name}");
+            repository
+                .add_detached_worktree(&name, &temp_dir.path().join(&name), &commit)
+                .unwrap();
+            let reason = format!("{RUN_WORKTREE_LOCK_PREFIX}{pid}");
+            repository.lock_worktree(&name, Some(&reason)).unwrap();
+        }
+        let git_actor = GitActor::try_from_path(temp_dir.path()).unwrap();
+        git_actor.remove_stale_run_worktrees();
+        let names: Vec<_> = repository
+            .list_worktrees()
+            .unwrap()
+            .iter()
+            .map(|w| w.name().to_string())
+            .collect();
+        assert_eq!(names, [format!("{RUN_WORKTREE_PREFIX}running")]);
+        repository.remove_worktree(&names[0], true).unwrap();
+
+        let git_actor = git_actor.spawn();
+        let outcome = git_actor
+            .call(RunCommand {
+                revision: commit,
+                command: "exit 3".to_string(),
+                timeout: None,
+            })
+            .await
+            .unwrap()
+            .unwrap()
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(outcome.exit_code(), Some(3));
+        assert!(repository.list_worktrees().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,650 @@
+use crate::actors::git::{self as git_actor, GitActorAddr};
+use crate::flow::{self, graph};
+use crate::utils;
+use git2_ox::CommitProperties;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::{fs, io, path, time};
+use utoipa::ToSchema;
+
+/// Name of the directory in the debug flow directory storing the bisection sessions
+const BISECT_DIR_NAME: &str = ".bisect";
+
+/// Exit code of a command marking a commit as untestable, same as for `git bisect run`
+const SKIP_EXIT_CODE: i32 = 125;
+
+/// Locks of the bisection sessions by the paths of their files
+static SESSION_LOCKS: LazyLock<Mutex<HashMap<path::PathBuf, Arc<tokio::sync::Mutex<()>>>>> =
+    LazyLock::new(Default::default);
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Flow(#[from] flow::Error),
+    #[error(transparent)]
+    Git(#[from] git2_ox::error::Error),
+    #[error(transparent)]
+    Actor(#[from] hannibal::error::ActorError),
+    #[error("Failed to access bisection state '{0}': {1}")]
+    Io(path::PathBuf, #[source] io::Error),
+    #[error("Failed to run command: {0}")]
+    Command(#[source] io::Error),
+    #[error("No bisection in progress for debug flow '{0}'")]
+    NotStarted(String),
+    #[error("Bisection of debug flow '{0}' is finished already")]
+    Finished(String),
+    #[error("{0}")]
+    Invalid(String),
+}
+
+/// Verdict for a tested commit
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    /// The commit does not have the bug
+    Good,
+    /// The commit has the bug
+    Bad,
+    /// The commit cannot be tested
+    Skip,
+}
+
+impl Verdict {
+    /// Get the state of the status node recording the verdict
+    fn state(&self) -> graph::StatusNodeState {
+        match self {
+            Verdict::Good => graph::StatusNodeState::Success,
+            Verdict::Bad => graph::StatusNodeState::Fail,
+            Verdict::Skip => graph::StatusNodeState::Unknown,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Verdict::Good => "good",
+            Verdict::Bad => "bad",
+            Verdict::Skip => "skip",
+        }
+    }
+}
+
+/// Verdict recorded during a bisection
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BisectLogEntry {
+    /// ID of the tested commit
+    commit: String,
+    /// Verdict for the commit
+    verdict: Verdict,
+    /// ID of the status node recording the verdict
+    node_id: String,
+}
+
+/// State of the bisection of a debug flow, stored in the debug flow directory
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BisectSession {
+    /// ID of the bad commit closest to the good commits
+    bad: String,
+    /// IDs of the commits known to be good
+    good: Vec<String>,
+    /// IDs of the commits which cannot be tested
+    #[serde(default)]
+    skipped: Vec<String>,
+    /// Command deciding the verdict for a commit automatically
+    #[serde(default)]
+    command: Option<String>,
+    /// ID of the node the next status node is attached to
+    #[serde(default)]
+    last_node_id: Option<String>,
+    /// Recorded verdicts, oldest first
+    #[serde(default)]
+    log: Vec<BisectLogEntry>,
+    /// Whether the result of the bisection was recorded
+    #[serde(default)]
+    finished: bool,
+}
+
+/// Bisection session and the next step of the bisection
+#[derive(Serialize, ToSchema, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BisectStatus {
+    /// State of the bisection
+    session: BisectSession,
+    /// Next step of the bisection
+    step: git2_ox::BisectStep,
+}
+
+/// Start parameters of a bisection
+///
+/// Good and bad revisions are either given directly or taken from the git revisions of status nodes.
+#[derive(Deserialize, ToSchema, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct StartBisect {
+    /// Revision known to be good
+    #[serde(default)]
+    pub good: Option<String>,
+    /// Revision known to be bad
+    #[serde(default)]
+    pub bad: Option<String>,
+    /// ID of a status node linked to a good revision
+    #[serde(default)]
+    pub good_node_id: Option<String>,
+    /// ID of a status node linked to a bad revision
+    #[serde(default)]
+    pub bad_node_id: Option<String>,
+    /// Command deciding the verdict for a commit automatically
+    ///
+    /// An exit code of 0 marks a commit as good, 125 skips a commit, any other code up to 127
+    /// marks a commit as bad.
+    #[serde(default)]
+    pub command: Option<String>,
+}
+
+fn session_path(flows_dir: &flow::FlowsDir, flow_id: &str) -> path::PathBuf {
+    let mut p = flows_dir.path().join(BISECT_DIR_NAME);
+    p.push(flow::FlowData::file_name_from_id(flow_id));
+    p
+}
+
+/// Lock the bisection session of the debug flow `flow_id` until the guard is dropped
+///
+/// A session is only updated while it is locked, so concurrent verdicts are never lost.
+async fn lock_session(
+    flows_dir: &flow::FlowsDir,
+    flow_id: &str,
+) -> tokio::sync::OwnedMutexGuard<()> {
+    let lock = SESSION_LOCKS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(session_path(flows_dir, flow_id))
+        .or_default()
+        .clone();
+    lock.lock_owned().await
+}
+
+/// Load the bisection session of the debug flow `flow_id`
+pub fn load_session(flows_dir: &flow::FlowsDir, flow_id: &str) -> Result<BisectSession, Error> {
+    let p = session_path(flows_dir, flow_id);
+    let content = match fs::read_to_string(&p) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(Error::NotStarted(flow_id.to_string()));
+        }
+        Err(e) => return Err(Error::Io(p, e)),
+    };
+    serde_json::from_str(&content).map_err(|e| Error::Flow(e.into()))
+}
+
+fn save_session(
+    flows_dir: &flow::FlowsDir,
+    flow_id: &str,
+    session: &BisectSession,
+) -> Result<(), Error> {
+    let p = session_path(flows_dir, flow_id);
+    if let Some(parent) = p.parent() {
+        fs::create_dir_all(parent).map_err(|e| Error::Io(parent.to_path_buf(), e))?;
+    }
+    let content = serde_json::to_string_pretty(session).map_err(|e| Error::Flow(e.into()))?;
+    utils::write_atomic(&p, content.as_bytes()).map_err(|e| Error::Io(p, e))
+}
+
+/// Stop the bisection of the debug flow `flow_id`, the recorded status nodes are kept
+pub async fn reset(flows_dir: &flow::FlowsDir, flow_id: &str) -> Result<(), Error> {
+    let _guard = lock_session(flows_dir, flow_id).await;
+    let p = session_path(flows_dir, flow_id);
+    match fs::remove_file(&p) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            Err(Error::NotStarted(flow_id.to_string()))
+        }
+        Err(e) => Err(Error::Io(p, e)),
+    }
+}
+
+/// Get the bisection session of the debug flow `flow_id` and its next step
+pub async fn status(
+    flows_dir: &flow::FlowsDir,
+    git_actor: &GitActorAddr,
+    flow_id: &str,
+) -> Result<BisectStatus, Error> {
+    let session = load_session(flows_dir, flow_id)?;
+    let step = next_step(git_actor, &session).await?;
+    Ok(BisectStatus { session, step })
+}
+
+/// Start a bisection of the debug flow `flow_id`, replacing a previous bisection
+///
+/// Status nodes recording the verdicts are chained starting at the bad or good node, if set.
+pub async fn start(
+    flows_dir: &flow::FlowsDir,
+    git_actor: &GitActorAddr,
+    flow_id: &str,
+    start: StartBisect,
+) -> Result<BisectStatus, Error> {
+    let data = flows_dir.get_flow_by_id(flow_id)?.data().clone();
+    let node_revision = |node_id: &str| -> Result<String, Error> {
+        let node = data
+            .reactflow()
+            .node(node_id)
+            .ok_or_else(|| flow::Error::NodeNotFound(node_id.to_string()))?;
+        node.git().map(|git| git.rev.clone()).ok_or_else(|| {
+            Error::Invalid(format!("Node '{node_id}' is not linked to a git revision"))
+        })
+    };
+    let good = match (start.good, &start.good_node_id) {
+        (Some(rev), _) => rev,
+        (None, Some(node_id)) => node_revision(node_id)?,
+        (None, None) => return Err(Error::Invalid("No good revision given".to_string())),
+    };
+    let bad = match (start.bad, &start.bad_node_id) {
+        (Some(rev), _) => rev,
+        (None, Some(node_id)) => node_revision(node_id)?,
+        (None, None) => return Err(Error::Invalid("No bad revision given".to_string())),
+    };
+
+    let _guard = lock_session(flows_dir, flow_id).await;
+    let session = BisectSession {
+        bad: resolve(git_actor, bad).await?,
+        good: vec![resolve(git_actor, good).await?],
+        skipped: Vec::new(),
+        command: start.command,
+        last_node_id: start.bad_node_id.or(start.good_node_id),
+        log: Vec::new(),
+        finished: false,
+    };
+    advance(flows_dir, git_actor, flow_id, session, Vec::new()).await
+}
+
+/// Record the `verdict` for `revision` and find the next step of the bisection
+///
+/// If `revision` is not set, the verdict is recorded for the commit proposed by the last step.
+pub async fn record_verdict(
+    flows_dir: &flow::FlowsDir,
+    git_actor: &GitActorAddr,
+    flow_id: &str,
+    verdict: Verdict,
+    revision: Option<String>,
+) -> Result<BisectStatus, Error> {
+    let _guard = lock_session(flows_dir, flow_id).await;
+    let session = load_session(flows_dir, flow_id)?;
+    let commit = match revision {
+        Some(revision) => resolve(git_actor, revision).await?,
+        None => proposed_commit(flow_id, &session, &next_step(git_actor, &session).await?)?,
+    };
+    apply_verdict(
+        flows_dir, git_actor, flow_id, session, commit, verdict, None,
+    )
+    .await
+}
+
+/// Decide the verdicts with the command of the bisection until the first bad commit is found
+///
+/// The command runs in a temporary worktree for every proposed commit. The bisection stops early
+/// if the command times out or exits with a code above 127, or if it is reset.
+///
+/// The session is only locked while a verdict is recorded, so it can be reset and get verdicts
+/// while the command runs.
+pub async fn run(
+    flows_dir: &flow::FlowsDir,
+    git_actor: &GitActorAddr,
+    flow_id: &str,
+    timeout: Option<time::Duration>,
+) -> Result<BisectStatus, Error> {
+    let command = load_session(flows_dir, flow_id)?
+        .command
+        .ok_or_else(|| Error::Invalid("The bisection has no command".to_string()))?;
+
+    loop {
+        let session = load_session(flows_dir, flow_id)?;
+        let step = next_step(git_actor, &session).await?;
+        if session.finished {
+            return Ok(BisectStatus { session, step });
+        }
+        let commit = proposed_commit(flow_id, &session, &step)?;
+        let msg = git_actor::RunCommand {
+            revision: commit.clone(),
+            command: command.clone(),
+            timeout,
+        };
+        let outcome = git_actor
+            .call(msg)
+            .await??
+            .await
+            .map_err(|e| Error::Command(io::Error::other(e)))?
+            .map_err(Error::Command)?;
+
+        let verdict = match outcome.exit_code() {
+            Some(0) => Verdict::Good,
+            Some(SKIP_EXIT_CODE) => Verdict::Skip,
+            Some(1..=127) => Verdict::Bad,
+            _ => {
+                return Err(Error::Invalid(format!(
+                    "Command `{command}` on `{commit}` {}, bisection stopped",
+                    outcome.summary()
+                )));
+            }
+        };
+
+        let _guard = lock_session(flows_dir, flow_id).await;
+        // The verdict is dropped if the session moved on to another commit in the meantime
+        let session = load_session(flows_dir, flow_id)?;
+        let step = next_step(git_actor, &session).await?;
+        if proposed_commit(flow_id, &session, &step).ok() != Some(commit.clone()) {
+            continue;
+        }
+        apply_verdict(
+            flows_dir,
+            git_actor,
+            flow_id,
+            session,
+            commit,
+            verdict,
+            Some(outcome.to_markdown()),
+        )
+        .await?;
+    }
+}
+
+/// Resolve `revision` to a commit ID
+async fn resolve(git_actor: &GitActorAddr, revision: String) -> Result<String, Error> {
+    let commit = git_actor
+        .call(git_actor::GetRevision { revision })
+        .await??;
+    Ok(commit.id().to_string())
+}
+
+async fn next_step(
+    git_actor: &GitActorAddr,
+    session: &BisectSession,
+) -> Result<git2_ox::BisectStep, Error> {
+    let msg = git_actor::Bisect {
+        bad: session.bad.clone(),
+        good: session.good.clone(),
+        skipped: session.skipped.clone(),
+    };
+    Ok(git_actor.call(msg).await??)
+}
+
+/// Get the ID of the commit proposed by `step`
+fn proposed_commit(
+    flow_id: &str,
+    session: &BisectSession,
+    step: &git2_ox::BisectStep,
+) -> Result<String, Error> {
+    match step {
+        git2_ox::BisectStep::Next { commit, .. } if !session.finished => {
+            Ok(commit.id().to_string())
+        }
+        _ => Err(Error::Finished(flow_id.to_string())),
+    }
+}
+
+/// Record `verdict` for `commit` in the session and the flow, then find the next step
+async fn apply_verdict(
+    flows_dir: &flow::FlowsDir,
+    git_actor: &GitActorAddr,
+    flow_id: &str,
+    mut session: BisectSession,
+    commit: String,
+    verdict: Verdict,
+    description: Option<String>,
+) -> Result<BisectStatus, Error> {
+    if session.finished {
+        return Err(Error::Finished(flow_id.to_string()));
+    }
+    let summary = git_actor
+        .call(git_actor::GetRevision {
+            revision: commit.clone(),
+        })
+        .await??
+        .summary()
+        .to_string();
+    let node = new_status_node(
+        &mut session,
+        graph::StatusNodeData {
+            title: format!("Bisect: {}", verdict.as_str()),
+            state: verdict.state(),
+            description: description.unwrap_or_default(),
+            git: Some(graph::GitMetadata::commit(&commit, &summary)),
+            ..Default::default()
+        },
+    );
+    let node_id = node.1.id().to_string();
+    match verdict {
+        Verdict::Good => session.good.push(commit.clone()),
+        Verdict::Bad => session.bad = commit.clone(),
+        Verdict::Skip => session.skipped.push(commit.clone()),
+    }
+    session.log.push(BisectLogEntry {
+        commit,
+        verdict,
+        node_id,
+    });
+    advance(flows_dir, git_actor, flow_id, session, vec![node]).await
+}
+
+/// Find the next step of the bisection and record the result once the bisection is finished
+///
+/// The session is saved before the status nodes in `nodes` and the result are added to the flow,
+/// so the flow never shows verdicts the session does not know about.
+async fn advance(
+    flows_dir: &flow::FlowsDir,
+    git_actor: &GitActorAddr,
+    flow_id: &str,
+    mut session: BisectSession,
+    mut nodes: Vec<(Option<String>, flow::Node)>,
+) -> Result<BisectStatus, Error> {
+    let step = next_step(git_actor, &session).await?;
+    let result = match &step {
+        git2_ox::BisectStep::Next { .. } => None,
+        git2_ox::BisectStep::FirstBad { commit } => Some(graph::StatusNodeData {
+            title: "Bisect: first bad commit".to_string(),
+            state: graph::StatusNodeState::Fail,
+            description: format!("`{}` is the first bad commit\n", commit.id()),
+            git: Some(graph::GitMetadata::commit(commit.id(), commit.summary())),
+            ..Default::default()
+        }),
+        git2_ox::BisectStep::Ambiguous { candidates } => Some(graph::StatusNodeData {
+            title: "Bisect: inconclusive".to_string(),
+            state: graph::StatusNodeState::Unknown,
+            description: candidates.iter().fold(
+                "The first bad commit could be any of:\n\n".to_string(),
+                |md, c| md + &format!("- `{}` {}\n", c.id(), c.summary()),
+            ),
+            ..Default::default()
+        }),
+    };
+    if let Some(data) = result {
+        nodes.push(new_status_node(&mut session, data));
+        session.finished = true;
+    }
+    save_session(flows_dir, flow_id, &session)?;
+    if !nodes.is_empty() {
+        add_status_nodes(flows_dir, flow_id, nodes).await?;
+    }
+    Ok(BisectStatus { session, step })
+}
+
+/// Create a status node chained to the last node of the session, returned with the ID of its
+/// parent
+fn new_status_node(
+    session: &mut BisectSession,
+    data: graph::StatusNodeData,
+) -> (Option<String>, flow::Node) {
+    let node = flow::Node::new_status(data, graph::Position::default());
+    let parent = session.last_node_id.replace(node.id().to_string());
+    (parent, node)
+}
+
+/// Add status nodes created by [`new_status_node`] to the flow, in order
+async fn add_status_nodes(
+    flows_dir: &flow::FlowsDir,
+    flow_id: &str,
+    nodes: Vec<(Option<String>, flow::Node)>,
+) -> Result<(), Error> {
+    let flow_id = flow_id.to_string();
+    flows_dir
+        .blocking(move |flows_dir| {
+            flows_dir.update_flow(&flow_id, |flow_data| {
+                let state = flow_data.reactflow_mut();
+                for (parent, node) in nodes {
+                    // The previous node may have been deleted in the meantime
+                    let parent = parent.filter(|id| state.node(id).is_some());
+                    state.add_node(parent.as_deref(), node)?;
+                }
+                Ok(())
+            })
+        })
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hannibal::prelude::*;
+
+    #[tokio::test]
+    async fn test_session_persistence() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let flows_dir = flow::FlowsDir::try_new(temp_dir.path()).unwrap();
+        assert!(matches!(
+            load_session(&flows_dir, "foo"),
+            Err(Error::NotStarted(_))
+        ));
+
+        let session = BisectSession {
+            bad: "b".to_string(),
+            good: vec!["a".to_string()],
+            skipped: Vec::new(),
+            command: Some("make test".to_string()),
+            last_node_id: None,
+            log: Vec::new(),
+            finished: false,
+        };
+        save_session(&flows_dir, "foo", &session).unwrap();
+        let loaded = load_session(&flows_dir, "foo").unwrap();
+        assert_eq!(loaded.bad, "b");
+        assert_eq!(loaded.command.as_deref(), Some("make test"));
+        // Sessions are not listed as flows
+        assert!(flows_dir.flow_files().unwrap().is_empty());
+
+        reset(&flows_dir, "foo").await.unwrap();
+        assert!(matches!(
+            reset(&flows_dir, "foo").await,
+            Err(Error::NotStarted(_))
+        ));
+    }
+
+    /// Create a repository at `path` with a linear history of `count` commits, oldest first
+    fn commit_chain(path: &path::Path, count: usize) -> Vec<git2::Oid> {
+        let repo = git2::Repository::init(path).unwrap();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let mut commits: Vec<git2::Oid> = Vec::new();
+        for i in 0..count {
+            let parents: Vec<_> = commits
+                .last()
+                .map(|id| repo.find_commit(*id).unwrap())
+                .into_iter()
+                .collect();
+            let parents: Vec<_> = parents.iter().collect();
+            let message = format!("Commit {i}");
+            let id = repo
+                .commit(
+                    Some("HEAD"),
+                    &signature,
+                    &signature,
+                    &message,
+                    &tree,
+                    &parents,
+                )
+                .unwrap();
+            commits.push(id);
+        }
+        commits
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_verdicts() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let commits = commit_chain(temp_dir.path(), 16);
+
+        let flows_dir = flow::FlowsDir::try_new(temp_dir.path()).unwrap();
+        let flow_id = flows_dir.create_flow("foo", false).unwrap().id();
+        let git_actor = git_actor::GitActor::try_from_path(temp_dir.path())
+            .unwrap()
+            .spawn();
+        let start_bisect = StartBisect {
+            good: Some(commits[0].to_string()),
+            bad: Some(commits[15].to_string()),
+            ..Default::default()
+        };
+        start(&flows_dir, &git_actor, &flow_id, start_bisect)
+            .await
+            .unwrap();
+
+        // Both verdicts are recorded instead of one overwriting the other
+        let (first, second) = tokio::join!(
+            record_verdict(&flows_dir, &git_actor, &flow_id, Verdict::Skip, None),
+            record_verdict(&flows_dir, &git_actor, &flow_id, Verdict::Skip, None),
+        );
+        first.unwrap();
+        second.unwrap();
+        let session = load_session(&flows_dir, &flow_id).unwrap();
+        assert_eq!(session.log.len(), 2);
+        assert_ne!(session.log[0].commit, session.log[1].commit);
+        let data = flows_dir.get_flow_by_id(&flow_id).unwrap().data().clone();
+        for entry in &session.log {
+            assert!(data.reactflow().node(&entry.node_id).is_some());
+        }
+        assert!(
+            data.reactflow()
+                .incoming_edges(&session.log[1].node_id)
+                .any(|edge| edge.source == session.log[0].node_id)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reset_while_running() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let commits = commit_chain(temp_dir.path(), 16);
+
+        let flows_dir = flow::FlowsDir::try_new(temp_dir.path()).unwrap();
+        let flow_id = flows_dir.create_flow("foo", false).unwrap().id();
+        let git_actor = git_actor::GitActor::try_from_path(temp_dir.path())
+            .unwrap()
+            .spawn();
+        let start_bisect = StartBisect {
+            good: Some(commits[0].to_string()),
+            bad: Some(commits[15].to_string()),
+            command: Some("sleep 1".to_string()),
+            ..Default::default()
+        };
+        start(&flows_dir, &git_actor, &flow_id, start_bisect)
+            .await
+            .unwrap();
+
+        let running = {
+            let (flows_dir, git_actor, flow_id) =
+                (flows_dir.clone(), git_actor.clone(), flow_id.clone());
+            tokio::spawn(async move { run(&flows_dir, &git_actor, &flow_id, None).await })
+        };
+        tokio::time::sleep(time::Duration::from_millis(200)).await;
+        // The reset does not wait for the command and stops the run
+        tokio::time::timeout(
+            time::Duration::from_millis(500),
+            reset(&flows_dir, &flow_id),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert!(matches!(running.await.unwrap(), Err(Error::NotStarted(_))));
+        let data = flows_dir.get_flow_by_id(&flow_id).unwrap().data().clone();
+        assert!(data.reactflow().nodes().is_empty());
+    }
+}
@@ -0,0 +1,57 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Capacity of the event channel, slower subscribers miss the oldest events
+pub const CHANNEL_CAPACITY: usize = 64;
+
+pub type EventSender = tokio::sync::broadcast::Sender<Event>;
+pub type EventReceiver = tokio::sync::broadcast::Receiver<Event>;
+
+/// Change in the repository or the debug flow directory published to connected clients
+#[derive(Serialize, ToSchema, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Event {
+    /// `HEAD` of the repository points to another commit or branch
+    #[serde(rename_all = "camelCase")]
+    HeadChanged {
+        /// ID of the commit `HEAD` points to
+        head: String,
+        /// Name of the current branch, not set in a detached HEAD state
+        current_branch: Option<String>,
+    },
+    /// References of the repository were created, moved or deleted
+    RefsChanged,
+    /// A debug flow was created
+    FlowCreated {
+        /// ID of the debug flow
+        id: String,
+    },
+    /// A debug flow was updated
+    FlowUpdated {
+        /// ID of the debug flow
+        id: String,
+    },
+    /// A debug flow was deleted
+    FlowDeleted {
+        /// ID of the debug flow
+        id: String,
+    },
+}
+
+impl Event {
+    /// Get the name of the event as used in the `event` field of server-sent events
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::HeadChanged { .. } => "headChanged",
+            Event::RefsChanged => "refsChanged",
+            Event::FlowCreated { .. } => "flowCreated",
+            Event::FlowUpdated { .. } => "flowUpdated",
+            Event::FlowDeleted { .. } => "flowDeleted",
+        }
+    }
+}
+
+/// Create a new event channel
+pub fn channel() -> EventSender {
+    tokio::sync::broadcast::channel(CHANNEL_CAPACITY).0
+}
@@ -0,0 +1,160 @@
+use crate::flow::{self, graph};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+pub mod dot;
+pub mod markdown;
+pub mod mermaid;
+
+/// Format a debug flow can be exported in
+#[derive(
+    Serialize, Deserialize, ToSchema, clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// Markdown debugging report
+    #[default]
+    Markdown,
+    /// Mermaid flowchart
+    Mermaid,
+    /// Graphviz DOT graph
+    Dot,
+}
+
+impl ExportFormat {
+    /// Get the content type of the exported document
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "text/markdown; charset=utf-8",
+            ExportFormat::Mermaid => "text/vnd.mermaid; charset=utf-8",
+            ExportFormat::Dot => "text/vnd.graphviz; charset=utf-8",
+        }
+    }
+}
+
+/// Options for exporting a debug flow
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ExportOptions {
+    /// Include the diff stats of the revisions linked to action nodes
+    pub diff_stats: bool,
+}
+
+/// Git information about the revisions linked to the nodes of a debug flow
+#[derive(Default)]
+pub struct RevisionInfos {
+    commits: HashMap<String, git2_ox::Commit>,
+    diff_stats: HashMap<String, git2_ox::diff::DiffStats>,
+}
+
+impl RevisionInfos {
+    /// Collect the commits linked to the nodes of `data` from `repository`
+    ///
+    /// Revisions which do not exist in the repository anymore are skipped. If `diff_stats` is set,
+    /// the stats of the diff of each revision linked to an action node to its first parent are
+    /// collected as well.
+    pub fn collect(
+        repository: &git2_ox::Repository,
+        data: &flow::FlowData,
+        diff_stats: bool,
+    ) -> Self {
+        let mut infos = Self::default();
+        for node in data.reactflow().nodes() {
+            let Some(git) = node.git() else {
+                continue;
+            };
+            if !infos.commits.contains_key(&git.rev) {
+                match git2_ox::Commit::try_from_revision(repository.repo(), &git.rev) {
+                    Ok(commit) => {
+                        infos.commits.insert(git.rev.clone(), commit);
+                    }
+                    Err(e) => {
+                        log::warn!("Failed to get revision '{}': {e}", git.rev);
+                        continue;
+                    }
+                }
+            }
+            let is_action = matches!(node, flow::Node::Action(_));
+            if diff_stats && is_action && !infos.diff_stats.contains_key(&git.rev) {
+                match Self::diff_stats_for_revision(repository, &git.rev) {
+                    Ok(stats) => {
+                        infos.diff_stats.insert(git.rev.clone(), stats);
+                    }
+                    Err(e) => log::warn!("Failed to get diff of revision '{}': {e}", git.rev),
+                }
+            }
+        }
+        infos
+    }
+
+    /// Get the commit for `rev`
+    pub fn commit(&self, rev: &str) -> Option<&git2_ox::Commit> {
+        self.commits.get(rev)
+    }
+
+    /// Get the diff stats for `rev`
+    pub fn diff_stats(&self, rev: &str) -> Option<&git2_ox::diff::DiffStats> {
+        self.diff_stats.get(rev)
+    }
+
+    fn diff_stats_for_revision(
+        repository: &git2_ox::Repository,
+        rev: &str,
+    ) -> Result<git2_ox::diff::DiffStats, git2_ox::error::Error> {
+        let commit = git2_ox::utils::get_commit_for_revision(repository.repo(), rev)?;
+        let parent = commit.parent_id(0).ok().map(|id| id.to_string());
+        let diff = repository.diff(
+            parent.as_deref(),
+            Some(&commit.id().to_string()),
+            git2_ox::DiffOptions::new(),
+        )?;
+        Ok(diff.stats().clone())
+    }
+}
+
+/// Export `data` in `format`, using `repository` to look up the linked revisions
+pub fn export(
+    repository: &git2_ox::Repository,
+    data: &flow::FlowData,
+    format: ExportFormat,
+    options: ExportOptions,
+) -> String {
+    match format {
+        ExportFormat::Markdown => {
+            let infos = RevisionInfos::collect(repository, data, options.diff_stats);
+            markdown::render(data, &infos)
+        }
+        ExportFormat::Mermaid => mermaid::render(data),
+        ExportFormat::Dot => dot::render(data),
+    }
+}
+
+/// Fill and border colours of a node, matching the colours used in the web interface
+struct NodeColors {
+    fill: &'static str,
+    border: &'static str,
+}
+
+impl NodeColors {
+    const ACTION: NodeColors = NodeColors {
+        fill: "#ffffff",
+        border: "#a1a1aa",
+    };
+
+    fn for_state(state: graph::StatusNodeState) -> Self {
+        let (fill, border) = match state {
+            graph::StatusNodeState::Unknown => ("#f4f4f5", "#d4d4d8"),
+            graph::StatusNodeState::Progress => ("#fef3c7", "#fcd34d"),
+            graph::StatusNodeState::Fail => ("#fee2e2", "#fca5a5"),
+            graph::StatusNodeState::Success => ("#d1fae5", "#6ee7b7"),
+        };
+        Self { fill, border }
+    }
+
+    fn for_node(node: &flow::Node) -> Self {
+        match node.state() {
+            Some(state) => Self::for_state(state),
+            None => Self::ACTION,
+        }
+    }
+}
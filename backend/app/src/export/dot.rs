@@ -0,0 +1,99 @@
+use super::NodeColors;
+use crate::flow::{self, Node};
+use std::fmt::Write;
+
+/// Render `data` as a Graphviz DOT graph
+///
+/// Action nodes are rendered as boxes, status nodes as rounded boxes coloured by their state. The
+/// root node gets a thicker border.
+pub fn render(data: &flow::FlowData) -> String {
+    let state = data.reactflow();
+    let mut out = format!("digraph \"{}\" {{\n", escape(&data.name()));
+    out.push_str("    node [shape=box, fontname=\"Helvetica\"];\n");
+    out.push_str("    edge [fontname=\"Helvetica\"];\n");
+
+    for node in state.nodes() {
+        let colors = NodeColors::for_node(node);
+        let style = match node {
+            Node::Action(_) | Node::Other(_) => "filled",
+            Node::Status(_) => "rounded,filled",
+        };
+        let penwidth = if node.is_root() { ", penwidth=3" } else { "" };
+        let _ = writeln!(
+            out,
+            "    \"{}\" [label=\"{}\", style=\"{style}\", fillcolor=\"{}\", color=\"{}\"{penwidth}];",
+            escape(node.id()),
+            escape(node.title()),
+            colors.fill,
+            colors.border
+        );
+    }
+    for edge in state.edges() {
+        if state.node(&edge.source).is_none() || state.node(&edge.target).is_none() {
+            continue;
+        }
+        let label = edge
+            .label
+            .as_ref()
+            .map(|l| format!(" [label=\"{}\"]", escape(l)))
+            .unwrap_or_default();
+        let _ = writeln!(
+            out,
+            "    \"{}\" -> \"{}\"{label};",
+            escape(&edge.source),
+            escape(&edge.target)
+        );
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Escape `text` for a quoted DOT ID
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow::graph;
+
+    #[test]
+    fn test_render() {
+        let mut data = flow::FlowData::new("foo");
+        let state = data.reactflow_mut();
+        let root = state
+            .add_node(
+                None,
+                Node::new_status(
+                    graph::StatusNodeData {
+                        title: "Crash in \"main\"".to_string(),
+                        state: graph::StatusNodeState::Success,
+                        is_root_node: true,
+                        ..Default::default()
+                    },
+                    graph::Position::default(),
+                ),
+            )
+            .unwrap();
+        let action = Node::new_action(graph::ActionNodeData::default(), graph::Position::default());
+        let action = state.add_node(Some(&root), action).unwrap();
+        state.edges_mut()[0].label = Some("try".to_string());
+
+        let graph = render(&data);
+        assert!(graph.starts_with("digraph \"foo\" {\n"));
+        assert!(graph.contains(&format!(
+            "    \"{root}\" [label=\"Crash in \\\"main\\\"\", style=\"rounded,filled\", \
+            fillcolor=\"#d1fae5\", color=\"#6ee7b7\", penwidth=3];\n"
+        )));
+        assert!(graph.contains(&format!(
+            "    \"{action}\" [label=\"\", style=\"filled\", fillcolor=\"#ffffff\", color=\"#a1a1aa\"];\n"
+        )));
+        assert!(graph.contains(&format!(
+            "    \"{root}\" -> \"{action}\" [label=\"try\"];\n"
+        )));
+        assert!(graph.ends_with("}\n"));
+    }
+}
@@ -0,0 +1,231 @@
+use super::RevisionInfos;
+use crate::flow::{self, Node};
+use git2_ox::CommitProperties;
+use std::collections::HashSet;
+use std::fmt::Write;
+
+/// Deepest heading level used in the report, deeper branches are not indented any further
+const MAX_HEADING_LEVEL: usize = 6;
+
+/// Render `data` as a Markdown debugging report
+///
+/// The nodes are rendered in the order of a depth-first traversal starting at the root node.
+/// Sequences of nodes stay on the same level, every branch of a node with multiple outgoing edges
+/// is rendered as a nested section. Nodes which cannot be reached from the root node are rendered
+/// at the end of the report.
+pub fn render(data: &flow::FlowData, infos: &RevisionInfos) -> String {
+    let state = data.reactflow();
+    let mut report = Report {
+        state,
+        infos,
+        visited: HashSet::new(),
+        out: format!("# {}\n", data.name()),
+    };
+
+    let mut starts: Vec<&Node> = state.root_node().into_iter().collect();
+    starts.extend(
+        state
+            .nodes()
+            .iter()
+            .filter(|n| !n.is_root() && state.incoming_edges(n.id()).next().is_none()),
+    );
+    for node in starts {
+        report.render_path(node, 2, None);
+    }
+
+    let unreachable: Vec<_> = state
+        .nodes()
+        .iter()
+        .filter(|n| !report.visited.contains(n.id()))
+        .collect();
+    if !unreachable.is_empty() {
+        report.out.push_str("\n## Unreachable nodes\n");
+        for node in unreachable {
+            report.render_path(node, 3, None);
+        }
+    }
+    report.out
+}
+
+struct Report<'a> {
+    state: &'a flow::ReactFlowState,
+    infos: &'a RevisionInfos,
+    visited: HashSet<&'a str>,
+    out: String,
+}
+
+impl<'a> Report<'a> {
+    /// Render `node` and all nodes reachable from it which were not rendered yet
+    fn render_path(&mut self, mut node: &'a Node, level: usize, mut label: Option<&'a str>) {
+        loop {
+            if !self.visited.insert(node.id()) {
+                let _ = writeln!(self.out, "\nContinues at *{}*.", node.title());
+                return;
+            }
+            self.render_node(node, level, label);
+
+            let children: Vec<_> = self
+                .state
+                .outgoing_edges(node.id())
+                .filter_map(|e| Some((self.state.node(&e.target)?, e.label.as_deref())))
+                .collect();
+            match children.as_slice() {
+                [] => return,
+                [(child, child_label)] => {
+                    node = child;
+                    label = *child_label;
+                }
+                _ => {
+                    for (child, child_label) in children {
+                        self.render_path(child, (level + 1).min(MAX_HEADING_LEVEL), child_label);
+                    }
+                    return;
+                }
+            }
+        }
+    }
+
+    fn render_node(&mut self, node: &Node, level: usize, label: Option<&str>) {
+        let title = match node.title() {
+            "" => "Untitled",
+            title => title,
+        };
+        let _ = writeln!(self.out, "\n{} {title}\n", "#".repeat(level));
+        if let Some(label) = label {
+            let _ = writeln!(self.out, "*{label}*\n");
+        }
+
+        let kind = match node {
+            Node::Action(_) => "Action".to_string(),
+            Node::Status(n) => format!("Status: **{}**", n.data.state.as_str()),
+            Node::Other(n) => format!("Unknown node type `{}`", n.kind),
+        };
+        let _ = writeln!(self.out, "- {kind}");
+        if let Some(git) = node.git() {
+            match self.infos.commit(&git.rev) {
+                Some(commit) => {
+                    let _ = writeln!(
+                        self.out,
+                        "- Commit: `{}` {} ({}, {})",
+                        short_id(commit.id()),
+                        commit.summary(),
+                        commit.author().name(),
+                        commit.time().format("%Y-%m-%d %H:%M UTC")
+                    );
+                }
+                None => {
+                    let _ = writeln!(self.out, "- Revision: `{}` {}", git.rev, git.summary);
+                }
+            }
+            if let Some(stats) = self.infos.diff_stats(&git.rev) {
+                let _ = writeln!(
+                    self.out,
+                    "- Diff: {} files changed, {} insertions(+), {} deletions(-)",
+                    stats.files_changed(),
+                    stats.insertions(),
+                    stats.deletions()
+                );
+            }
+        }
+        let description = node.description().trim();
+        if !description.is_empty() {
+            let _ = writeln!(self.out, "\n{description}");
+        }
+    }
+}
+
+/// Get the abbreviated form of a commit ID
+fn short_id(id: &str) -> &str {
+    id.get(..7).unwrap_or(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow::{Edge, graph};
+
+    fn status_node(title: &str, state: graph::StatusNodeState, is_root: bool) -> Node {
+        Node::new_status(
+            graph::StatusNodeData {
+                title: title.to_string(),
+                state,
+                is_root_node: is_root,
+                ..Default::default()
+            },
+            graph::Position::default(),
+        )
+    }
+
+    #[test]
+    fn test_render() {
+        let mut data = flow::FlowData::new("foo");
+        let state = data.reactflow_mut();
+        let root = state
+            .add_node(
+                None,
+                status_node("Crash", graph::StatusNodeState::Fail, true),
+            )
+            .unwrap();
+        let action = Node::new_action(
+            graph::ActionNodeData {
+                title: "Revert".to_string(),
+                description: "Reverted the last commit".to_string(),
+                git: Some(graph::GitMetadata::commit("abc", "Fix it")),
+                ..Default::default()
+            },
+            graph::Position::default(),
+        );
+        let action = state.add_node(Some(&root), action).unwrap();
+        state
+            .add_node(
+                Some(&action),
+                status_node("Works", graph::StatusNodeState::Success, false),
+            )
+            .unwrap();
+        state
+            .add_node(
+                Some(&action),
+                status_node("Still slow", graph::StatusNodeState::Fail, false),
+            )
+            .unwrap();
+        state
+            .add_node(
+                None,
+                status_node("Loose", graph::StatusNodeState::Unknown, false),
+            )
+            .unwrap();
+        // A cycle back to the root node
+        state.edges_mut().push(Edge::new(&action, &root));
+
+        let report = render(&data, &RevisionInfos::default());
+        let expected = "\
+# foo
+
+## Crash
+
+- Status: **fail**
+
+## Revert
+
+- Action
+- Revision: `abc` Fix it
+
+Reverted the last commit
+
+### Works
+
+- Status: **success**
+
+### Still slow
+
+- Status: **fail**
+
+Continues at *Crash*.
+
+## Loose
+
+- Status: **unknown**
+";
+        assert_eq!(report, expected);
+    }
+}
@@ -0,0 +1,110 @@
+use super::NodeColors;
+use crate::flow::{self, Node, graph};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Render `data` as a Mermaid flowchart
+///
+/// Action nodes are rendered as rectangles, status nodes as rounded rectangles coloured by their
+/// state. The root node gets a thicker border. Node IDs are replaced by short IDs, as the IDs of
+/// the web interface are too long to be readable in the source of the chart.
+pub fn render(data: &flow::FlowData) -> String {
+    let state = data.reactflow();
+    let ids: HashMap<&str, String> = state
+        .nodes()
+        .iter()
+        .enumerate()
+        .map(|(i, n)| (n.id(), format!("n{i}")))
+        .collect();
+
+    let mut out = "flowchart TD\n".to_string();
+    for node in state.nodes() {
+        let id = &ids[node.id()];
+        let title = escape(node.title());
+        let _ = match node {
+            Node::Action(_) => writeln!(out, "    {id}[\"{title}\"]:::action"),
+            Node::Status(n) => writeln!(out, "    {id}(\"{title}\"):::{}", n.data.state.as_str()),
+            Node::Other(_) => writeln!(out, "    {id}[\"{title}\"]"),
+        };
+    }
+    for edge in state.edges() {
+        let (Some(source), Some(target)) =
+            (ids.get(edge.source.as_str()), ids.get(edge.target.as_str()))
+        else {
+            continue;
+        };
+        let _ = match &edge.label {
+            Some(label) => writeln!(out, "    {source} -->|\"{}\"| {target}", escape(label)),
+            None => writeln!(out, "    {source} --> {target}"),
+        };
+    }
+
+    let _ = writeln!(out, "    {}", class_def("action", &NodeColors::ACTION));
+    for node_state in [
+        graph::StatusNodeState::Unknown,
+        graph::StatusNodeState::Progress,
+        graph::StatusNodeState::Fail,
+        graph::StatusNodeState::Success,
+    ] {
+        let colors = NodeColors::for_state(node_state);
+        let _ = writeln!(out, "    {}", class_def(node_state.as_str(), &colors));
+    }
+    if let Some(root) = state.root_node() {
+        let _ = writeln!(out, "    style {} stroke-width:3px", ids[root.id()]);
+    }
+    out
+}
+
+fn class_def(name: &str, colors: &NodeColors) -> String {
+    format!(
+        "classDef {name} fill:{},stroke:{},color:#000000",
+        colors.fill, colors.border
+    )
+}
+
+/// Escape `text` for a quoted Mermaid label
+fn escape(text: &str) -> String {
+    text.replace('"', "#quot;").replace('\n', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let mut data = flow::FlowData::new("foo");
+        let state = data.reactflow_mut();
+        let root = state
+            .add_node(
+                None,
+                Node::new_status(
+                    graph::StatusNodeData {
+                        title: "Crash in \"main\"".to_string(),
+                        state: graph::StatusNodeState::Fail,
+                        is_root_node: true,
+                        ..Default::default()
+                    },
+                    graph::Position::default(),
+                ),
+            )
+            .unwrap();
+        let action = Node::new_action(
+            graph::ActionNodeData {
+                title: "Revert".to_string(),
+                ..Default::default()
+            },
+            graph::Position::default(),
+        );
+        state.add_node(Some(&root), action).unwrap();
+        state.edges_mut()[0].label = Some("try".to_string());
+
+        let chart = render(&data);
+        assert!(chart.starts_with("flowchart TD\n"));
+        assert!(chart.contains("    n0(\"Crash in #quot;main#quot;\"):::fail\n"));
+        assert!(chart.contains("    n1[\"Revert\"]:::action\n"));
+        assert!(chart.contains("    n0 -->|\"try\"| n1\n"));
+        assert!(chart.contains("    classDef fail fill:#fee2e2,stroke:#fca5a5,color:#000000\n"));
+        assert!(chart.ends_with("    style n0 stroke-width:3px\n"));
+    }
+}
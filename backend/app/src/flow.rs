@@ -1,20 +1,40 @@
 use crate::utils;
 use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
 use std::{ffi, fmt, fs, io, path};
 use utoipa::ToSchema;
 
+pub mod backups;
+pub mod graph;
+mod lock;
+pub mod migrations;
+
+pub use backups::FlowBackup;
+pub use graph::{Edge, GitMetadata, Node, StatusNodeState};
+pub use migrations::{FlowMigration, MigrationReport};
+
 #[derive(thiserror::Error)]
 pub enum Error {
     #[error("Path '{0}' is not a directory.")]
     NotADirectory(path::PathBuf),
     #[error("Debug flow '{0}' exists already in directory '{1}'.")]
     DebugFlowExistsAlready(String, path::PathBuf),
-    #[error("I/O error")]
+    #[error("I/O error on '{}': {}", .0.display(), .1)]
     Io(path::PathBuf, #[source] io::Error),
     #[error("Failed to read directory entry: {0}")]
     EntryError(#[from] io::Error),
     #[error("JSON error")]
     Json(#[from] serde_json::Error),
+    #[error("Flow file version {0} is newer than the supported version {1}")]
+    UnsupportedVersion(u32, u32),
+    #[error("Invalid flow file: {0}")]
+    InvalidFormat(String),
+    #[error("Node '{0}' does not exist")]
+    NodeNotFound(String),
+    #[error("Debug flow '{0}' was modified by someone else")]
+    Modified(String, Option<Box<FlowData>>),
+    #[error("Debug flows are locked by another process, remove '{}' if it crashed", .0.display())]
+    Locked(path::PathBuf),
 }
 
 impl fmt::Debug for Error {
@@ -30,10 +50,31 @@ pub struct FlowsDir {
     git_repo: path::PathBuf,
     /// Path of the debug flow directory
     path: path::PathBuf,
+    /// Number of backups to keep per debug flow
+    max_backups: usize,
+    /// Lock serializing writes of debug flows within the process, see [`FlowsDir::lock_for_write`]
+    write_lock: Arc<Mutex<()>>,
 }
 
 impl FlowsDir {
     const DIR_NAME: &str = ".debug-flow";
+    /// Name of the file storing the ID of the active debug flow
+    const ACTIVE_FLOW_FILE_NAME: &str = ".active-flow";
+    /// Default number of backups to keep per debug flow
+    pub const DEFAULT_MAX_BACKUPS: usize = 5;
+    /// Name of the file keeping local state in the debug flow directory out of git
+    const GITIGNORE_FILE_NAME: &str = ".gitignore";
+    /// Local state in the debug flow directory which is not committed with the debug flows: the
+    /// active debug flow, backups, bisection sessions, the write lock and temporary files of atomic
+    /// writes
+    const IGNORED_PATHS: &[&str] = &[
+        "/.active-flow",
+        "/.backups/",
+        "/.bisect/",
+        "/.lock",
+        ".*.tmp",
+    ];
+
     pub fn try_new<P>(git_repo: P) -> Result<FlowsDir, Error>
     where
         P: AsRef<std::path::Path>,
@@ -43,11 +84,18 @@ impl FlowsDir {
         let p = FlowsDir {
             git_repo: git_repo.to_path_buf(),
             path,
+            max_backups: Self::DEFAULT_MAX_BACKUPS,
+            write_lock: Arc::new(Mutex::new(())),
         };
-        match p.create_if_not_exists() {
-            Ok(()) => Ok(p),
-            Err(e) => Err(e),
-        }
+        p.create_if_not_exists()?;
+        p.ignore_local_state()?;
+        Ok(p)
+    }
+
+    /// Set the number of backups to keep per debug flow, `0` disables backups
+    pub fn with_max_backups(mut self, max_backups: usize) -> Self {
+        self.max_backups = max_backups;
+        self
     }
 
     /// Get a reference to the path of the debug flow dir
@@ -75,6 +123,37 @@ impl FlowsDir {
         }
     }
 
+    /// Add the paths of local state missing in the `.gitignore` of the debug flow directory, so it
+    /// is neither committed nor switched by checkouts
+    ///
+    /// Existing entries of the file are kept.
+    fn ignore_local_state(&self) -> Result<(), Error> {
+        let p = self.path.join(Self::GITIGNORE_FILE_NAME);
+        let content = match fs::read_to_string(&p) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(Error::Io(p, e)),
+        };
+        let missing: Vec<_> = Self::IGNORED_PATHS
+            .iter()
+            .filter(|ignored| !content.lines().any(|line| line.trim() == **ignored))
+            .collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        let mut updated = content;
+        if updated.is_empty() {
+            updated.push_str("# Local state of debug-flow, not shared with others\n");
+        } else if !updated.ends_with('\n') {
+            updated.push('\n');
+        }
+        for ignored in missing {
+            updated.push_str(ignored);
+            updated.push('\n');
+        }
+        utils::write_atomic(&p, updated.as_bytes()).map_err(|e| Error::Io(p, e))
+    }
+
     /// Load of create a debug flow in the storage directory
     pub fn load_or_create_flow(&self, name: &str) -> Result<Flow, Error> {
         let mut flow_path = self.path.clone();
@@ -92,9 +171,12 @@ impl FlowsDir {
         let mut flow_path = self.path.clone();
         flow_path.push(FlowData::file_name_from_flow_name(name));
 
+        // Check and create under the lock, so concurrent creations do not overwrite each other
+        let _lock = self.lock_for_write()?;
         match !flow_path.is_file() || force {
             true => {
                 let p = Flow::new(&self.path, name);
+                self.backup_flow(&p.id())?;
                 p.to_file()?;
                 Ok(p)
             }
@@ -118,10 +200,73 @@ impl FlowsDir {
     pub fn delete_flow_by_id(&self, id: &str) -> Result<(), Error> {
         let mut p = self.path.clone();
         p.push(FlowData::file_name_from_id(id));
-        fs::remove_file(p)?;
+        fs::remove_file(&p).map_err(|e| Error::Io(p, e))?;
+        if self.active_flow_id()?.as_deref() == Some(id) {
+            self.set_active_flow_id(None)?;
+        }
         Ok(())
     }
 
+    /// Get the ID of the active debug flow, commits and checkouts are logged to it by the git hooks
+    pub fn active_flow_id(&self) -> Result<Option<String>, Error> {
+        let p = self.path.join(Self::ACTIVE_FLOW_FILE_NAME);
+        match fs::read_to_string(&p) {
+            Ok(id) if id.trim().is_empty() => Ok(None),
+            Ok(id) => Ok(Some(id.trim().to_string())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(Error::Io(p, e)),
+        }
+    }
+
+    /// Set the active debug flow, `None` clears it
+    pub fn set_active_flow_id(&self, id: Option<&str>) -> Result<(), Error> {
+        let p = self.path.join(Self::ACTIVE_FLOW_FILE_NAME);
+        match id {
+            Some(id) => {
+                let flow_path = self.path.join(FlowData::file_name_from_id(id));
+                if !flow_path.is_file() {
+                    return Err(Error::Io(flow_path, io::ErrorKind::NotFound.into()));
+                }
+                utils::write_atomic(&p, id.as_bytes()).map_err(|e| Error::Io(p, e))
+            }
+            None => match fs::remove_file(&p) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(Error::Io(p, e)),
+                _ => Ok(()),
+            },
+        }
+    }
+
+    /// Rename the debug flow with ID `id` to `name`
+    ///
+    /// The ID of a debug flow is derived from its name, so the flow file is moved if the ID changes.
+    /// The state before the rename is kept as a backup of the old ID.
+    pub fn rename_flow(&self, id: &str, name: &str) -> Result<Flow, Error> {
+        let old_path = self.path.join(FlowData::file_name_from_id(id));
+        let new_path = self.path.join(FlowData::file_name_from_flow_name(name));
+
+        let _lock = self.lock_for_write()?;
+        let mut data = FlowData::from_file(&old_path)?;
+        if new_path != old_path && new_path.exists() {
+            return Err(Error::DebugFlowExistsAlready(
+                name.to_string(),
+                self.path.clone(),
+            ));
+        }
+        self.backup_flow(id)?;
+        data.name = name.to_string();
+        data.to_file(&new_path)?;
+        if new_path != old_path {
+            fs::remove_file(&old_path).map_err(|e| Error::Io(old_path, e))?;
+            if self.active_flow_id()?.as_deref() == Some(id) {
+                self.set_active_flow_id(Some(&data.id()))?;
+            }
+        }
+        Ok(Flow {
+            path: new_path,
+            data,
+        })
+    }
+
     /// Get the metadata objects for all debug flows in the debug flow directory
     pub fn metadatas(&self) -> Result<impl Iterator<Item = FlowMetadata> + '_, Error> {
         Ok(self.flows()?.filter_map(|flow| flow.try_into().ok()))
@@ -157,36 +302,224 @@ impl FlowsDir {
             }))
     }
 
+    /// Get the paths of all flow files in the debug flow directory
+    pub fn flow_files(&self) -> Result<Vec<path::PathBuf>, Error> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&self.path).map_err(|e| Error::Io(self.path.clone(), e))? {
+            let path = entry?.path();
+            if path.is_file() && path.extension() == Some(ffi::OsStr::new("json")) {
+                files.push(path);
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    /// Migrate all flow files in the debug flow directory to the current format version
+    ///
+    /// * `dry_run` - Only report which files would be migrated, do not write any files
+    pub fn migrate_flows(&self, dry_run: bool) -> Result<MigrationReport, Error> {
+        let mut report = MigrationReport {
+            dry_run,
+            flows: Vec::new(),
+        };
+        for path in self.flow_files()? {
+            // Backups are stored by the file stem, which may differ from the ID in the file
+            let id = path
+                .file_stem()
+                .map(|f| f.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let mut migration = FlowMigration {
+                file_name: path
+                    .file_name()
+                    .map(|f| f.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                from_version: None,
+                to_version: migrations::CURRENT_VERSION,
+                written: false,
+                error: None,
+            };
+            // Concurrent saves must not be lost or overwritten with the content read here
+            let _lock = self.lock_for_write()?;
+            match FlowData::read_migrated(&path) {
+                Ok((data, from_version)) => {
+                    migration.from_version = Some(from_version);
+                    if migration.is_outdated() && !dry_run {
+                        match self.backup_flow(&id).and_then(|_| data.to_file(&path)) {
+                            Ok(()) => migration.written = true,
+                            Err(e) => migration.error = Some(e.to_string()),
+                        }
+                    }
+                }
+                Err(e) => migration.error = Some(e.to_string()),
+            }
+            report.flows.push(migration);
+        }
+        Ok(report)
+    }
+
     /// Save a debug flow to the debug flow directory
+    ///
+    /// The previous state of the flow is kept as a backup.
     pub fn save_flow(&self, debug_flow: &FlowData) -> Result<(), Error> {
+        self.save_flow_if_match(debug_flow, None)?;
+        Ok(())
+    }
+
+    /// Save a debug flow to the debug flow directory if its stored state matches one of the
+    /// `expected` entity tags.
+    ///
+    /// The entity tag `*` matches any existing debug flow. If `expected` is `None`, the debug flow
+    /// is saved unconditionally. Returns the entity tag of the saved debug flow.
+    pub fn save_flow_if_match(
+        &self,
+        debug_flow: &FlowData,
+        expected: Option<&[&str]>,
+    ) -> Result<String, Error> {
         let mut p = self.path.clone();
         p.push(FlowData::file_name_from_flow_name(&debug_flow.name));
+
+        let _lock = self.lock_for_write()?;
+        if let Some(expected) = expected {
+            let current = if p.is_file() {
+                Some(FlowData::from_file(&p)?)
+            } else {
+                None
+            };
+            let matches = current.as_ref().is_some_and(|current| {
+                let etag = current.etag();
+                expected.iter().any(|e| *e == "*" || *e == etag)
+            });
+            if !matches {
+                return Err(Error::Modified(debug_flow.id(), current.map(Box::new)));
+            }
+        }
+        self.backup_flow(&debug_flow.id())?;
         debug_flow.to_file(&p)?;
-        Ok(())
+        Ok(debug_flow.etag())
+    }
+
+    /// Apply `update` to the stored debug flow with ID `id` and save it
+    ///
+    /// The debug flow cannot be saved by anyone else while `update` runs. Nothing is saved if
+    /// `update` fails.
+    pub fn update_flow<T>(
+        &self,
+        id: &str,
+        update: impl FnOnce(&mut FlowData) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let p = self.path.join(FlowData::file_name_from_id(id));
+        let _lock = self.lock_for_write()?;
+        let mut data = FlowData::from_file(&p)?;
+        let result = update(&mut data)?;
+        self.backup_flow(id)?;
+        data.to_file(&p)?;
+        Ok(result)
     }
 }
 
 #[derive(Serialize, Deserialize, ToSchema, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ReactFlowState {
-    /// Nodes of the reactflow state, the types of the nodes are managed on the frontend
-    nodes: Vec<serde_json::Value>,
-    /// Edges of the reactflow state, the types of the nodes are managed on the frontend
-    edges: Vec<serde_json::Value>,
+    /// Nodes of the reactflow state
+    nodes: Vec<Node>,
+    /// Edges of the reactflow state
+    edges: Vec<Edge>,
 }
 
 impl ReactFlowState {
+    /// Distance between a node and the nodes added below it
+    const NODE_SPACING: graph::Position = graph::Position { x: 300.0, y: 200.0 };
+
     pub fn new() -> Self {
         ReactFlowState {
             nodes: Vec::new(),
             edges: Vec::new(),
         }
     }
+
+    /// Get the nodes of the flow
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    /// Get mutable access to the nodes of the flow
+    pub fn nodes_mut(&mut self) -> &mut Vec<Node> {
+        &mut self.nodes
+    }
+
+    /// Get the edges of the flow
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    /// Get mutable access to the edges of the flow
+    pub fn edges_mut(&mut self) -> &mut Vec<Edge> {
+        &mut self.edges
+    }
+
+    /// Get the root node of the flow
+    pub fn root_node(&self) -> Option<&Node> {
+        self.nodes.iter().find(|n| n.is_root())
+    }
+
+    /// Get a node by its ID
+    pub fn node(&self, id: &str) -> Option<&Node> {
+        self.nodes.iter().find(|n| n.id() == id)
+    }
+
+    /// Get a mutable reference to a node by its ID
+    pub fn node_mut(&mut self, id: &str) -> Option<&mut Node> {
+        self.nodes.iter_mut().find(|n| n.id() == id)
+    }
+
+    /// Get an iterator over the edges starting at the node with ID `id`
+    pub fn outgoing_edges<'a>(&'a self, id: &'a str) -> impl Iterator<Item = &'a Edge> + 'a {
+        self.edges.iter().filter(move |e| e.source == id)
+    }
+
+    /// Get an iterator over the edges ending at the node with ID `id`
+    pub fn incoming_edges<'a>(&'a self, id: &'a str) -> impl Iterator<Item = &'a Edge> + 'a {
+        self.edges.iter().filter(move |e| e.target == id)
+    }
+
+    /// Get the most recently added node without outgoing edges
+    pub fn latest_leaf(&self) -> Option<&Node> {
+        self.nodes
+            .iter()
+            .rev()
+            .find(|n| self.outgoing_edges(n.id()).next().is_none())
+    }
+
+    /// Add `node` to the flow, connected to the node with ID `parent_id` if set
+    ///
+    /// The node is placed below its parent, next to the existing children of the parent. Returns
+    /// the ID of the added node.
+    pub fn add_node(&mut self, parent_id: Option<&str>, mut node: Node) -> Result<String, Error> {
+        if let Some(parent_id) = parent_id {
+            let parent = self
+                .node(parent_id)
+                .ok_or_else(|| Error::NodeNotFound(parent_id.to_string()))?;
+            let num_children = self.outgoing_edges(parent_id).count() as f64;
+            let parent_position = parent.position();
+            *node.position_mut() = graph::Position {
+                x: parent_position.x + num_children * Self::NODE_SPACING.x,
+                y: parent_position.y + Self::NODE_SPACING.y,
+            };
+            self.edges.push(Edge::new(parent_id, node.id()));
+        }
+        let id = node.id().to_string();
+        self.nodes.push(node);
+        Ok(id)
+    }
 }
 
 #[derive(Serialize, Deserialize, ToSchema, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct FlowData {
+    /// Version of the flow file format
+    #[serde(default = "FlowData::current_version")]
+    version: u32,
     /// Name of the debug flow
     name: String,
     /// Representation of the reactflow state
@@ -196,11 +529,35 @@ pub struct FlowData {
 impl FlowData {
     pub fn new(name: &str) -> Self {
         FlowData {
+            version: migrations::CURRENT_VERSION,
             name: name.to_string(),
             reactflow: ReactFlowState::new(),
         }
     }
 
+    /// Get the entity tag of the debug flow, it changes whenever the content of the flow changes
+    ///
+    /// The tag is the git blob ID of the flow in the current file format, so it stays the same
+    /// across restarts and versions of `debug-flow`.
+    pub fn etag(&self) -> String {
+        let data = FlowData {
+            version: migrations::CURRENT_VERSION,
+            ..self.clone()
+        };
+        let json = serde_json::to_vec(&data).unwrap_or_default();
+        let id = git2::Oid::hash_object(git2::ObjectType::Blob, &json).unwrap_or(git2::Oid::zero());
+        format!("\"{id}\"")
+    }
+
+    fn current_version() -> u32 {
+        migrations::CURRENT_VERSION
+    }
+
+    /// Get the version of the flow file format
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
     /// Get the ID of the debug flow
     pub fn id(&self) -> String {
         utils::to_kebab_case(self.name.as_str())
@@ -211,10 +568,17 @@ impl FlowData {
         self.name.clone()
     }
 
-    /// Store the debug flow data to a file
-    pub fn to_file(&self, path: &path::PathBuf) -> std::result::Result<(), Error> {
-        let json_content = serde_json::to_string(self)?;
-        std::fs::write(path, json_content)?;
+    /// Store the debug flow data to a file, the file is always written in the current format version
+    ///
+    /// The file is replaced atomically, a failed write never leaves a truncated file behind.
+    pub fn to_file(&self, path: &path::Path) -> std::result::Result<(), Error> {
+        let data = FlowData {
+            version: migrations::CURRENT_VERSION,
+            ..self.clone()
+        };
+        let json_content = serde_json::to_string(&data)?;
+        utils::write_atomic(path, json_content.as_bytes())
+            .map_err(|e| Error::Io(path.to_path_buf(), e))?;
         Ok(())
     }
 
@@ -230,10 +594,29 @@ impl FlowData {
         p
     }
 
-    /// Create debug flow data from a file
-    pub fn from_file(path: &path::PathBuf) -> Result<FlowData, Error> {
-        let file_content = std::fs::read_to_string(path)?;
-        serde_json::from_str(&file_content).map_err(Error::Json)
+    /// Create debug flow data from a file, files in older format versions are migrated on load
+    pub fn from_file(path: &path::Path) -> Result<FlowData, Error> {
+        Ok(FlowData::read_migrated(path)?.0)
+    }
+
+    /// Read debug flow data from a file and migrate it to the current format version
+    ///
+    /// Returns the data and the format version of the file.
+    fn read_migrated(path: &path::Path) -> Result<(FlowData, u32), Error> {
+        let file_content =
+            std::fs::read_to_string(path).map_err(|e| Error::Io(path.to_path_buf(), e))?;
+        let (value, from_version) = migrations::migrate(serde_json::from_str(&file_content)?)?;
+        Ok((serde_json::from_value(value)?, from_version))
+    }
+
+    /// Get the reactflow state of the debug flow
+    pub fn reactflow(&self) -> &ReactFlowState {
+        &self.reactflow
+    }
+
+    /// Get mutable access to the reactflow state of the debug flow
+    pub fn reactflow_mut(&mut self) -> &mut ReactFlowState {
+        &mut self.reactflow
     }
 
     /// Get the number of nodes in the diagram
@@ -286,9 +669,9 @@ impl Flow {
         })
     }
 
-    pub fn from_file(path: &path::PathBuf) -> Result<Flow, Error> {
+    pub fn from_file(path: &path::Path) -> Result<Flow, Error> {
         Ok(Flow {
-            path: path.clone(),
+            path: path.to_path_buf(),
             data: FlowData::from_file(path)?,
         })
     }
@@ -336,6 +719,33 @@ pub struct FlowMetadata {
     num_edges: usize,
 }
 
+impl FlowMetadata {
+    /// Get the ID of the debug flow
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Get the name of the debug flow
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the last modified date of the debug flow
+    pub fn last_modified_date(&self) -> chrono::DateTime<chrono::Utc> {
+        self.last_modified_date
+    }
+
+    /// Get the number of nodes in the debug flow
+    pub fn num_nodes(&self) -> usize {
+        self.num_nodes
+    }
+
+    /// Get the number of edges in the debug flow
+    pub fn num_edges(&self) -> usize {
+        self.num_edges
+    }
+}
+
 impl TryFrom<Flow> for FlowMetadata {
     type Error = Error;
 
@@ -362,4 +772,193 @@ mod tests {
             path::Path::new("hello-world.json")
         );
     }
+
+    #[test]
+    fn test_local_state_is_ignored() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init(temp_dir.path()).unwrap();
+        let flows_dir = FlowsDir::try_new(temp_dir.path()).unwrap();
+        let gitignore = flows_dir.path().join(FlowsDir::GITIGNORE_FILE_NAME);
+        assert!(gitignore.is_file());
+
+        let ignored = |p: &str| repo.is_path_ignored(p).unwrap();
+        assert!(ignored(
+            ".debug-flow/.backups/foo/20250101T000000000000000Z.json"
+        ));
+        assert!(ignored(".debug-flow/.bisect/foo.json"));
+        assert!(ignored(".debug-flow/.active-flow"));
+        assert!(ignored(".debug-flow/.lock"));
+        assert!(ignored(".debug-flow/.foo.json.123.tmp"));
+        assert!(!ignored(".debug-flow/foo.json"));
+
+        // Entries added by the user are kept and nothing is added twice
+        fs::write(&gitignore, "custom/").unwrap();
+        FlowsDir::try_new(temp_dir.path()).unwrap();
+        FlowsDir::try_new(temp_dir.path()).unwrap();
+        let content = fs::read_to_string(&gitignore).unwrap();
+        assert!(content.starts_with("custom/\n"));
+        for path in FlowsDir::IGNORED_PATHS {
+            assert_eq!(content.lines().filter(|l| l == path).count(), 1);
+        }
+    }
+
+    #[test]
+    fn test_migrate_flows() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let flows_dir = FlowsDir::try_new(temp_dir.path()).unwrap();
+        let flow_path = flows_dir.path().join("foo.json");
+        fs::write(
+            &flow_path,
+            r#"{"name":"foo","reactflow":{"nodes":[],"edges":[]}}"#,
+        )
+        .unwrap();
+
+        let report = flows_dir.migrate_flows(true).unwrap();
+        assert_eq!(report.flows.len(), 1);
+        assert!(report.flows[0].is_outdated());
+        assert!(!report.flows[0].written);
+
+        let report = flows_dir.migrate_flows(false).unwrap();
+        assert!(report.flows[0].written);
+        let data = FlowData::from_file(&flow_path).unwrap();
+        assert_eq!(data.version(), migrations::CURRENT_VERSION);
+        assert_eq!(flows_dir.list_backups("foo").unwrap().len(), 1);
+
+        // Backups of files not named after the ID of their flow are found by the file name
+        let renamed_path = flows_dir.path().join("bar.json");
+        fs::write(
+            &renamed_path,
+            r#"{"name":"foo","reactflow":{"nodes":[],"edges":[]}}"#,
+        )
+        .unwrap();
+        flows_dir.migrate_flows(false).unwrap();
+        assert_eq!(flows_dir.list_backups("bar").unwrap().len(), 1);
+        fs::remove_file(&renamed_path).unwrap();
+
+        let report = flows_dir.migrate_flows(false).unwrap();
+        assert!(!report.flows[0].is_outdated());
+    }
+
+    #[test]
+    fn test_save_flow_if_match() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let flows_dir = FlowsDir::try_new(temp_dir.path()).unwrap();
+        let mut flow = FlowData::new("foo");
+        assert!(matches!(
+            flows_dir.save_flow_if_match(&flow, Some(&["*"])),
+            Err(Error::Modified(_, None))
+        ));
+        let etag = flows_dir.save_flow_if_match(&flow, None).unwrap();
+
+        flow.reactflow_mut().edges_mut().push(Edge::new("a", "b"));
+        let new_etag = flows_dir
+            .save_flow_if_match(&flow, Some(&[etag.as_str()]))
+            .unwrap();
+        assert_ne!(etag, new_etag);
+
+        // Saving based on the outdated entity tag fails and reports the stored state
+        match flows_dir.save_flow_if_match(&flow, Some(&[etag.as_str()])) {
+            Err(Error::Modified(_, Some(current))) => assert_eq!(current.etag(), new_etag),
+            _ => panic!("Expected a modification error"),
+        }
+        // Weak entity tags never match
+        let weak_etag = format!("W/{new_etag}");
+        assert!(matches!(
+            flows_dir.save_flow_if_match(&flow, Some(&[weak_etag.as_str()])),
+            Err(Error::Modified(_, Some(_)))
+        ));
+    }
+
+    #[test]
+    fn test_etag_is_stable() {
+        let flow = FlowData::new("foo");
+        let json = serde_json::to_vec(&flow).unwrap();
+        let blob_id = git2::Oid::hash_object(git2::ObjectType::Blob, &json).unwrap();
+        assert_eq!(flow.etag(), format!("\"{blob_id}\""));
+        assert_eq!(flow.etag(), FlowData::new("foo").etag());
+        assert_ne!(flow.etag(), FlowData::new("bar").etag());
+    }
+
+    #[test]
+    fn test_rename_flow() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let flows_dir = FlowsDir::try_new(temp_dir.path()).unwrap();
+        flows_dir.create_flow("foo", false).unwrap();
+        flows_dir.create_flow("bar", false).unwrap();
+
+        assert!(matches!(
+            flows_dir.rename_flow("foo", "Bar"),
+            Err(Error::DebugFlowExistsAlready(_, _))
+        ));
+        let flow = flows_dir.rename_flow("foo", "Foo Baz").unwrap();
+        assert_eq!(flow.id(), "foo-baz");
+        assert_eq!(
+            flows_dir.get_flow_by_id("foo-baz").unwrap().name(),
+            "Foo Baz"
+        );
+        assert!(matches!(
+            flows_dir.get_flow_by_id("foo"),
+            Err(Error::Io(_, e)) if e.kind() == io::ErrorKind::NotFound
+        ));
+        // Renaming without changing the ID only changes the name
+        let flow = flows_dir.rename_flow("foo-baz", "foo baz").unwrap();
+        assert_eq!(flow.id(), "foo-baz");
+        assert_eq!(flow.name(), "foo baz");
+    }
+
+    #[test]
+    fn test_active_flow() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let flows_dir = FlowsDir::try_new(temp_dir.path()).unwrap();
+        assert_eq!(flows_dir.active_flow_id().unwrap(), None);
+        assert!(flows_dir.set_active_flow_id(Some("foo")).is_err());
+
+        flows_dir.create_flow("foo", false).unwrap();
+        flows_dir.set_active_flow_id(Some("foo")).unwrap();
+        assert_eq!(flows_dir.active_flow_id().unwrap().as_deref(), Some("foo"));
+
+        // The active flow follows renames and is cleared when the flow is deleted
+        flows_dir.rename_flow("foo", "bar").unwrap();
+        assert_eq!(flows_dir.active_flow_id().unwrap().as_deref(), Some("bar"));
+        flows_dir.delete_flow_by_id("bar").unwrap();
+        assert_eq!(flows_dir.active_flow_id().unwrap(), None);
+    }
+
+    #[test]
+    fn test_add_node() {
+        let mut state = ReactFlowState::new();
+        let data = graph::StatusNodeData::default();
+        let root = state
+            .add_node(
+                None,
+                Node::new_status(data.clone(), graph::Position::default()),
+            )
+            .unwrap();
+        assert!(root.starts_with("status-node-"));
+        let first = state
+            .add_node(
+                Some(&root),
+                Node::new_status(data.clone(), graph::Position::default()),
+            )
+            .unwrap();
+        let second = state
+            .add_node(
+                Some(&root),
+                Node::new_status(data.clone(), graph::Position::default()),
+            )
+            .unwrap();
+        assert_eq!(state.outgoing_edges(&root).count(), 2);
+        assert_eq!(state.latest_leaf().unwrap().id(), second);
+        assert_eq!(state.incoming_edges(&first).count(), 1);
+        assert!(
+            state.node(&second).unwrap().position().x > state.node(&first).unwrap().position().x
+        );
+        assert!(matches!(
+            state.add_node(
+                Some("missing"),
+                Node::new_status(data, graph::Position::default())
+            ),
+            Err(Error::NodeNotFound(_))
+        ));
+    }
 }
@@ -0,0 +1,159 @@
+use super::{Error, FlowData, FlowsDir};
+use serde::Serialize;
+use std::{ffi, fs, io, path};
+use utoipa::ToSchema;
+
+/// Format of the backup IDs, sortable in chronological order
+const BACKUP_ID_FORMAT: &str = "%Y%m%dT%H%M%S%9fZ";
+
+/// Metadata of a backup of a debug flow
+#[derive(Serialize, ToSchema, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FlowBackup {
+    /// ID of the backup
+    id: String,
+    /// Date at which the backup was created
+    created_date: chrono::DateTime<chrono::Utc>,
+    /// Size of the backup file in bytes
+    size: u64,
+}
+
+impl FlowBackup {
+    /// Get the ID of the backup
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Get the creation date of the backup
+    pub fn created_date(&self) -> chrono::DateTime<chrono::Utc> {
+        self.created_date
+    }
+
+    fn try_from_path(path: &path::Path) -> Option<Self> {
+        let id = path.file_stem()?.to_str()?.to_string();
+        let created_date = chrono::NaiveDateTime::parse_from_str(&id, BACKUP_ID_FORMAT)
+            .ok()?
+            .and_utc();
+        let size = fs::metadata(path).ok()?.len();
+        Some(Self {
+            id,
+            created_date,
+            size,
+        })
+    }
+}
+
+impl FlowsDir {
+    const BACKUPS_DIR_NAME: &str = ".backups";
+
+    /// Get the directory containing the backups of the flow with ID `id`
+    fn backups_path(&self, id: &str) -> path::PathBuf {
+        let mut p = self.path.join(Self::BACKUPS_DIR_NAME);
+        p.push(id);
+        p
+    }
+
+    /// Get the path of the backup `backup_id` of the flow with ID `id`
+    fn backup_path(&self, id: &str, backup_id: &str) -> path::PathBuf {
+        let mut p = self.backups_path(id);
+        p.push(format!("{backup_id}.json"));
+        p
+    }
+
+    /// Copy the current file of the flow with ID `id` to its backups and drop the oldest backups
+    /// exceeding the configured number of backups.
+    ///
+    /// Nothing is done if the flow file does not exist yet or backups are disabled.
+    pub(super) fn backup_flow(&self, id: &str) -> Result<(), Error> {
+        let flow_path = self.path.join(FlowData::file_name_from_id(id));
+        if self.max_backups == 0 || !flow_path.is_file() {
+            return Ok(());
+        }
+        let backups_path = self.backups_path(id);
+        fs::create_dir_all(&backups_path).map_err(|e| Error::Io(backups_path.clone(), e))?;
+
+        let backup_id = chrono::Utc::now().format(BACKUP_ID_FORMAT).to_string();
+        let backup_path = self.backup_path(id, &backup_id);
+        fs::copy(&flow_path, &backup_path).map_err(|e| Error::Io(backup_path, e))?;
+
+        let backups = self.list_backups(id)?;
+        let num_outdated = backups.len().saturating_sub(self.max_backups);
+        for backup in backups.iter().rev().take(num_outdated) {
+            let p = self.backup_path(id, backup.id());
+            if let Err(e) = fs::remove_file(&p) {
+                log::warn!("Failed to remove outdated backup '{}': {e}", p.display());
+            }
+        }
+        Ok(())
+    }
+
+    /// List the backups of the flow with ID `id`, newest first
+    pub fn list_backups(&self, id: &str) -> Result<Vec<FlowBackup>, Error> {
+        let backups_path = self.backups_path(id);
+        let entries = match fs::read_dir(&backups_path) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(Error::Io(backups_path, e)),
+        };
+        let mut backups = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension() != Some(ffi::OsStr::new("json")) {
+                continue;
+            }
+            if let Some(backup) = FlowBackup::try_from_path(&path) {
+                backups.push(backup);
+            }
+        }
+        backups.sort_by(|a, b| b.id.cmp(&a.id));
+        Ok(backups)
+    }
+
+    /// Restore the backup `backup_id` of the flow with ID `id`
+    ///
+    /// The current state of the flow is backed up before it is replaced.
+    pub fn restore_backup(&self, id: &str, backup_id: &str) -> Result<FlowData, Error> {
+        let backup_path = self.backup_path(id, backup_id);
+        // Only accept IDs created by `backup_flow`, this also rejects paths escaping the backups
+        if chrono::NaiveDateTime::parse_from_str(backup_id, BACKUP_ID_FORMAT).is_err() {
+            return Err(Error::Io(backup_path, io::ErrorKind::NotFound.into()));
+        }
+        // Backups are rotated under the lock, read the backup before it can be removed
+        let _lock = self.lock_for_write()?;
+        let data = FlowData::from_file(&backup_path)?;
+        self.backup_flow(id)?;
+        let flow_path = self.path.join(FlowData::file_name_from_id(id));
+        data.to_file(&flow_path)?;
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow::Edge;
+
+    #[test]
+    fn test_backup_rotation_and_restore() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let flows_dir = FlowsDir::try_new(temp_dir.path())
+            .unwrap()
+            .with_max_backups(2);
+        let mut flow = FlowData::new("foo");
+        flows_dir.save_flow(&flow).unwrap();
+        assert!(flows_dir.list_backups("foo").unwrap().is_empty());
+
+        for _ in 0..3 {
+            flow.reactflow_mut().edges_mut().push(Edge::new("a", "b"));
+            flows_dir.save_flow(&flow).unwrap();
+        }
+        let backups = flows_dir.list_backups("foo").unwrap();
+        assert_eq!(backups.len(), 2);
+
+        // The newest backup contains the state before the last save
+        let restored = flows_dir.restore_backup("foo", backups[0].id()).unwrap();
+        assert_eq!(restored.num_edges(), 2);
+        assert_eq!(flows_dir.get_flow_by_id("foo").unwrap().num_edges(), 2);
+        assert_eq!(flows_dir.list_backups("foo").unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,459 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
+
+/// Kind of the revision a node is linked to
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GitMetadataKind {
+    Commit,
+    Tag,
+    Branch,
+}
+
+/// Git revision a node is linked to
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GitMetadata {
+    /// Commit ID, tag name or branch name
+    pub rev: String,
+    /// Summary of the referenced commit, for branches this is the summary of the branch head
+    pub summary: String,
+    /// Kind of the revision
+    #[serde(rename = "type")]
+    pub kind: GitMetadataKind,
+}
+
+impl GitMetadata {
+    /// Create git metadata pointing at a single commit
+    pub fn commit(id: &str, summary: &str) -> Self {
+        Self {
+            rev: id.to_string(),
+            summary: summary.to_string(),
+            kind: GitMetadataKind::Commit,
+        }
+    }
+}
+
+/// State of a status node
+#[derive(
+    Serialize, Deserialize, ToSchema, clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum StatusNodeState {
+    #[default]
+    Unknown,
+    Progress,
+    Fail,
+    Success,
+}
+
+impl StatusNodeState {
+    /// Get the name of the state as used in the flow files
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StatusNodeState::Unknown => "unknown",
+            StatusNodeState::Progress => "progress",
+            StatusNodeState::Fail => "fail",
+            StatusNodeState::Success => "success",
+        }
+    }
+}
+
+/// Position of a node on the canvas
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, Debug, Default, PartialEq)]
+pub struct Position {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// Data of an action node
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionNodeData {
+    /// Title of the node
+    #[serde(default)]
+    pub title: String,
+    /// Markdown description of the node
+    #[serde(default)]
+    pub description: String,
+    /// Git revision the action was performed on
+    #[serde(default)]
+    pub git: Option<GitMetadata>,
+    /// Fields managed by the frontend only, kept to store them back unchanged
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+/// Data of a status node
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusNodeData {
+    /// Title of the node
+    #[serde(default)]
+    pub title: String,
+    /// State of the node
+    #[serde(default)]
+    pub state: StatusNodeState,
+    /// Markdown description of the node
+    #[serde(default)]
+    pub description: String,
+    /// Git revision the status was observed on
+    #[serde(default)]
+    pub git: Option<GitMetadata>,
+    /// Whether the node is the root node of the flow
+    #[serde(default)]
+    pub is_root_node: bool,
+    /// Fields managed by the frontend only, kept to store them back unchanged
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+/// Node describing an action taken during debugging
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionNode {
+    /// ID of the node
+    pub id: String,
+    /// Position of the node on the canvas
+    #[serde(default)]
+    pub position: Position,
+    /// Data of the node
+    pub data: ActionNodeData,
+    /// Fields managed by the frontend only, kept to store them back unchanged
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+/// Node describing an observed status during debugging
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusNode {
+    /// ID of the node
+    pub id: String,
+    /// Position of the node on the canvas
+    #[serde(default)]
+    pub position: Position,
+    /// Data of the node
+    pub data: StatusNodeData,
+    /// Fields managed by the frontend only, kept to store them back unchanged
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+/// Data of a node of an unknown type
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OtherNodeData {
+    /// Title of the node
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub title: String,
+    /// Markdown description of the node
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    /// Git revision the node is linked to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git: Option<GitMetadata>,
+    /// Fields managed by the frontend only, kept to store them back unchanged
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+/// Node of a type unknown to the backend, e.g. added by a newer frontend, stored back unchanged
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OtherNode {
+    /// Type of the node
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// ID of the node
+    pub id: String,
+    /// Position of the node on the canvas
+    #[serde(default)]
+    pub position: Position,
+    /// Data of the node
+    #[serde(default)]
+    pub data: OtherNodeData,
+    /// Fields managed by the frontend only, kept to store them back unchanged
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+/// Node of a debug flow
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type")]
+pub enum Node {
+    #[serde(rename = "actionNode")]
+    Action(ActionNode),
+    #[serde(rename = "statusNode")]
+    Status(StatusNode),
+    /// Node of an unknown type, a flow with such nodes still loads
+    #[serde(untagged)]
+    Other(OtherNode),
+}
+
+// Written by hand as the derive does not support untagged variants of tagged enums
+impl utoipa::PartialSchema for Node {
+    fn schema() -> utoipa::openapi::RefOr<utoipa::openapi::schema::Schema> {
+        use utoipa::openapi::Ref;
+        use utoipa::openapi::schema::{AllOfBuilder, ObjectBuilder, OneOfBuilder, Type};
+
+        let tagged = |name: &str, kind: &str| {
+            AllOfBuilder::new().item(Ref::from_schema_name(name)).item(
+                ObjectBuilder::new()
+                    .property(
+                        "type",
+                        ObjectBuilder::new()
+                            .schema_type(Type::String)
+                            .enum_values(Some([kind])),
+                    )
+                    .required("type"),
+            )
+        };
+        OneOfBuilder::new()
+            .item(tagged(&ActionNode::name(), "actionNode"))
+            .item(tagged(&StatusNode::name(), "statusNode"))
+            .item(Ref::from_schema_name(OtherNode::name()))
+            .description(Some("Node of a debug flow"))
+            .into()
+    }
+}
+
+impl ToSchema for Node {
+    fn schemas(
+        schemas: &mut Vec<(
+            String,
+            utoipa::openapi::RefOr<utoipa::openapi::schema::Schema>,
+        )>,
+    ) {
+        use utoipa::PartialSchema;
+
+        schemas.push((ActionNode::name().into(), ActionNode::schema()));
+        ActionNode::schemas(schemas);
+        schemas.push((StatusNode::name().into(), StatusNode::schema()));
+        StatusNode::schemas(schemas);
+        schemas.push((OtherNode::name().into(), OtherNode::schema()));
+        OtherNode::schemas(schemas);
+    }
+}
+
+impl Node {
+    /// Create a new action node with a random ID
+    pub fn new_action(data: ActionNodeData, position: Position) -> Self {
+        Node::Action(ActionNode {
+            id: format!("action-node-{}", uuid::Uuid::new_v4()),
+            position,
+            data,
+            extra: BTreeMap::new(),
+        })
+    }
+
+    /// Create a new status node with a random ID
+    pub fn new_status(data: StatusNodeData, position: Position) -> Self {
+        Node::Status(StatusNode {
+            id: format!("status-node-{}", uuid::Uuid::new_v4()),
+            position,
+            data,
+            extra: BTreeMap::new(),
+        })
+    }
+
+    /// Get the ID of the node
+    pub fn id(&self) -> &str {
+        match self {
+            Node::Action(n) => &n.id,
+            Node::Status(n) => &n.id,
+            Node::Other(n) => &n.id,
+        }
+    }
+
+    /// Get the position of the node
+    pub fn position(&self) -> Position {
+        match self {
+            Node::Action(n) => n.position,
+            Node::Status(n) => n.position,
+            Node::Other(n) => n.position,
+        }
+    }
+
+    /// Get a mutable reference to the position of the node
+    pub fn position_mut(&mut self) -> &mut Position {
+        match self {
+            Node::Action(n) => &mut n.position,
+            Node::Status(n) => &mut n.position,
+            Node::Other(n) => &mut n.position,
+        }
+    }
+
+    /// Get the title of the node
+    pub fn title(&self) -> &str {
+        match self {
+            Node::Action(n) => &n.data.title,
+            Node::Status(n) => &n.data.title,
+            Node::Other(n) => &n.data.title,
+        }
+    }
+
+    /// Get the Markdown description of the node
+    pub fn description(&self) -> &str {
+        match self {
+            Node::Action(n) => &n.data.description,
+            Node::Status(n) => &n.data.description,
+            Node::Other(n) => &n.data.description,
+        }
+    }
+
+    /// Get a mutable reference to the Markdown description of the node
+    pub fn description_mut(&mut self) -> &mut String {
+        match self {
+            Node::Action(n) => &mut n.data.description,
+            Node::Status(n) => &mut n.data.description,
+            Node::Other(n) => &mut n.data.description,
+        }
+    }
+
+    /// Get the git revision the node is linked to
+    pub fn git(&self) -> Option<&GitMetadata> {
+        match self {
+            Node::Action(n) => n.data.git.as_ref(),
+            Node::Status(n) => n.data.git.as_ref(),
+            Node::Other(n) => n.data.git.as_ref(),
+        }
+    }
+
+    /// Get a mutable reference to the git revision the node is linked to
+    pub fn git_mut(&mut self) -> &mut Option<GitMetadata> {
+        match self {
+            Node::Action(n) => &mut n.data.git,
+            Node::Status(n) => &mut n.data.git,
+            Node::Other(n) => &mut n.data.git,
+        }
+    }
+
+    /// Get the state of the node, `None` for action nodes and nodes of unknown types
+    pub fn state(&self) -> Option<StatusNodeState> {
+        match self {
+            Node::Action(_) | Node::Other(_) => None,
+            Node::Status(n) => Some(n.data.state),
+        }
+    }
+
+    /// Whether this node is the root node of the flow
+    pub fn is_root(&self) -> bool {
+        match self {
+            Node::Action(_) | Node::Other(_) => false,
+            Node::Status(n) => n.data.is_root_node,
+        }
+    }
+}
+
+/// Edge connecting two nodes of a debug flow
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Edge {
+    /// ID of the edge
+    pub id: String,
+    /// ID of the source node
+    pub source: String,
+    /// ID of the target node
+    pub target: String,
+    /// ID of the handle on the source node
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_handle: Option<String>,
+    /// ID of the handle on the target node
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_handle: Option<String>,
+    /// Label of the edge
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Fields managed by the frontend only, kept to store them back unchanged
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+impl Edge {
+    /// Create a new edge with a random ID from `source` to `target`
+    pub fn new(source: &str, target: &str) -> Self {
+        Self {
+            id: format!("edge-{}", uuid::Uuid::new_v4()),
+            source: source.to_string(),
+            target: target.to_string(),
+            source_handle: None,
+            target_handle: None,
+            label: None,
+            extra: BTreeMap::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_node_round_trip() {
+        let json = serde_json::json!({
+            "id": "1",
+            "type": "statusNode",
+            "position": { "x": 1.0, "y": 2.0 },
+            "data": {
+                "title": "Root",
+                "state": "fail",
+                "description": "It crashes",
+                "git": { "rev": "abc", "summary": "Initial", "type": "commit" },
+                "isRootNode": true,
+                "unknownData": 1
+            },
+            "measured": { "width": 10, "height": 20 },
+            "selected": false
+        });
+        let node: Node = serde_json::from_value(json.clone()).unwrap();
+        assert!(node.is_root());
+        assert_eq!(node.state(), Some(StatusNodeState::Fail));
+        assert_eq!(node.git().unwrap().rev, "abc");
+        assert_eq!(serde_json::to_value(&node).unwrap(), json);
+    }
+
+    #[test]
+    fn test_unknown_node_round_trip() {
+        let json = serde_json::json!({
+            "id": "2",
+            "type": "noteNode",
+            "position": { "x": 1.0, "y": 2.0 },
+            "data": { "text": "Check the logs", "color": "yellow" },
+            "selected": true
+        });
+        let node: Node = serde_json::from_value(json.clone()).unwrap();
+        assert!(matches!(&node, Node::Other(n) if n.kind == "noteNode"));
+        assert_eq!(node.id(), "2");
+        assert_eq!(node.title(), "");
+        assert_eq!(node.state(), None);
+        assert_eq!(serde_json::to_value(&node).unwrap(), json);
+
+        // Known nodes without a title load too
+        let json = serde_json::json!({
+            "id": "3",
+            "type": "actionNode",
+            "position": { "x": 0.0, "y": 0.0 },
+            "data": {}
+        });
+        let node: Node = serde_json::from_value(json).unwrap();
+        assert!(matches!(&node, Node::Action(_)));
+        assert_eq!(node.title(), "");
+    }
+
+    #[test]
+    fn test_edge_round_trip() {
+        let json = serde_json::json!({
+            "id": "e1-2",
+            "source": "1",
+            "target": "2",
+            "sourceHandle": "a",
+            "type": "smoothstep",
+            "animated": true
+        });
+        let edge: Edge = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(edge.source, "1");
+        assert_eq!(serde_json::to_value(&edge).unwrap(), json);
+    }
+}
@@ -0,0 +1,188 @@
+use super::{Error, FlowsDir};
+use std::sync::MutexGuard;
+use std::{fs, io, path, thread, time};
+
+/// Name of the lock file in the debug flow directory
+pub(super) const LOCK_FILE_NAME: &str = ".lock";
+/// Time to wait for another process to release the lock
+const LOCK_TIMEOUT: time::Duration = time::Duration::from_secs(5);
+/// Interval at which a held lock is checked again
+const LOCK_RETRY_INTERVAL: time::Duration = time::Duration::from_millis(10);
+/// Age after which a lock is considered left behind by a crashed process, writes take far less
+const LOCK_STALE_AFTER: time::Duration = time::Duration::from_secs(30);
+
+/// Lock of the debug flow directory for writes, released when dropped
+///
+/// Writes are serialized by a mutex within the process and by a lock file across processes, e.g.
+/// the server, the command line and git hooks.
+pub(super) struct WriteLock<'a> {
+    path: path::PathBuf,
+    _guard: MutexGuard<'a, ()>,
+}
+
+impl Drop for WriteLock<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            log::warn!("Failed to remove lock file '{}': {e}", self.path.display());
+        }
+    }
+}
+
+impl FlowsDir {
+    /// Lock the debug flow directory for writes, waiting for other writers to finish
+    pub(super) fn lock_for_write(&self) -> Result<WriteLock<'_>, Error> {
+        let guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let path = self.path.join(LOCK_FILE_NAME);
+        let started = time::Instant::now();
+        loop {
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(_) => {
+                    return Ok(WriteLock {
+                        path,
+                        _guard: guard,
+                    });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    if is_stale(&path) {
+                        log::warn!("Removing stale lock file '{}'", path.display());
+                        let _ = fs::remove_file(&path);
+                        continue;
+                    }
+                    if started.elapsed() > LOCK_TIMEOUT {
+                        return Err(Error::Locked(path));
+                    }
+                    thread::sleep(LOCK_RETRY_INTERVAL);
+                }
+                Err(e) => return Err(Error::Io(path, e)),
+            }
+        }
+    }
+
+    /// Run `f` on the debug flow directory in a thread for blocking tasks
+    ///
+    /// Writes wait for other writers, async code runs them through this function to not block the
+    /// threads of the async runtime. Panics of `f` are passed on.
+    pub async fn blocking<T, E, F>(&self, f: F) -> Result<T, E>
+    where
+        F: FnOnce(&FlowsDir) -> Result<T, E> + Send + 'static,
+        T: Send + 'static,
+        E: Send + 'static,
+    {
+        let flows_dir = self.clone();
+        match tokio::task::spawn_blocking(move || f(&flows_dir)).await {
+            Ok(result) => result,
+            Err(e) => std::panic::resume_unwind(e.into_panic()),
+        }
+    }
+}
+
+/// Check whether the lock file at `path` was left behind by a crashed process
+fn is_stale(path: &path::Path) -> bool {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age > LOCK_STALE_AFTER)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_for_write() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let flows_dir = FlowsDir::try_new(temp_dir.path()).unwrap();
+        let lock_path = flows_dir.path().join(LOCK_FILE_NAME);
+
+        let lock = flows_dir.lock_for_write().unwrap();
+        assert!(lock_path.is_file());
+        drop(lock);
+        assert!(!lock_path.exists());
+
+        // A lock file of another process blocks writes until it is released
+        fs::write(&lock_path, "").unwrap();
+        let flow = crate::flow::FlowData::new("foo");
+        let releaser = {
+            let lock_path = lock_path.clone();
+            thread::spawn(move || {
+                thread::sleep(time::Duration::from_millis(100));
+                fs::remove_file(lock_path).unwrap();
+            })
+        };
+        let started = time::Instant::now();
+        flows_dir.save_flow(&flow).unwrap();
+        assert!(started.elapsed() >= time::Duration::from_millis(100));
+        releaser.join().unwrap();
+
+        // Lock files left behind by crashed processes are ignored
+        let stale = fs::File::create(&lock_path).unwrap();
+        stale
+            .set_modified(time::SystemTime::now() - 2 * LOCK_STALE_AFTER)
+            .unwrap();
+        flows_dir.save_flow(&flow).unwrap();
+        assert!(!lock_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_blocking() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let flows_dir = FlowsDir::try_new(temp_dir.path()).unwrap();
+        let lock_path = flows_dir.path().join(LOCK_FILE_NAME);
+
+        // The lock is released by a task on the same single threaded runtime, which only runs if
+        // waiting for the lock does not block the runtime
+        fs::write(&lock_path, "").unwrap();
+        let releaser = tokio::spawn(async move {
+            tokio::time::sleep(time::Duration::from_millis(100)).await;
+            fs::remove_file(lock_path).unwrap();
+        });
+        let flow = crate::flow::FlowData::new("foo");
+        flows_dir
+            .blocking(move |flows_dir| flows_dir.save_flow(&flow))
+            .await
+            .unwrap();
+        releaser.await.unwrap();
+        assert!(flows_dir.get_flow_by_id("foo").is_ok());
+    }
+
+    #[test]
+    fn test_concurrent_updates() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let flow_id = FlowsDir::try_new(temp_dir.path())
+            .unwrap()
+            .create_flow("foo", false)
+            .unwrap()
+            .id();
+
+        // Every writer has its own handle like the server, the command line and git hooks do
+        let writers: Vec<_> = (0..4)
+            .map(|_| {
+                let flows_dir = FlowsDir::try_new(temp_dir.path()).unwrap();
+                let flow_id = flow_id.clone();
+                thread::spawn(move || {
+                    for _ in 0..10 {
+                        flows_dir
+                            .update_flow(&flow_id, |data| {
+                                let node = crate::flow::Node::new_status(
+                                    Default::default(),
+                                    Default::default(),
+                                );
+                                data.reactflow_mut().add_node(None, node)
+                            })
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        let flows_dir = FlowsDir::try_new(temp_dir.path()).unwrap();
+        assert_eq!(flows_dir.get_flow_by_id(&flow_id).unwrap().num_nodes(), 40);
+    }
+}
@@ -0,0 +1,109 @@
+use super::Error;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Version of the flow file format written by this version of debug-flow
+pub const CURRENT_VERSION: u32 = 1;
+
+/// Key of the format version in the flow files
+const VERSION_KEY: &str = "version";
+
+/// A migration upgrades the JSON representation of a flow file by exactly one version
+type Migration = fn(serde_json::Value) -> Result<serde_json::Value, Error>;
+
+/// Migrations indexed by the version they upgrade from
+const MIGRATIONS: [Migration; CURRENT_VERSION as usize] = [v0_to_v1];
+
+/// Get the format version of the JSON representation of a flow file.
+///
+/// Files without a version field were written before the format was versioned and are treated as
+/// version 0.
+pub fn version_of(value: &serde_json::Value) -> Result<u32, Error> {
+    match value.get(VERSION_KEY) {
+        None => Ok(0),
+        Some(v) => v
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| Error::InvalidFormat(format!("Invalid format version '{v}'"))),
+    }
+}
+
+/// Migrate the JSON representation of a flow file to [`CURRENT_VERSION`]
+///
+/// Returns the migrated value and the version it was migrated from.
+pub fn migrate(mut value: serde_json::Value) -> Result<(serde_json::Value, u32), Error> {
+    let from_version = version_of(&value)?;
+    if from_version > CURRENT_VERSION {
+        return Err(Error::UnsupportedVersion(from_version, CURRENT_VERSION));
+    }
+    for migration in &MIGRATIONS[from_version as usize..] {
+        value = migration(value)?;
+    }
+    Ok((value, from_version))
+}
+
+/// Version 0 is identical to version 1 except for the missing version field
+fn v0_to_v1(mut value: serde_json::Value) -> Result<serde_json::Value, Error> {
+    let object = value
+        .as_object_mut()
+        .ok_or_else(|| Error::InvalidFormat("Flow file is not a JSON object".to_string()))?;
+    object.insert(VERSION_KEY.to_string(), 1.into());
+    Ok(value)
+}
+
+/// Result of the migration of a single flow file
+#[derive(Serialize, ToSchema, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FlowMigration {
+    /// Name of the flow file
+    pub file_name: String,
+    /// Format version of the file before the migration
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_version: Option<u32>,
+    /// Format version of the file after the migration
+    pub to_version: u32,
+    /// Whether the file was rewritten, always `false` in a dry-run
+    pub written: bool,
+    /// Error which occurred during the migration of the file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl FlowMigration {
+    /// Whether the file is not on the current version
+    pub fn is_outdated(&self) -> bool {
+        self.from_version.is_some_and(|v| v < self.to_version)
+    }
+}
+
+/// Report of the migration of all flows in a debug flow directory
+#[derive(Serialize, ToSchema, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationReport {
+    /// Whether this report was created in a dry-run, no files were written in that case
+    pub dry_run: bool,
+    /// Migration results for every flow file
+    pub flows: Vec<FlowMigration>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate_unversioned() {
+        let value = serde_json::json!({"name": "foo", "reactflow": {"nodes": [], "edges": []}});
+        let (migrated, from_version) = migrate(value).unwrap();
+        assert_eq!(from_version, 0);
+        assert_eq!(version_of(&migrated).unwrap(), CURRENT_VERSION);
+    }
+
+    #[test]
+    fn test_migrate_newer_version() {
+        let value = serde_json::json!({"version": CURRENT_VERSION + 1, "name": "foo"});
+        assert!(matches!(
+            migrate(value),
+            Err(Error::UnsupportedVersion(_, CURRENT_VERSION))
+        ));
+    }
+}
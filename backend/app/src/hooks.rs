@@ -0,0 +1,221 @@
+use crate::flow::{self, graph};
+use git2_ox::CommitProperties;
+use std::{fs, io, path};
+
+/// Line identifying hooks installed by `debug-flow`, other hooks are never replaced or removed
+const MARKER: &str = "# Installed by debug-flow";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Flow(#[from] flow::Error),
+    #[error(transparent)]
+    Git(#[from] git2_ox::error::Error),
+    #[error("Failed to access hook '{}': {}", .0.display(), .1)]
+    Io(path::PathBuf, #[source] io::Error),
+    #[error(
+        "Hook '{}' exists already and was not installed by debug-flow, use --force to replace it",
+        .0.display()
+    )]
+    Exists(path::PathBuf),
+}
+
+/// Git hook logging into the active debug flow
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hook {
+    /// Runs after a commit was created
+    PostCommit,
+    /// Runs after a checkout
+    PostCheckout,
+}
+
+impl Hook {
+    pub const ALL: [Hook; 2] = [Hook::PostCommit, Hook::PostCheckout];
+
+    /// Get the file name of the hook
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Hook::PostCommit => "post-commit",
+            Hook::PostCheckout => "post-checkout",
+        }
+    }
+
+    /// Get the script of the hook, running `executable` to record the hook
+    fn script(&self, executable: &path::Path) -> String {
+        // Quote the path for the shell, a single quote is closed, escaped and reopened
+        let executable = executable.display().to_string().replace('\'', r"'\''");
+        // The exit code of post-checkout hooks becomes the exit code of the checkout, logging
+        // must never fail it
+        format!(
+            "#!/bin/sh\n{MARKER}, logs commits and checkouts into the active debug flow\n\
+            '{executable}' hooks run {} \"$@\" || true\n",
+            self.as_str()
+        )
+    }
+}
+
+/// Check whether the file at `path` is a hook installed by `debug-flow`
+fn is_installed_by_us(path: &path::Path) -> Result<bool, Error> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(content.lines().any(|l| l.starts_with(MARKER))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(Error::Io(path.to_path_buf(), e)),
+    }
+}
+
+/// Install all hooks into `repository`, the hooks run `executable`
+///
+/// Existing hooks which were not installed by `debug-flow` are only replaced if `force` is set.
+/// Returns the paths of the installed hooks.
+pub fn install(
+    repository: &git2_ox::Repository,
+    executable: &path::Path,
+    force: bool,
+) -> Result<Vec<path::PathBuf>, Error> {
+    let hooks_path = repository.hooks_path()?;
+    let paths: Vec<_> = Hook::ALL
+        .iter()
+        .map(|h| hooks_path.join(h.as_str()))
+        .collect();
+    // Check all hooks first to not install only some of them
+    if !force {
+        for p in &paths {
+            if p.exists() && !is_installed_by_us(p)? {
+                return Err(Error::Exists(p.clone()));
+            }
+        }
+    }
+
+    fs::create_dir_all(&hooks_path).map_err(|e| Error::Io(hooks_path.clone(), e))?;
+    for (hook, p) in Hook::ALL.iter().zip(&paths) {
+        fs::write(p, hook.script(executable)).map_err(|e| Error::Io(p.clone(), e))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(p, fs::Permissions::from_mode(0o755))
+                .map_err(|e| Error::Io(p.clone(), e))?;
+        }
+    }
+    Ok(paths)
+}
+
+/// Remove all hooks installed by `debug-flow` from `repository`
+///
+/// Returns the paths of the removed hooks.
+pub fn uninstall(repository: &git2_ox::Repository) -> Result<Vec<path::PathBuf>, Error> {
+    let hooks_path = repository.hooks_path()?;
+    let mut removed = Vec::new();
+    for hook in Hook::ALL {
+        let p = hooks_path.join(hook.as_str());
+        if is_installed_by_us(&p)? {
+            fs::remove_file(&p).map_err(|e| Error::Io(p.clone(), e))?;
+            removed.push(p);
+        }
+    }
+    Ok(removed)
+}
+
+/// Record a run of `hook` in `repository` with the arguments `args` as an action node in the
+/// active debug flow
+///
+/// The node is linked to the new `HEAD` and attached below the most recently added leaf node.
+/// Hooks also run in linked worktrees, the debug flows are always those of the main worktree.
+/// Nothing is recorded if no debug flow is active, the active flow has no root node yet or the
+/// checkout only checked out files. Returns the ID of the added node.
+pub fn record(
+    repository: &git2_ox::Repository,
+    hook: Hook,
+    args: &[String],
+) -> Result<Option<String>, Error> {
+    let flows_dir = flow::FlowsDir::try_new(repository.main_workdir()?)?;
+    let Some(flow_id) = flows_dir.active_flow_id()? else {
+        return Ok(None);
+    };
+    // The third argument of post-checkout hooks is 1 for checkouts of branches or commits and 0
+    // for checkouts of files
+    if hook == Hook::PostCheckout && args.get(2).map(String::as_str) == Some("0") {
+        return Ok(None);
+    }
+
+    let flow = flows_dir.get_flow_by_id(&flow_id)?;
+    if flow.data().reactflow().root_node().is_none() {
+        return Ok(None);
+    }
+
+    let commit = repository.get_commit_for_revision("HEAD")?;
+    let title = match hook {
+        Hook::PostCommit => format!("Commit: {}", commit.summary()),
+        Hook::PostCheckout => match repository.current_branch_name() {
+            Some(branch) => format!("Checkout: {branch}"),
+            None => format!("Checkout: {}", commit.id().get(..7).unwrap_or(commit.id())),
+        },
+    };
+    let node = flow::Node::new_action(
+        graph::ActionNodeData {
+            title,
+            git: Some(graph::GitMetadata::commit(commit.id(), commit.summary())),
+            ..Default::default()
+        },
+        graph::Position::default(),
+    );
+
+    let node_id = flows_dir.update_flow(&flow_id, |data| {
+        let state = data.reactflow_mut();
+        let parent_id = state.latest_leaf().map(|n| n.id().to_string());
+        state.add_node(parent_id.as_deref(), node)
+    })?;
+    Ok(Some(node_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script() {
+        let script = Hook::PostCommit.script(path::Path::new("/opt/it's/debug-flow"));
+        assert!(script.starts_with("#!/bin/sh\n"));
+        assert!(script.contains("'/opt/it'\\''s/debug-flow' hooks run post-commit \"$@\" || true"));
+    }
+
+    #[test]
+    fn test_record_in_linked_worktree() {
+        let parent_dir = tempfile::tempdir().unwrap();
+        let repo_path = parent_dir.path().join("repo");
+        let repo = git2::Repository::init(&repo_path).unwrap();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let commit = repo
+            .commit(Some("HEAD"), &signature, &signature, "Initial", &tree, &[])
+            .unwrap()
+            .to_string();
+
+        let flows_dir = flow::FlowsDir::try_new(&repo_path).unwrap();
+        let mut data = flow::FlowData::new("foo");
+        let root = flow::Node::new_status(
+            graph::StatusNodeData {
+                is_root_node: true,
+                ..Default::default()
+            },
+            Default::default(),
+        );
+        data.reactflow_mut().add_node(None, root).unwrap();
+        flows_dir.save_flow(&data).unwrap();
+        flows_dir.set_active_flow_id(Some(&data.id())).unwrap();
+
+        let worktree_path = parent_dir.path().join("worktree");
+        git2_ox::Repository::try_open(&repo_path)
+            .unwrap()
+            .add_detached_worktree("worktree", &worktree_path, &commit)
+            .unwrap();
+        let worktree = git2_ox::Repository::try_open(&worktree_path).unwrap();
+        let node_id = record(&worktree, Hook::PostCheckout, &[]).unwrap().unwrap();
+
+        // The node is recorded in the debug flows of the main worktree
+        let stored = flows_dir.get_flow_by_id(&data.id()).unwrap();
+        assert!(stored.data().reactflow().node(&node_id).is_some());
+        assert!(!worktree_path.join(".debug-flow").exists());
+    }
+}
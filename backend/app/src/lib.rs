@@ -1,4 +1,13 @@
 pub mod actors;
+pub mod bisect;
+pub mod events;
+pub mod export;
 pub mod flow;
+pub mod hooks;
+pub mod revisions;
+pub mod runner;
+pub mod snapshots;
 pub mod utils;
+pub mod watcher;
 pub mod web;
+pub mod worktrees;
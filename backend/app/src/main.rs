@@ -1,5 +1,9 @@
 use clap::Parser;
+use debug_flow::flow::graph;
+use debug_flow::{export, flow, hooks};
+use git2_ox::CommitProperties;
 use std::path::PathBuf;
+use std::{env, fs, process};
 
 #[derive(Parser)]
 #[command(
@@ -7,9 +11,68 @@ use std::path::PathBuf;
     about = "A tool for visualizing and debugging Git repository history as an interactive tree",
     long_about = "Debug Tree provides an interactive web interface for exploring Git repository history. \
                   It serves a local web server that visualizes commits, branches, and repository structure \
-                  in an easy-to-navigate tree format, perfect for debugging complex Git workflows."
+                  in an easy-to-navigate tree format, perfect for debugging complex Git workflows.",
+    args_conflicts_with_subcommands = true
 )]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    serve: ServeArgs,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Serve the web interface, this is the default if no subcommand is given
+    Serve(ServeArgs),
+    /// List all debug flows
+    List(ListArgs),
+    /// Show the nodes of a debug flow
+    Show(ShowArgs),
+    /// Create a new debug flow
+    New(NewArgs),
+    /// Delete a debug flow
+    Delete(DeleteArgs),
+    /// Rename a debug flow, this also changes its ID
+    Rename(RenameArgs),
+    /// Export a debug flow as a Markdown debugging report, Mermaid flowchart or DOT graph
+    Export(ExportArgs),
+    /// Manage the nodes of a debug flow
+    Node {
+        #[command(subcommand)]
+        command: NodeCommand,
+    },
+    /// Select the debug flow the git hooks log commits and checkouts to
+    Activate(ActivateArgs),
+    /// Stop logging commits and checkouts to the active debug flow
+    Deactivate(RepoArgs),
+    /// Manage the git hooks logging commits and checkouts to the active debug flow
+    Hooks {
+        #[command(subcommand)]
+        command: HooksCommand,
+    },
+}
+
+#[derive(clap::Subcommand)]
+enum HooksCommand {
+    /// Install the post-commit and post-checkout hooks into the repository
+    Install(HooksInstallArgs),
+    /// Remove the hooks installed by debug-flow from the repository
+    Uninstall(RepoArgs),
+    /// Record a run of a hook in the active debug flow, called by the installed hooks
+    #[command(hide = true)]
+    Run(HooksRunArgs),
+}
+
+#[derive(clap::Subcommand)]
+enum NodeCommand {
+    /// Add an action or status node to a debug flow
+    Add(NodeAddArgs),
+}
+
+/// Arguments for serving the web interface
+#[derive(clap::Args)]
+struct ServeArgs {
     /// Path to a git repository
     #[arg(default_value = "./")]
     repo: PathBuf,
@@ -22,6 +85,460 @@ struct Cli {
     /// Do not open the website in the default browser
     #[arg(long, default_value_t = false)]
     no_browser: bool,
+    /// Number of backups to keep per debug flow, use 0 to disable backups
+    #[arg(long, default_value_t = flow::FlowsDir::DEFAULT_MAX_BACKUPS)]
+    backups: usize,
+    /// Migrate all debug flows to the current file format version and exit
+    #[arg(long, default_value_t = false)]
+    migrate: bool,
+    /// Only report which debug flows would be migrated, requires `--migrate`
+    #[arg(long, default_value_t = false, requires = "migrate")]
+    dry_run: bool,
+}
+
+/// Arguments selecting the git repository of the debug flows to manage
+#[derive(clap::Args)]
+struct RepoArgs {
+    /// Path to a git repository
+    #[arg(short = 'C', long, default_value = "./")]
+    repo: PathBuf,
+}
+
+impl RepoArgs {
+    fn flows_dir(&self) -> Result<flow::FlowsDir, flow::Error> {
+        flow::FlowsDir::try_new(&self.repo)
+    }
+}
+
+#[derive(clap::Args)]
+struct ListArgs {
+    #[command(flatten)]
+    repo: RepoArgs,
+    /// Print the debug flows as JSON
+    #[arg(long, default_value_t = false)]
+    json: bool,
+}
+
+#[derive(clap::Args)]
+struct ShowArgs {
+    /// ID of the debug flow to show
+    id: String,
+    #[command(flatten)]
+    repo: RepoArgs,
+    /// Print the debug flow as JSON
+    #[arg(long, default_value_t = false)]
+    json: bool,
+}
+
+#[derive(clap::Args)]
+struct NewArgs {
+    /// Name of the debug flow to create
+    name: String,
+    #[command(flatten)]
+    repo: RepoArgs,
+    /// Print the created debug flow as JSON
+    #[arg(long, default_value_t = false)]
+    json: bool,
+}
+
+#[derive(clap::Args)]
+struct DeleteArgs {
+    /// ID of the debug flow to delete
+    id: String,
+    #[command(flatten)]
+    repo: RepoArgs,
+}
+
+#[derive(clap::Args)]
+struct RenameArgs {
+    /// ID of the debug flow to rename
+    id: String,
+    /// New name of the debug flow
+    name: String,
+    #[command(flatten)]
+    repo: RepoArgs,
+    /// Print the renamed debug flow as JSON
+    #[arg(long, default_value_t = false)]
+    json: bool,
+}
+
+#[derive(clap::Args)]
+struct ActivateArgs {
+    /// ID of the debug flow to activate
+    id: String,
+    #[command(flatten)]
+    repo: RepoArgs,
+}
+
+#[derive(clap::Args)]
+struct HooksInstallArgs {
+    /// Replace existing hooks which were not installed by debug-flow
+    #[arg(long, default_value_t = false)]
+    force: bool,
+    #[command(flatten)]
+    repo: RepoArgs,
+}
+
+#[derive(clap::Args)]
+struct HooksRunArgs {
+    /// Hook to record
+    #[arg(value_enum)]
+    hook: hooks::Hook,
+    /// Arguments passed to the hook by git
+    args: Vec<String>,
+    #[command(flatten)]
+    repo: RepoArgs,
+}
+
+#[derive(clap::Args)]
+struct ExportArgs {
+    /// ID of the debug flow to export
+    id: String,
+    #[command(flatten)]
+    repo: RepoArgs,
+    /// Format to export the debug flow in
+    #[arg(short, long, value_enum, default_value_t)]
+    format: export::ExportFormat,
+    /// Include the diff stats of the revisions linked to action nodes
+    #[arg(long, default_value_t = false)]
+    diff_stats: bool,
+    /// File to write the export to, printed to standard output if not set
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+type CommandResult = Result<(), Box<dyn std::error::Error>>;
+
+/// List the debug flows as described by `args`
+fn list(args: &ListArgs) -> CommandResult {
+    let flows_dir = args.repo.flows_dir()?;
+    let active_flow_id = flows_dir.active_flow_id()?;
+    let mut metadatas: Vec<_> = flows_dir.metadatas()?.collect();
+    metadatas.sort_by(|a, b| a.id().cmp(b.id()));
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&metadatas)?);
+        return Ok(());
+    }
+    let id_width = metadatas.iter().map(|m| m.id().len()).max().unwrap_or(0);
+    for metadata in &metadatas {
+        let marker = match active_flow_id.as_deref() == Some(metadata.id()) {
+            true => '*',
+            false => ' ',
+        };
+        println!(
+            "{marker} {:id_width$}  {:>3} nodes  {:>3} edges  {}  {}",
+            metadata.id(),
+            metadata.num_nodes(),
+            metadata.num_edges(),
+            metadata.last_modified_date().format("%Y-%m-%d %H:%M"),
+            metadata.name()
+        );
+    }
+    Ok(())
+}
+
+/// Print `flow` in a human readable form
+fn print_flow(flow: &flow::Flow) {
+    let state = flow.data().reactflow();
+    println!("{} ({})", flow.name(), flow.id());
+    println!("{} nodes, {} edges", flow.num_nodes(), flow.num_edges());
+    for node in state.nodes() {
+        let kind = match node.state() {
+            Some(node_state) => format!("status: {}", node_state.as_str()),
+            None => "action".to_string(),
+        };
+        let root = if node.is_root() { ", root" } else { "" };
+        println!("\n{} ({kind}{root})", node.title());
+        println!("  ID: {}", node.id());
+        if let Some(git) = node.git() {
+            println!("  Revision: {} {}", git.rev, git.summary);
+        }
+        for edge in state.outgoing_edges(node.id()) {
+            match &edge.label {
+                Some(label) => println!("  -> {} ({label})", edge.target),
+                None => println!("  -> {}", edge.target),
+            }
+        }
+    }
+}
+
+/// Show a debug flow as described by `args`
+fn show(args: &ShowArgs) -> CommandResult {
+    let flow = args.repo.flows_dir()?.get_flow_by_id(&args.id)?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(flow.data())?);
+    } else {
+        print_flow(&flow);
+    }
+    Ok(())
+}
+
+/// Create a debug flow as described by `args`
+fn new(args: &NewArgs) -> CommandResult {
+    let flow = args.repo.flows_dir()?.create_flow(&args.name, false)?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(flow.data())?);
+    } else {
+        println!(
+            "Created debug flow '{}' with ID '{}'",
+            flow.name(),
+            flow.id()
+        );
+    }
+    Ok(())
+}
+
+/// Delete a debug flow as described by `args`
+fn delete(args: &DeleteArgs) -> CommandResult {
+    args.repo.flows_dir()?.delete_flow_by_id(&args.id)?;
+    println!("Deleted debug flow '{}'", args.id);
+    Ok(())
+}
+
+/// Rename a debug flow as described by `args`
+fn rename(args: &RenameArgs) -> CommandResult {
+    let flow = args.repo.flows_dir()?.rename_flow(&args.id, &args.name)?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(flow.data())?);
+    } else {
+        println!(
+            "Renamed debug flow '{}' to '{}' with ID '{}'",
+            args.id,
+            flow.name(),
+            flow.id()
+        );
+    }
+    Ok(())
+}
+
+/// Kind of a node
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq)]
+enum NodeKind {
+    Action,
+    Status,
+}
+
+#[derive(clap::Args)]
+struct NodeAddArgs {
+    /// ID of the debug flow to add the node to
+    flow_id: String,
+    /// Kind of the node to add
+    #[arg(value_enum)]
+    kind: NodeKind,
+    /// Title of the node, the title and description are edited in `$EDITOR` if not set
+    #[arg(short, long)]
+    title: Option<String>,
+    /// Markdown description of the node
+    #[arg(short = 'm', long)]
+    description: Option<String>,
+    /// State of the node, only for status nodes [default: unknown]
+    #[arg(short, long, value_enum)]
+    state: Option<graph::StatusNodeState>,
+    /// ID of the node to attach the node to, defaults to the most recently added leaf node
+    #[arg(short, long)]
+    parent: Option<String>,
+    /// Revision to link the node to
+    #[arg(short, long, default_value = "HEAD")]
+    rev: String,
+    /// Do not link the node to a revision
+    #[arg(long, default_value_t = false, conflicts_with = "rev")]
+    no_rev: bool,
+    #[command(flatten)]
+    repo: RepoArgs,
+    /// Print the added node as JSON
+    #[arg(long, default_value_t = false)]
+    json: bool,
+}
+
+/// Add a node to a debug flow as described by `args`
+fn add_node(args: &NodeAddArgs) -> CommandResult {
+    if args.kind == NodeKind::Action && args.state.is_some() {
+        return Err("`--state` can only be used for status nodes".into());
+    }
+    let flows_dir = args.repo.flows_dir()?;
+    if args.kind == NodeKind::Action && flows_dir.get_flow_by_id(&args.flow_id)?.num_nodes() == 0 {
+        return Err(format!(
+            "Debug flow '{}' has no nodes, add a status node as root node first",
+            args.flow_id
+        )
+        .into());
+    }
+    let git = if args.no_rev {
+        None
+    } else {
+        let repository = git2_ox::Repository::try_open(&args.repo.repo)?;
+        let commit = repository.get_commit_for_revision(&args.rev)?;
+        Some(graph::GitMetadata::commit(commit.id(), commit.summary()))
+    };
+    let (title, description) = match &args.title {
+        Some(title) => (title.clone(), args.description.clone().unwrap_or_default()),
+        None => edit_node_text(args.description.as_deref().unwrap_or_default())?,
+    };
+    if title.is_empty() {
+        return Err("Aborting, the title of the node is empty".into());
+    }
+
+    let node = flows_dir.update_flow(&args.flow_id, |data| {
+        let state = data.reactflow_mut();
+        let parent_id = match &args.parent {
+            Some(parent_id) => Some(parent_id.clone()),
+            None => state.latest_leaf().map(|n| n.id().to_string()),
+        };
+        let position = graph::Position::default();
+        let node = match args.kind {
+            NodeKind::Action => flow::Node::new_action(
+                graph::ActionNodeData {
+                    title,
+                    description,
+                    git,
+                    ..Default::default()
+                },
+                position,
+            ),
+            NodeKind::Status => flow::Node::new_status(
+                graph::StatusNodeData {
+                    title,
+                    state: args.state.unwrap_or_default(),
+                    description,
+                    git,
+                    is_root_node: state.nodes().is_empty(),
+                    ..Default::default()
+                },
+                position,
+            ),
+        };
+        let id = state.add_node(parent_id.as_deref(), node)?;
+        state
+            .node(&id)
+            .cloned()
+            .ok_or(flow::Error::NodeNotFound(id))
+    })?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&node)?);
+    } else {
+        println!(
+            "Added node '{}' to debug flow '{}'",
+            node.id(),
+            args.flow_id
+        );
+    }
+    Ok(())
+}
+
+/// Edit the title and description of a node in the editor of the user
+///
+/// The first line of the edited text is the title, the remaining lines are the description. The
+/// editor is taken from `$VISUAL` or `$EDITOR` and defaults to `vi`.
+fn edit_node_text(description: &str) -> Result<(String, String), Box<dyn std::error::Error>> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let path = env::temp_dir().join(format!("debug-flow-node-{}.md", uuid::Uuid::new_v4()));
+    fs::write(&path, format!("\n\n{description}"))?;
+
+    // Run the editor through the shell, like git does, so `$EDITOR` may contain arguments
+    let status = if cfg!(windows) {
+        process::Command::new("cmd")
+            .arg("/C")
+            .arg(format!("{editor} \"{}\"", path.display()))
+            .status()
+    } else {
+        process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("{editor} \"$1\""))
+            .arg(&editor)
+            .arg(&path)
+            .status()
+    };
+    let text = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+    if !status?.success() {
+        return Err(format!("Editor '{editor}' failed").into());
+    }
+    let text = text?;
+    let (title, description) = text.split_once('\n').unwrap_or((&text, ""));
+    Ok((title.trim().to_string(), description.trim().to_string()))
+}
+
+/// Activate a debug flow as described by `args`
+fn activate(args: &ActivateArgs) -> CommandResult {
+    args.repo.flows_dir()?.set_active_flow_id(Some(&args.id))?;
+    println!(
+        "Commits and checkouts are logged to debug flow '{}'",
+        args.id
+    );
+    Ok(())
+}
+
+/// Deactivate the active debug flow of the repository `repo`
+fn deactivate(repo: &RepoArgs) -> CommandResult {
+    repo.flows_dir()?.set_active_flow_id(None)?;
+    println!("Commits and checkouts are not logged anymore");
+    Ok(())
+}
+
+/// Install the git hooks as described by `args`
+fn install_hooks(args: &HooksInstallArgs) -> CommandResult {
+    let repository = git2_ox::Repository::try_open(&args.repo.repo)?;
+    for path in hooks::install(&repository, &env::current_exe()?, args.force)? {
+        println!("Installed hook '{}'", path.display());
+    }
+    Ok(())
+}
+
+/// Remove the git hooks from the repository `repo`
+fn uninstall_hooks(repo: &RepoArgs) -> CommandResult {
+    let repository = git2_ox::Repository::try_open(&repo.repo)?;
+    for path in hooks::uninstall(&repository)? {
+        println!("Removed hook '{}'", path.display());
+    }
+    Ok(())
+}
+
+/// Record a run of a git hook as described by `args`
+fn run_hook(args: &HooksRunArgs) -> CommandResult {
+    let repository = git2_ox::Repository::try_open(&args.repo.repo)?;
+    if let Some(node_id) = hooks::record(&repository, args.hook, &args.args)? {
+        log::debug!("Recorded {} hook as node '{node_id}'", args.hook.as_str());
+    }
+    Ok(())
+}
+
+/// Export a debug flow as described by `args`
+fn export(args: &ExportArgs) -> CommandResult {
+    let flow = args.repo.flows_dir()?.get_flow_by_id(&args.id)?;
+    let repository = git2_ox::Repository::try_open(&args.repo.repo)?;
+    let options = export::ExportOptions {
+        diff_stats: args.diff_stats,
+    };
+    let document = export::export(&repository, flow.data(), args.format, options);
+    match &args.output {
+        Some(path) => std::fs::write(path, document)?,
+        None => print!("{document}"),
+    }
+    Ok(())
+}
+
+/// Migrate all flows in `flows_dir` and print a report
+fn migrate(flows_dir: &flow::FlowsDir, dry_run: bool) {
+    let report = flows_dir
+        .migrate_flows(dry_run)
+        .expect("Error migrating debug flows");
+    for flow in &report.flows {
+        let from_version = flow
+            .from_version
+            .map_or_else(|| "?".to_string(), |v| v.to_string());
+        let status = match (&flow.error, flow.is_outdated(), flow.written) {
+            (Some(e), _, _) => format!("error: {e}"),
+            (None, false, _) => "up to date".to_string(),
+            (None, true, true) => "migrated".to_string(),
+            (None, true, false) => "would be migrated".to_string(),
+        };
+        println!(
+            "{}: v{} -> v{}: {}",
+            flow.file_name, from_version, flow.to_version, status
+        );
+    }
 }
 
 #[tokio::main]
@@ -30,9 +547,48 @@ async fn main() {
         .filter_level(log::LevelFilter::Info)
         .init();
 
-    let args = Cli::parse();
-    let flows_dir = debug_flow::flow::FlowsDir::try_new(args.repo)
-        .expect("Error creating debug flow directory");
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Some(Command::Serve(args)) => {
+            serve(args).await;
+            Ok(())
+        }
+        Some(Command::List(args)) => list(&args),
+        Some(Command::Show(args)) => show(&args),
+        Some(Command::New(args)) => new(&args),
+        Some(Command::Delete(args)) => delete(&args),
+        Some(Command::Rename(args)) => rename(&args),
+        Some(Command::Export(args)) => export(&args),
+        Some(Command::Node {
+            command: NodeCommand::Add(args),
+        }) => add_node(&args),
+        Some(Command::Activate(args)) => activate(&args),
+        Some(Command::Deactivate(args)) => deactivate(&args),
+        Some(Command::Hooks { command }) => match command {
+            HooksCommand::Install(args) => install_hooks(&args),
+            HooksCommand::Uninstall(args) => uninstall_hooks(&args),
+            HooksCommand::Run(args) => run_hook(&args),
+        },
+        None => {
+            serve(cli.serve).await;
+            Ok(())
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("Error: {e}");
+        process::exit(1);
+    }
+}
+
+/// Serve the web interface as described by `args`
+async fn serve(args: ServeArgs) {
+    let flows_dir = flow::FlowsDir::try_new(args.repo)
+        .expect("Error creating debug flow directory")
+        .with_max_backups(args.backups);
+    if args.migrate {
+        migrate(&flows_dir, args.dry_run);
+        return;
+    }
     let server = debug_flow::web::serve(
         "localhost",
         args.port,
@@ -0,0 +1,155 @@
+use crate::actors::git::{self as git_actor, GitActorAddr};
+use crate::flow::{self, graph};
+use git2_ox::CommitProperties;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Flow(#[from] flow::Error),
+    #[error(transparent)]
+    Git(#[from] git2_ox::error::Error),
+    #[error(transparent)]
+    Actor(#[from] hannibal::error::ActorError),
+}
+
+/// Node linked to a revision which is not reachable anymore
+#[derive(Serialize, ToSchema, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct StaleNode {
+    /// ID of the node
+    node_id: String,
+    /// Title of the node
+    title: String,
+    /// Revision the node is linked to
+    git: graph::GitMetadata,
+    /// State of the revision and suggested replacements
+    check: git2_ox::RevisionCheck,
+}
+
+/// New revision for a node
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RevisionRewrite {
+    /// ID of the node
+    pub node_id: String,
+    /// Revision to link the node to
+    pub revision: String,
+}
+
+/// Find the nodes of `data` linked to revisions which are not reachable in `repository` anymore
+pub fn stale_nodes(
+    repository: &git2_ox::Repository,
+    data: &flow::FlowData,
+) -> Result<Vec<StaleNode>, git2_ox::error::Error> {
+    let nodes: Vec<_> = data
+        .reactflow()
+        .nodes()
+        .iter()
+        .filter_map(|n| Some((n, n.git()?)))
+        .collect();
+    let revisions: Vec<_> = nodes
+        .iter()
+        .map(|(_, git)| (git.rev.as_str(), git.summary.as_str()))
+        .collect();
+    let checks = repository.check_revisions(&revisions)?;
+    Ok(nodes
+        .into_iter()
+        .zip(checks)
+        .filter(|(_, check)| check.state() != git2_ox::RevisionState::Reachable)
+        .map(|((node, git), check)| StaleNode {
+            node_id: node.id().to_string(),
+            title: node.title().to_string(),
+            git: git.clone(),
+            check,
+        })
+        .collect())
+}
+
+/// Find the nodes of the debug flow `flow_id` linked to revisions which are not reachable anymore
+pub async fn check(
+    flows_dir: &flow::FlowsDir,
+    git_actor: &GitActorAddr,
+    flow_id: &str,
+) -> Result<Vec<StaleNode>, Error> {
+    let data = flows_dir.get_flow_by_id(flow_id)?.data().clone();
+    Ok(git_actor.call(git_actor::CheckRevisions { data }).await??)
+}
+
+/// Link nodes of the debug flow `flow_id` to new revisions
+///
+/// All revisions are resolved before the debug flow is changed, the nodes are rewritten in a
+/// single save. Returns the nodes which are still linked to revisions which are not reachable.
+pub async fn rewrite(
+    flows_dir: &flow::FlowsDir,
+    git_actor: &GitActorAddr,
+    flow_id: &str,
+    rewrites: Vec<RevisionRewrite>,
+) -> Result<Vec<StaleNode>, Error> {
+    let mut resolved = Vec::with_capacity(rewrites.len());
+    for rewrite in rewrites {
+        let commit = git_actor
+            .call(git_actor::GetRevision {
+                revision: rewrite.revision,
+            })
+            .await??;
+        let git = graph::GitMetadata::commit(commit.id(), commit.summary());
+        resolved.push((rewrite.node_id, git));
+    }
+
+    let id = flow_id.to_string();
+    flows_dir
+        .blocking(move |flows_dir| {
+            flows_dir.update_flow(&id, |data| {
+                for (node_id, git) in resolved {
+                    let node = data
+                        .reactflow_mut()
+                        .node_mut(&node_id)
+                        .ok_or(flow::Error::NodeNotFound(node_id))?;
+                    *node.git_mut() = Some(git);
+                }
+                Ok(())
+            })
+        })
+        .await?;
+    check(flows_dir, git_actor, flow_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stale_nodes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init(temp_dir.path()).unwrap();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let head = repo
+            .commit(Some("HEAD"), &signature, &signature, "Initial", &tree, &[])
+            .unwrap()
+            .to_string();
+        let repository = git2_ox::Repository::try_open(temp_dir.path()).unwrap();
+
+        let mut data = flow::FlowData::new("foo");
+        for rev in [head.as_str(), "0123456789012345678901234567890123456789"] {
+            let node = flow::Node::new_action(
+                graph::ActionNodeData {
+                    git: Some(graph::GitMetadata::commit(rev, "Initial")),
+                    ..Default::default()
+                },
+                graph::Position::default(),
+            );
+            data.reactflow_mut().add_node(None, node).unwrap();
+        }
+
+        let stale = stale_nodes(&repository, &data).unwrap();
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].node_id, data.reactflow().nodes()[1].id());
+        assert_eq!(stale[0].check.state(), git2_ox::RevisionState::Missing);
+        assert_eq!(stale[0].check.candidates()[0].commit().id(), head);
+    }
+}
@@ -0,0 +1,297 @@
+use crate::flow::{self, graph};
+use git2_ox::CommitProperties;
+use serde::{Deserialize, Serialize};
+use std::{io, path, process, time};
+use tokio::io::AsyncReadExt;
+use utoipa::ToSchema;
+
+/// Maximum number of bytes kept of the standard output and standard error of a command.
+///
+/// The end of the output usually contains the relevant information, the beginning is dropped.
+const MAX_OUTPUT_LEN: usize = 16 * 1024;
+
+/// Time to wait for the output of a command after it was killed
+const KILL_GRACE_PERIOD: time::Duration = time::Duration::from_secs(1);
+
+/// Outcome of a command run at a revision
+#[derive(Serialize, ToSchema, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandOutcome {
+    /// Command that was run
+    command: String,
+    /// Commit the command was run on
+    commit: git2_ox::Commit,
+    /// Exit code of the command, not set if the command was terminated by a signal or timed out
+    exit_code: Option<i32>,
+    /// Whether the command was killed because it exceeded its timeout
+    timed_out: bool,
+    /// Duration of the command in milliseconds
+    duration_ms: u64,
+    /// Standard output of the command, possibly truncated at the beginning
+    stdout: String,
+    /// Standard error of the command, possibly truncated at the beginning
+    stderr: String,
+}
+
+impl CommandOutcome {
+    /// Whether the command exited successfully
+    pub fn success(&self) -> bool {
+        !self.timed_out && self.exit_code == Some(0)
+    }
+
+    /// Get the exit code of the command, not set if it was terminated by a signal or timed out
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    /// Get the commit the command was run on
+    pub fn commit(&self) -> &git2_ox::Commit {
+        &self.commit
+    }
+
+    /// Get the state of a status node describing the outcome
+    pub fn state(&self) -> graph::StatusNodeState {
+        if self.success() {
+            graph::StatusNodeState::Success
+        } else {
+            graph::StatusNodeState::Fail
+        }
+    }
+
+    /// Get a one line summary of the outcome
+    pub fn summary(&self) -> String {
+        let duration = time::Duration::from_millis(self.duration_ms).as_secs_f64();
+        match (self.timed_out, self.exit_code) {
+            (true, _) => format!("timed out after {duration:.2}s"),
+            (false, Some(code)) => format!("exited with code {code} after {duration:.2}s"),
+            (false, None) => format!("was terminated after {duration:.2}s"),
+        }
+    }
+
+    /// Render the outcome as Markdown, as used for the description of status nodes
+    pub fn to_markdown(&self) -> String {
+        let mut md = format!(
+            "`{}` on `{}` {}\n",
+            self.command,
+            self.commit.id(),
+            self.summary()
+        );
+        for (name, output) in [("stdout", &self.stdout), ("stderr", &self.stderr)] {
+            if !output.trim().is_empty() {
+                md.push_str(&format!(
+                    "\n**{name}**\n\n```text\n{}\n```\n",
+                    output.trim_end()
+                ));
+            }
+        }
+        md
+    }
+}
+
+/// Status node to record the outcome of a command in
+#[derive(Deserialize, ToSchema, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordTarget {
+    /// ID of the debug flow
+    pub flow_id: String,
+    /// ID of an existing node to update, a new status node is created if not set
+    #[serde(default)]
+    pub node_id: Option<String>,
+    /// ID of the node to attach a new status node to
+    #[serde(default)]
+    pub parent_node_id: Option<String>,
+    /// Title of a new status node, defaults to the command
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+/// Run `command` in a shell in `working_dir`
+///
+/// The command is killed if it does not finish within `timeout`.
+pub async fn run_command(
+    working_dir: &path::Path,
+    command: &str,
+    commit: git2_ox::Commit,
+    timeout: Option<time::Duration>,
+) -> io::Result<CommandOutcome> {
+    let mut cmd = shell_command(command);
+    cmd.current_dir(working_dir)
+        .stdin(process::Stdio::null())
+        .stdout(process::Stdio::piped())
+        .stderr(process::Stdio::piped())
+        .kill_on_drop(true);
+
+    let start = time::Instant::now();
+    let mut child = cmd.spawn()?;
+    let stdout = tokio::spawn(read_to_end(child.stdout.take()));
+    let stderr = tokio::spawn(read_to_end(child.stderr.take()));
+
+    let (status, timed_out) = match timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, child.wait()).await {
+            Ok(status) => (Some(status?), false),
+            Err(_) => {
+                child.kill().await?;
+                (None, true)
+            }
+        },
+        None => (Some(child.wait().await?), false),
+    };
+    let duration_ms = start.elapsed().as_millis() as u64;
+
+    // Processes started by the command may keep the pipes open after the shell was killed
+    let grace_period = if timed_out {
+        KILL_GRACE_PERIOD
+    } else {
+        time::Duration::MAX
+    };
+    let mut outputs = Vec::new();
+    for handle in [stdout, stderr] {
+        let abort_handle = handle.abort_handle();
+        let output = match tokio::time::timeout(grace_period, handle).await {
+            Ok(Ok(output)) => output,
+            Ok(Err(_)) => Vec::new(),
+            Err(_) => {
+                abort_handle.abort();
+                Vec::new()
+            }
+        };
+        outputs.push(truncate_output(&output));
+    }
+    let stderr = outputs.pop().unwrap_or_default();
+    let stdout = outputs.pop().unwrap_or_default();
+
+    Ok(CommandOutcome {
+        command: command.to_string(),
+        commit,
+        exit_code: status.and_then(|s| s.code()),
+        timed_out,
+        duration_ms,
+        stdout,
+        stderr,
+    })
+}
+
+/// Record `outcome` in the status node described by `target`
+///
+/// An existing node gets its description and git revision replaced, status nodes also get their
+/// state updated. Returns the ID of the updated or created node.
+pub fn record_outcome(
+    flows_dir: &flow::FlowsDir,
+    target: &RecordTarget,
+    outcome: &CommandOutcome,
+) -> Result<String, flow::Error> {
+    let git = graph::GitMetadata::commit(outcome.commit.id(), outcome.commit.summary());
+    flows_dir.update_flow(&target.flow_id, |data| match &target.node_id {
+        Some(node_id) => {
+            let node = data
+                .reactflow_mut()
+                .node_mut(node_id)
+                .ok_or_else(|| flow::Error::NodeNotFound(node_id.clone()))?;
+            *node.description_mut() = outcome.to_markdown();
+            *node.git_mut() = Some(git);
+            if let flow::Node::Status(status_node) = node {
+                status_node.data.state = outcome.state();
+            }
+            Ok(node_id.clone())
+        }
+        None => {
+            let node = flow::Node::new_status(
+                graph::StatusNodeData {
+                    title: target
+                        .title
+                        .clone()
+                        .unwrap_or_else(|| outcome.command.clone()),
+                    state: outcome.state(),
+                    description: outcome.to_markdown(),
+                    git: Some(git),
+                    ..Default::default()
+                },
+                graph::Position::default(),
+            );
+            data.reactflow_mut()
+                .add_node(target.parent_node_id.as_deref(), node)
+        }
+    })
+}
+
+/// Create a command running `command` in the platform's shell
+fn shell_command(command: &str) -> tokio::process::Command {
+    if cfg!(windows) {
+        let mut cmd = tokio::process::Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    } else {
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    }
+}
+
+async fn read_to_end<R: tokio::io::AsyncRead + Unpin>(reader: Option<R>) -> Vec<u8> {
+    let mut buf = Vec::new();
+    if let Some(mut reader) = reader {
+        // Keep what was read so far if reading fails
+        let _ = reader.read_to_end(&mut buf).await;
+    }
+    buf
+}
+
+/// Convert `output` to a string, keeping only the last `MAX_OUTPUT_LEN` bytes
+fn truncate_output(output: &[u8]) -> String {
+    if output.len() <= MAX_OUTPUT_LEN {
+        return String::from_utf8_lossy(output).into_owned();
+    }
+    let tail = String::from_utf8_lossy(&output[output.len() - MAX_OUTPUT_LEN..]);
+    format!(
+        "[... {} bytes truncated]\n{tail}",
+        output.len() - MAX_OUTPUT_LEN
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_output() {
+        assert_eq!(truncate_output(b"foo"), "foo");
+        let output = vec![b'a'; MAX_OUTPUT_LEN + 3];
+        let truncated = truncate_output(&output);
+        assert!(truncated.starts_with("[... 3 bytes truncated]\n"));
+        assert!(truncated.ends_with(&"a".repeat(MAX_OUTPUT_LEN)));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_command() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let repo = git2_ox::Repository::try_init(temp_dir.path()).unwrap();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        let tree_id = repo.repo().index().unwrap().write_tree().unwrap();
+        let tree = repo.repo().find_tree(tree_id).unwrap();
+        repo.repo()
+            .commit(Some("HEAD"), &signature, &signature, "Initial", &tree, &[])
+            .unwrap();
+        let commit = git2_ox::Commit::try_from_revision(repo.repo(), "HEAD").unwrap();
+
+        let outcome = run_command(
+            temp_dir.path(),
+            "echo foo; echo bar >&2; exit 3",
+            commit.clone(),
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(outcome.exit_code, Some(3));
+        assert_eq!(outcome.stdout, "foo\n");
+        assert_eq!(outcome.stderr, "bar\n");
+        assert_eq!(outcome.state(), graph::StatusNodeState::Fail);
+
+        let timeout = Some(time::Duration::from_millis(100));
+        let outcome = run_command(temp_dir.path(), "sleep 5", commit, timeout)
+            .await
+            .unwrap();
+        assert!(outcome.timed_out);
+        assert!(!outcome.success());
+    }
+}
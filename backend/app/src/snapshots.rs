@@ -0,0 +1,339 @@
+use std::collections::HashSet;
+
+use crate::actors::git::{self as git_actor, GitActorAddr};
+use crate::flow::{self, graph};
+use git2_ox::CommitProperties;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Flow(#[from] flow::Error),
+    #[error(transparent)]
+    Git(#[from] git2_ox::error::Error),
+    #[error(transparent)]
+    Actor(#[from] hannibal::error::ActorError),
+    #[error("Node '{0}' is not an action node")]
+    NotAnActionNode(String),
+}
+
+/// Get the name of the snapshot of the node `node_id`
+///
+/// Node IDs are unique across debug flows and do not change when a debug flow is renamed, so the
+/// snapshot of a node is found without knowing its debug flow.
+pub fn snapshot_name(node_id: &str) -> String {
+    node_id.to_string()
+}
+
+/// Get the IDs of the nodes of the debug flow `flow_id`
+fn node_ids(flows_dir: &flow::FlowsDir, flow_id: &str) -> Result<HashSet<String>, Error> {
+    Ok(flows_dir
+        .get_flow_by_id(flow_id)?
+        .data()
+        .reactflow()
+        .nodes()
+        .iter()
+        .map(|n| n.id().to_string())
+        .collect())
+}
+
+/// Fail if the debug flow `flow_id` has no node `node_id`
+fn check_node(flows_dir: &flow::FlowsDir, flow_id: &str, node_id: &str) -> Result<(), Error> {
+    if !node_ids(flows_dir, flow_id)?.contains(node_id) {
+        return Err(flow::Error::NodeNotFound(node_id.to_string()).into());
+    }
+    Ok(())
+}
+
+/// Snapshot the working tree and the index and link the action node `node_id` of the debug flow
+/// `flow_id` to it
+///
+/// The debug flow directory is not included in the snapshot. An existing snapshot of the node is
+/// replaced.
+pub async fn create(
+    flows_dir: &flow::FlowsDir,
+    git_actor: &GitActorAddr,
+    flow_id: &str,
+    node_id: &str,
+) -> Result<git2_ox::Snapshot, Error> {
+    let data = flows_dir.get_flow_by_id(flow_id)?.data().clone();
+    let node = data
+        .reactflow()
+        .node(node_id)
+        .ok_or_else(|| flow::Error::NodeNotFound(node_id.to_string()))?;
+    if !matches!(node, graph::Node::Action(_)) {
+        return Err(Error::NotAnActionNode(node_id.to_string()));
+    }
+    let excluded = flows_dir
+        .path()
+        .strip_prefix(flows_dir.git_repo())
+        .map(|p| vec![p.to_string_lossy().to_string()])
+        .unwrap_or_default();
+
+    let snapshot = git_actor
+        .call(git_actor::CreateSnapshot {
+            name: snapshot_name(node_id),
+            message: format!("Snapshot of '{}'", node.title()),
+            excluded,
+        })
+        .await??;
+    let git = graph::GitMetadata::commit(snapshot.commit().id(), snapshot.commit().summary());
+    let (flow_id, node_id) = (flow_id.to_string(), node_id.to_string());
+    flows_dir
+        .blocking(move |flows_dir| {
+            flows_dir.update_flow(&flow_id, |data| {
+                let node = data
+                    .reactflow_mut()
+                    .node_mut(&node_id)
+                    .ok_or_else(|| flow::Error::NodeNotFound(node_id.clone()))?;
+                *node.git_mut() = Some(git);
+                Ok(())
+            })
+        })
+        .await?;
+    Ok(snapshot)
+}
+
+/// List the snapshots of the nodes of the debug flow `flow_id`
+pub async fn list(
+    flows_dir: &flow::FlowsDir,
+    git_actor: &GitActorAddr,
+    flow_id: &str,
+) -> Result<Vec<git2_ox::Snapshot>, Error> {
+    // Fail for unknown debug flows instead of returning no snapshots
+    let node_ids = node_ids(flows_dir, flow_id)?;
+    let snapshots = git_actor
+        .call(git_actor::ListSnapshots {
+            prefix: String::new(),
+        })
+        .await??;
+    Ok(snapshots
+        .into_iter()
+        .filter(|snapshot| node_ids.contains(snapshot.name()))
+        .collect())
+}
+
+/// Restore the working tree and the index of the snapshot of the node `node_id` of the debug flow
+/// `flow_id`, local changes are handled according to `mode`
+pub async fn restore(
+    flows_dir: &flow::FlowsDir,
+    git_actor: &GitActorAddr,
+    flow_id: &str,
+    node_id: &str,
+    mode: git2_ox::CheckoutMode,
+) -> Result<git2_ox::Snapshot, Error> {
+    check_node(flows_dir, flow_id, node_id)?;
+    Ok(git_actor
+        .call(git_actor::RestoreSnapshot {
+            name: snapshot_name(node_id),
+            mode,
+        })
+        .await??)
+}
+
+/// Delete the snapshot of the node `node_id` of the debug flow `flow_id`
+///
+/// The node stays linked to the commit of the snapshot until it is removed by the garbage
+/// collection of git.
+pub async fn delete(
+    flows_dir: &flow::FlowsDir,
+    git_actor: &GitActorAddr,
+    flow_id: &str,
+    node_id: &str,
+) -> Result<(), Error> {
+    check_node(flows_dir, flow_id, node_id)?;
+    Ok(git_actor
+        .call(git_actor::DeleteSnapshot {
+            name: snapshot_name(node_id),
+        })
+        .await??)
+}
+
+/// Delete the snapshots of nodes which do not exist anymore in any debug flow
+///
+/// Nothing is deleted if a debug flow can not be read, its nodes are unknown. Returns the deleted
+/// snapshots, their commits are removed by the next garbage collection of git.
+pub async fn prune(
+    flows_dir: &flow::FlowsDir,
+    git_actor: &GitActorAddr,
+) -> Result<Vec<git2_ox::Snapshot>, Error> {
+    let mut node_ids = HashSet::new();
+    for path in flows_dir.flow_files()? {
+        match flow::FlowData::from_file(&path) {
+            Ok(data) => {
+                node_ids.extend(data.reactflow().nodes().iter().map(|n| n.id().to_string()))
+            }
+            Err(e) => {
+                log::warn!(
+                    "Keeping all snapshots, debug flow '{}': {e}",
+                    path.display()
+                );
+                return Ok(Vec::new());
+            }
+        }
+    }
+
+    let snapshots = git_actor
+        .call(git_actor::ListSnapshots {
+            prefix: String::new(),
+        })
+        .await??;
+    let mut pruned = Vec::new();
+    for snapshot in snapshots {
+        if node_ids.contains(snapshot.name()) {
+            continue;
+        }
+        git_actor
+            .call(git_actor::DeleteSnapshot {
+                name: snapshot.name().to_string(),
+            })
+            .await??;
+        pruned.push(snapshot);
+    }
+    Ok(pruned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hannibal::prelude::*;
+
+    #[tokio::test]
+    async fn test_create_and_prune() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init(temp_dir.path()).unwrap();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "Initial", &tree, &[])
+            .unwrap();
+        std::fs::write(temp_dir.path().join("fix.txt"), "speculative fix\n").unwrap();
+
+        let flows_dir = flow::FlowsDir::try_new(temp_dir.path()).unwrap();
+        let mut data = flow::FlowData::new("foo");
+        let status = flow::Node::new_status(Default::default(), Default::default());
+        let status_id = data.reactflow_mut().add_node(None, status).unwrap();
+        let action = flow::Node::new_action(
+            graph::ActionNodeData {
+                title: "Try a fix".to_string(),
+                ..Default::default()
+            },
+            Default::default(),
+        );
+        let action_id = data
+            .reactflow_mut()
+            .add_node(Some(&status_id), action)
+            .unwrap();
+        flows_dir.save_flow(&data).unwrap();
+        let git_actor = git_actor::GitActor::try_from_path(temp_dir.path())
+            .unwrap()
+            .spawn();
+
+        assert!(matches!(
+            create(&flows_dir, &git_actor, &data.id(), &status_id).await,
+            Err(Error::NotAnActionNode(_))
+        ));
+        let snapshot = create(&flows_dir, &git_actor, &data.id(), &action_id)
+            .await
+            .unwrap();
+        assert_eq!(snapshot.commit().summary(), "Snapshot of 'Try a fix'");
+        let stored = flows_dir.get_flow_by_id(&data.id()).unwrap();
+        let node = stored.data().reactflow().node(&action_id).unwrap();
+        assert_eq!(node.git().unwrap().rev, snapshot.commit().id());
+        // The commit of the snapshot is only reachable from its reference, the node is not stale
+        let stale = git_actor
+            .call(git_actor::CheckRevisions {
+                data: stored.data().clone(),
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(stale.is_empty());
+        let listed = list(&flows_dir, &git_actor, &data.id()).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        let files = repository_files(temp_dir.path(), snapshot.commit().id());
+        assert_eq!(files, ["fix.txt"]);
+
+        assert!(prune(&flows_dir, &git_actor).await.unwrap().is_empty());
+        flows_dir
+            .update_flow(&data.id(), |data| {
+                data.reactflow_mut()
+                    .nodes_mut()
+                    .retain(|n| n.id() != action_id);
+                Ok(())
+            })
+            .unwrap();
+        let pruned = prune(&flows_dir, &git_actor).await.unwrap();
+        assert_eq!(pruned.len(), 1);
+        assert!(
+            list(&flows_dir, &git_actor, &data.id())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rename_and_prune() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init(temp_dir.path()).unwrap();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "Initial", &tree, &[])
+            .unwrap();
+
+        let flows_dir = flow::FlowsDir::try_new(temp_dir.path()).unwrap();
+        let mut data = flow::FlowData::new("foo");
+        let action = flow::Node::new_action(Default::default(), Default::default());
+        let action_id = data.reactflow_mut().add_node(None, action).unwrap();
+        flows_dir.save_flow(&data).unwrap();
+        let git_actor = git_actor::GitActor::try_from_path(temp_dir.path())
+            .unwrap()
+            .spawn();
+        create(&flows_dir, &git_actor, &data.id(), &action_id)
+            .await
+            .unwrap();
+
+        // The snapshots of a renamed debug flow belong to its new ID
+        let renamed = flows_dir.rename_flow(&data.id(), "bar").unwrap();
+        assert_ne!(renamed.id(), data.id());
+        assert!(prune(&flows_dir, &git_actor).await.unwrap().is_empty());
+        let listed = list(&flows_dir, &git_actor, &renamed.id()).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        restore(
+            &flows_dir,
+            &git_actor,
+            &renamed.id(),
+            &action_id,
+            git2_ox::CheckoutMode::Refuse,
+        )
+        .await
+        .unwrap();
+        assert!(matches!(
+            delete(&flows_dir, &git_actor, &data.id(), &action_id).await,
+            Err(Error::Flow(_))
+        ));
+        delete(&flows_dir, &git_actor, &renamed.id(), &action_id)
+            .await
+            .unwrap();
+        assert!(
+            list(&flows_dir, &git_actor, &renamed.id())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    /// Get the names of the files in the root tree of `revision`
+    fn repository_files(path: &std::path::Path, revision: &str) -> Vec<String> {
+        let repository = git2_ox::Repository::try_open(path).unwrap();
+        repository
+            .list_tree(revision, "")
+            .unwrap()
+            .iter()
+            .map(|e| e.name().to_string())
+            .collect()
+    }
+}
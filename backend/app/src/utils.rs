@@ -1,7 +1,45 @@
+use std::io::Write;
+use std::{fs, io, path};
+
 pub fn to_kebab_case(s: &str) -> String {
     s.to_lowercase().replace(' ', "-").replace("_", "-")
 }
 
+/// Atomically replace the file at `path` with `content`
+///
+/// The content is written to a temporary file in the same directory, which is synced to disk and
+/// renamed to `path`. A crash during the write leaves either the old or the new file behind, never a
+/// truncated one.
+pub fn write_atomic(path: &path::Path, content: &[u8]) -> io::Result<()> {
+    let dir = path.parent().unwrap_or(path::Path::new("."));
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Path has no file name"))?;
+    let mut tmp_path = dir.to_path_buf();
+    tmp_path.push(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    let result = (|| {
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(content)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result?;
+
+    // Persist the rename itself, opening directories is not supported on all platforms
+    if let Ok(dir) = fs::File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -13,4 +51,14 @@ mod tests {
         assert_eq!(to_kebab_case("Rust Programming"), "rust-programming");
         assert_eq!(to_kebab_case("Debug Tree CLI"), "debug-tree-cli");
     }
+
+    #[test]
+    fn test_write_atomic() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("foo.json");
+        write_atomic(&path, b"foo").unwrap();
+        write_atomic(&path, b"bar").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "bar");
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
 }
@@ -0,0 +1,138 @@
+use crate::{actors, events, flow};
+use notify::Watcher;
+use std::collections::{HashMap, HashSet};
+use std::{ffi, path, time};
+
+/// Time to wait for further file system events before processing a batch of events.
+///
+/// Git operations and atomic writes touch several files at once, batching them avoids publishing
+/// the same change multiple times.
+const DEBOUNCE_DURATION: time::Duration = time::Duration::from_millis(100);
+
+/// Watch the git directory and the debug flow directory for changes
+///
+/// Changes in the git directory are forwarded to the `GitActor` which publishes events if `HEAD` or
+/// the references changed. Changes of flow files are published as flow events through the
+/// `GitActor`. The watcher stops when the returned object is dropped.
+pub fn watch(
+    git_dir: &path::Path,
+    flows_dir: &flow::FlowsDir,
+    git_actor: actors::git::GitActorAddr,
+) -> notify::Result<notify::RecommendedWatcher> {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<path::PathBuf>();
+    let mut watcher =
+        notify::recommended_watcher(move |res: notify::Result<notify::Event>| match res {
+            Ok(event) => {
+                for p in event.paths {
+                    let _ = tx.send(p);
+                }
+            }
+            Err(e) => log::warn!("File system watcher error: {e}"),
+        })?;
+
+    watcher.watch(git_dir, notify::RecursiveMode::NonRecursive)?;
+    let refs_dir = git_dir.join("refs");
+    if refs_dir.is_dir() {
+        watcher.watch(&refs_dir, notify::RecursiveMode::Recursive)?;
+    }
+    watcher.watch(flows_dir.path(), notify::RecursiveMode::NonRecursive)?;
+
+    let git_dir = git_dir.to_path_buf();
+    let flows_path = flows_dir.path().to_path_buf();
+    let mut known_flows: HashSet<String> = flows_dir
+        .flow_files()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|p| Some(p.file_stem()?.to_str()?.to_string()))
+        .collect();
+
+    tokio::spawn(async move {
+        while let Some(first) = rx.recv().await {
+            let mut paths = HashSet::from([first]);
+            while let Ok(Some(p)) = tokio::time::timeout(DEBOUNCE_DURATION, rx.recv()).await {
+                paths.insert(p);
+            }
+
+            if paths.iter().any(|p| p.starts_with(&git_dir)) {
+                let _ = git_actor.send(actors::git::RepositoryChanged).await;
+            }
+            for event in flow_events(&flows_path, &paths, &mut known_flows) {
+                let _ = git_actor.send(actors::git::PublishEvent { event }).await;
+            }
+        }
+    });
+    Ok(watcher)
+}
+
+/// Get the events for the changed `paths` in the debug flow directory
+///
+/// Whether a flow was created, updated or deleted is derived from the existence of the flow file
+/// and the set of `known_flows`, which is updated accordingly.
+fn flow_events(
+    flows_path: &path::Path,
+    paths: &HashSet<path::PathBuf>,
+    known_flows: &mut HashSet<String>,
+) -> Vec<events::Event> {
+    let mut flow_paths = HashMap::new();
+    for p in paths {
+        if p.parent() != Some(flows_path) || p.extension() != Some(ffi::OsStr::new("json")) {
+            continue;
+        }
+        if let Some(id) = p.file_stem().and_then(|s| s.to_str()) {
+            flow_paths.insert(id.to_string(), p);
+        }
+    }
+
+    let mut events: Vec<_> = flow_paths
+        .into_iter()
+        .map(|(id, p)| match (p.is_file(), known_flows.contains(&id)) {
+            (true, true) => events::Event::FlowUpdated { id },
+            (true, false) => {
+                known_flows.insert(id.clone());
+                events::Event::FlowCreated { id }
+            }
+            (false, _) => {
+                known_flows.remove(&id);
+                events::Event::FlowDeleted { id }
+            }
+        })
+        .collect();
+    events.sort_by(|a, b| a.name().cmp(b.name()));
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flow_events() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let flows_path = temp_dir.path();
+        let foo = flows_path.join("foo.json");
+        std::fs::write(&foo, "{}").unwrap();
+        let mut known_flows = HashSet::new();
+
+        let paths = HashSet::from([foo.clone(), flows_path.join(".foo.json.1.tmp")]);
+        assert_eq!(
+            flow_events(flows_path, &paths, &mut known_flows),
+            vec![events::Event::FlowCreated {
+                id: "foo".to_string()
+            }]
+        );
+        assert_eq!(
+            flow_events(flows_path, &paths, &mut known_flows),
+            vec![events::Event::FlowUpdated {
+                id: "foo".to_string()
+            }]
+        );
+        std::fs::remove_file(&foo).unwrap();
+        assert_eq!(
+            flow_events(flows_path, &paths, &mut known_flows),
+            vec![events::Event::FlowDeleted {
+                id: "foo".to_string()
+            }]
+        );
+        assert!(known_flows.is_empty());
+    }
+}
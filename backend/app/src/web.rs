@@ -1,4 +1,4 @@
-use crate::{actors, flow};
+use crate::{actors, flow, watcher};
 use axum::routing;
 #[cfg(not(debug_assertions))]
 use axum::{http, response::IntoResponse};
@@ -34,16 +34,30 @@ pub struct ApiDoc;
 struct AppState {
     flows_dir: flow::FlowsDir,
     git_actor: actors::git::GitActorAddr,
+    /// Watcher publishing changes of the repository and the debug flows, stops when dropped
+    _watcher: Option<std::sync::Arc<notify::RecommendedWatcher>>,
 }
 
 impl AppState {
     pub fn try_new(flows_dir: flow::FlowsDir) -> Result<Self, Box<dyn std::error::Error>> {
         let repo = flows_dir.git_repo();
-        let git_actor = crate::actors::git::GitActor::try_from_path(repo)?.spawn();
+        let git_actor = crate::actors::git::GitActor::try_from_path(repo)?;
+        git_actor.remove_stale_run_worktrees();
+        let git_dir = git_actor.git_dir().to_path_buf();
+        let git_actor = git_actor.spawn();
+
+        let watcher = match watcher::watch(&git_dir, &flows_dir, git_actor.clone()) {
+            Ok(w) => Some(std::sync::Arc::new(w)),
+            Err(e) => {
+                log::warn!("Failed to watch for changes, no live updates will be published: {e}");
+                None
+            }
+        };
 
         Ok(Self {
             flows_dir,
             git_actor,
+            _watcher: watcher,
         })
     }
 
@@ -15,6 +15,10 @@ pub enum AppError {
     InternalServerError(String),
     #[error("{0}")]
     BadRequest(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("{message}")]
+    ConflictingPaths { message: String, paths: Vec<String> },
     #[error("JSON Deserialization Error")]
     JsonExtractionError(#[from] axum::extract::rejection::JsonRejection), // Handle Axum's JSON parsing errors
 }
@@ -26,6 +30,7 @@ impl AppError {
             AppError::NotFound(_) => http::StatusCode::NOT_FOUND,
             AppError::InternalServerError(_) => http::StatusCode::INTERNAL_SERVER_ERROR,
             AppError::BadRequest(_) => http::StatusCode::BAD_REQUEST,
+            AppError::Conflict(_) | AppError::ConflictingPaths { .. } => http::StatusCode::CONFLICT,
             AppError::JsonExtractionError(rejection) => rejection.status(),
         }
     }
@@ -59,6 +64,91 @@ impl From<git2_ox::error::Error> for AppError {
     fn from(error: git2_ox::error::Error) -> Self {
         match error {
             git2_ox::error::Error::NotFound(_) => AppError::NotFound(error.to_string()),
+            git2_ox::error::Error::Invalid(_) => AppError::BadRequest(error.to_string()),
+            git2_ox::error::Error::Conflict(ref conflict) => AppError::ConflictingPaths {
+                paths: conflict.paths.clone(),
+                message: error.to_string(),
+            },
+            _ => AppError::InternalServerError(error.to_string()),
+        }
+    }
+}
+
+impl From<crate::bisect::Error> for AppError {
+    fn from(error: crate::bisect::Error) -> Self {
+        use crate::{bisect, flow};
+
+        match error {
+            bisect::Error::Git(e) => e.into(),
+            bisect::Error::Flow(flow::Error::Io(path, e))
+                if e.kind() == std::io::ErrorKind::NotFound =>
+            {
+                AppError::NotFound(format!("Debug flow file '{}'", path.display()))
+            }
+            bisect::Error::Flow(flow::Error::NodeNotFound(_)) | bisect::Error::NotStarted(_) => {
+                AppError::NotFound(error.to_string())
+            }
+            bisect::Error::Finished(_) => AppError::Conflict(error.to_string()),
+            bisect::Error::Invalid(_) => AppError::BadRequest(error.to_string()),
+            _ => AppError::InternalServerError(error.to_string()),
+        }
+    }
+}
+
+impl From<crate::revisions::Error> for AppError {
+    fn from(error: crate::revisions::Error) -> Self {
+        use crate::{flow, revisions};
+
+        match error {
+            revisions::Error::Git(e) => e.into(),
+            revisions::Error::Flow(flow::Error::Io(path, e))
+                if e.kind() == std::io::ErrorKind::NotFound =>
+            {
+                AppError::NotFound(format!("Debug flow file '{}'", path.display()))
+            }
+            revisions::Error::Flow(flow::Error::NodeNotFound(_)) => {
+                AppError::NotFound(error.to_string())
+            }
+            _ => AppError::InternalServerError(error.to_string()),
+        }
+    }
+}
+
+impl From<crate::snapshots::Error> for AppError {
+    fn from(error: crate::snapshots::Error) -> Self {
+        use crate::{flow, snapshots};
+
+        match error {
+            snapshots::Error::Git(e) => e.into(),
+            snapshots::Error::Flow(flow::Error::Io(path, e))
+                if e.kind() == std::io::ErrorKind::NotFound =>
+            {
+                AppError::NotFound(format!("Debug flow file '{}'", path.display()))
+            }
+            snapshots::Error::Flow(flow::Error::NodeNotFound(_)) => {
+                AppError::NotFound(error.to_string())
+            }
+            snapshots::Error::NotAnActionNode(_) => AppError::BadRequest(error.to_string()),
+            _ => AppError::InternalServerError(error.to_string()),
+        }
+    }
+}
+
+impl From<crate::worktrees::Error> for AppError {
+    fn from(error: crate::worktrees::Error) -> Self {
+        use crate::{flow, worktrees};
+
+        match error {
+            worktrees::Error::Git(e) => e.into(),
+            worktrees::Error::Flow(flow::Error::Io(path, e))
+                if e.kind() == std::io::ErrorKind::NotFound =>
+            {
+                AppError::NotFound(format!("Debug flow file '{}'", path.display()))
+            }
+            worktrees::Error::Flow(flow::Error::NodeNotFound(_)) => {
+                AppError::NotFound(error.to_string())
+            }
+            worktrees::Error::NoRevision(_) => AppError::BadRequest(error.to_string()),
             _ => AppError::InternalServerError(error.to_string()),
         }
     }
@@ -105,11 +195,15 @@ pub struct ApiStatusDetailResponse {
 
 impl From<AppError> for ApiStatusDetailResponse {
     fn from(app_error: AppError) -> Self {
+        let details = match &app_error {
+            AppError::ConflictingPaths { paths, .. } => Some(paths.clone()),
+            _ => None,
+        };
         ApiStatusDetailResponse {
             status: app_error.code().as_u16(),
             reason: app_error.reason(),
             message: app_error.message(),
-            details: None,
+            details,
         }
     }
 }
@@ -134,12 +228,16 @@ pub mod v1 {
 
     use crate::web;
 
+    mod events;
     mod flows;
     mod git;
 
     /// API documentation for the v1 endpoints.
     #[derive(utoipa::OpenApi)]
-    #[openapi(nest(
+    #[openapi(
+        paths(flows::migrate_flows),
+        nest(
+        (path = "/events", api = events::ApiDoc),
         (path = "/flows", api = flows::ApiDoc),
         (path = "/git", api = git::ApiDoc)
     ))]
@@ -148,6 +246,7 @@ pub mod v1 {
     /// Get the router for the v1 API.
     pub(super) fn router() -> routing::Router<web::AppState> {
         routing::Router::new()
+            .merge(events::router())
             .merge(flows::router())
             .nest("/git", git::router())
     }
@@ -0,0 +1,59 @@
+use std::convert::Infallible;
+
+use axum::extract::State;
+use axum::response::sse;
+use axum::routing;
+use futures::Stream;
+
+use crate::{
+    actors, events,
+    web::{self, api},
+};
+
+pub fn router() -> routing::Router<web::AppState> {
+    routing::Router::new().route("/events", routing::get(stream_events))
+}
+
+/// API documentation for the events endpoints.
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(stream_events), tags((name = "Events", description="Live updates of the repository and debug flows")))]
+pub(super) struct ApiDoc;
+
+#[utoipa::path(
+    get,
+    path = "",
+    summary = "Stream events",
+    description = "Stream changes of the repository and the debug flows as server-sent events. \
+    The `event` field of each server-sent event contains the type of the event, the `data` field \
+    the JSON serialized event. Events are published when `HEAD` or the references of the \
+    repository change and when debug flow files are created, updated or deleted, also if the \
+    change was done outside of debug-flow.",
+    responses(
+        (status = http::StatusCode::OK, description = "Stream of events", body = events::Event, content_type = "text/event-stream"),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn stream_events(
+    State(app_state): State<web::AppState>,
+) -> Result<sse::Sse<impl Stream<Item = Result<sse::Event, Infallible>>>, api::AppError> {
+    let receiver = app_state.git_actor().call(actors::git::Subscribe).await?;
+
+    let stream = futures::stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    let sse_event = sse::Event::default()
+                        .event(event.name())
+                        .json_data(&event)
+                        .unwrap_or_else(|_| sse::Event::default().event(event.name()));
+                    return Some((Ok(sse_event), receiver));
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
+                    log::warn!("Event stream lagged behind, skipped {n} events");
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+    Ok(sse::Sse::new(stream).keep_alive(sse::KeepAlive::default()))
+}
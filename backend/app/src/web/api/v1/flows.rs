@@ -1,18 +1,53 @@
 use std::io;
 
-use axum::extract::{Path, State};
+use axum::extract::{Path, Query, State};
+use axum::response::{IntoResponse, Response};
 use axum::{Json, http, routing};
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
 
 use crate::{
-    flow,
+    actors, bisect, export, flow, revisions, snapshots,
     web::{self, api},
+    worktrees,
 };
 
 pub fn router() -> routing::Router<web::AppState> {
     routing::Router::new()
         .route("/flows", routing::get(list_flows).post(create_flow))
+        .route("/flows-migration", routing::post(migrate_flows))
+        .route("/flows/{id}/backups", routing::get(list_backups))
+        .route(
+            "/flows/{id}/backups/{backup_id}/restore",
+            routing::post(restore_backup),
+        )
+        .route(
+            "/flows/{id}/bisect",
+            routing::get(get_bisect)
+                .post(start_bisect)
+                .delete(reset_bisect),
+        )
+        .route("/flows/{id}/bisect/verdict", routing::post(bisect_verdict))
+        .route("/flows/{id}/bisect/run", routing::post(run_bisect))
+        .route("/flows/{id}/export", routing::get(export_flow))
+        .route(
+            "/flows/{id}/revisions",
+            routing::get(check_revisions).post(rewrite_revisions),
+        )
+        .route("/flows/{id}/snapshots", routing::get(list_snapshots))
+        .route(
+            "/flows/{id}/nodes/{node_id}/snapshot",
+            routing::post(create_snapshot).delete(delete_snapshot),
+        )
+        .route(
+            "/flows/{id}/nodes/{node_id}/snapshot/restore",
+            routing::post(restore_snapshot),
+        )
+        .route("/flows/{id}/worktrees", routing::get(list_worktrees))
+        .route(
+            "/flows/{id}/nodes/{node_id}/worktree",
+            routing::post(open_worktree).delete(remove_worktree),
+        )
         .route(
             "/flows/{id}",
             routing::get(get_flow).delete(delete_flow).post(store_flow),
@@ -21,7 +56,7 @@ pub fn router() -> routing::Router<web::AppState> {
 
 /// API documentation for the flows endpoints.
 #[derive(utoipa::OpenApi)]
-#[openapi(paths(list_flows, create_flow, get_flow, delete_flow, store_flow), tags((name = "Debug Flow Management", description="Debug Flow related endpoints")) )]
+#[openapi(paths(list_flows, create_flow, get_flow, delete_flow, store_flow, list_backups, restore_backup, get_bisect, start_bisect, reset_bisect, bisect_verdict, run_bisect, export_flow, check_revisions, rewrite_revisions, list_snapshots, create_snapshot, delete_snapshot, restore_snapshot, list_worktrees, open_worktree, remove_worktree), tags((name = "Debug Flow Management", description="Debug Flow related endpoints")) )]
 pub(super) struct ApiDoc;
 
 #[derive(Serialize, ToSchema)]
@@ -106,7 +141,8 @@ async fn create_flow(
 ) -> api::Result<CreateFlowResponse> {
     let resp: CreateFlowResponse = app_state
         .flows_dir()
-        .create_flow(&new_flow.name, false)
+        .blocking(move |flows_dir| flows_dir.create_flow(&new_flow.name, false))
+        .await
         .map_err(|e| api::AppError::InternalServerError(e.to_string()))?
         .try_into()
         .map_err(|e: flow::Error| api::AppError::InternalServerError(e.to_string()))?;
@@ -142,9 +178,11 @@ impl From<&flow::FlowData> for FullFlowRequestResponse {
     get,
     path = "/{id}",
     summary="Get debug flow",
-    description = "Get debug flow",
+    description = "Get debug flow. The `ETag` header of the response identifies the state of the debug flow, \
+    it can be passed in the `If-Match` header when storing the debug flow to detect concurrent modifications.",
     responses(
-        (status = http::StatusCode::OK, description = "Debug flow is available", body = FullFlowRequestResponse),
+        (status = http::StatusCode::OK, description = "Debug flow is available", body = FullFlowRequestResponse,
+            headers(("ETag" = String, description = "Entity tag of the debug flow"))),
         (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
         (status = http::StatusCode::NOT_FOUND, description = "File not found", body = api::ApiStatusDetailResponse),
     )
@@ -152,7 +190,7 @@ impl From<&flow::FlowData> for FullFlowRequestResponse {
 async fn get_flow(
     State(app_state): State<web::AppState>,
     Path(id): Path<String>,
-) -> api::Result<FullFlowRequestResponse> {
+) -> Result<impl IntoResponse, api::AppError> {
     let flow = match app_state.flows_dir().get_flow_by_id(&id) {
         Ok(p) => p,
         Err(flow::Error::Io(_, io_err)) => match io_err.kind() {
@@ -168,7 +206,8 @@ async fn get_flow(
         Err(e) => return Err(api::AppError::InternalServerError(e.to_string())),
     };
 
-    Ok(Json(flow.data().into()))
+    let response: FullFlowRequestResponse = flow.data().into();
+    Ok(([(http::header::ETAG, flow.data().etag())], Json(response)))
 }
 
 #[utoipa::path(
@@ -198,23 +237,91 @@ async fn delete_flow(
     }
 }
 
+/// Response sent if a debug flow could not be stored because it was modified concurrently
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct FlowModifiedResponse {
+    /// HTTP status code
+    status: u16,
+    /// Canonical reason for the error
+    reason: String,
+    /// Error message
+    message: String,
+    /// Entity tag of the stored debug flow, not set if the debug flow does not exist
+    etag: Option<String>,
+    /// Stored state of the debug flow, not set if the debug flow does not exist
+    flow: Option<flow::FlowData>,
+}
+
+/// Parse the entity tags of an `If-Match` header
+///
+/// Weak entity tags are kept as they are. `If-Match` requires a strong comparison, so they never
+/// match and the request fails with status 412.
+fn parse_if_match(headers: &http::HeaderMap) -> Result<Option<Vec<String>>, api::AppError> {
+    let Some(value) = headers.get(http::header::IF_MATCH) else {
+        return Ok(None);
+    };
+    let value = value
+        .to_str()
+        .map_err(|_| api::AppError::BadRequest("Invalid If-Match header".to_string()))?;
+    Ok(Some(
+        value
+            .split(',')
+            .map(|etag| etag.trim().to_string())
+            .filter(|etag| !etag.is_empty())
+            .collect(),
+    ))
+}
+
 #[utoipa::path(
     post,
     path = "/{id}",
     summary = "Store debug flow",
-    description = "Store debug flow",
+    description = "Store debug flow. If the `If-Match` header is set, the debug flow is only stored if its \
+    stored state matches one of the entity tags, otherwise the stored state is returned with status 412.",
+    params(
+        ("If-Match" = Option<String>, Header, description = "Entity tags of the expected stored state of the debug flow"),
+    ),
     responses(
-        (status = http::StatusCode::OK, description = "Debug flow is stored", body = api::ApiStatusResponse),
+        (status = http::StatusCode::OK, description = "Debug flow is stored", body = api::ApiStatusResponse,
+            headers(("ETag" = String, description = "Entity tag of the stored debug flow"))),
+        (status = http::StatusCode::PRECONDITION_FAILED, description = "Debug flow was modified concurrently", body = FlowModifiedResponse),
         (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
     )
 )]
 async fn store_flow(
     State(app_state): State<web::AppState>,
     Path(id): Path<String>,
+    headers: http::HeaderMap,
     Json(new_flow): Json<FullFlowRequestResponse>,
-) -> api::Result<api::ApiStatusResponse> {
-    match app_state.flows_dir().save_flow(&new_flow.flow) {
-        Ok(_) => Ok(Json(http::StatusCode::OK.into())),
+) -> Result<Response, api::AppError> {
+    let if_match = parse_if_match(&headers)?;
+
+    let stored = app_state
+        .flows_dir()
+        .blocking(move |flows_dir| {
+            let expected = if_match
+                .as_ref()
+                .map(|etags| etags.iter().map(String::as_str).collect::<Vec<_>>());
+            flows_dir.save_flow_if_match(&new_flow.flow, expected.as_deref())
+        })
+        .await;
+    match stored {
+        Ok(etag) => {
+            let response: api::ApiStatusResponse = http::StatusCode::OK.into();
+            Ok(([(http::header::ETAG, etag)], Json(response)).into_response())
+        }
+        Err(flow::Error::Modified(flow_id, current)) => {
+            let code = http::StatusCode::PRECONDITION_FAILED;
+            let response = FlowModifiedResponse {
+                status: code.as_u16(),
+                reason: code.canonical_reason().unwrap_or("Unknown").to_string(),
+                message: format!("Debug flow '{flow_id}' was modified by someone else"),
+                etag: current.as_ref().map(|c| c.etag()),
+                flow: current.map(|c| *c),
+            };
+            Ok((code, Json(response)).into_response())
+        }
         Err(flow::Error::Io(_, io_err)) => match io_err.kind() {
             io::ErrorKind::NotFound => Err(api::AppError::NotFound(id)),
             _ => Err(api::AppError::InternalServerError(
@@ -224,3 +331,557 @@ async fn store_flow(
         Err(e) => Err(api::AppError::InternalServerError(e.to_string())),
     }
 }
+
+#[derive(Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct MigrateFlowsQuery {
+    /// Only report which flows would be migrated, do not write any files
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Migration of all debug flows, kept out of `/flows/{id}` so it cannot collide with the ID of a
+/// debug flow
+#[utoipa::path(
+    post,
+    path = "/flows-migration",
+    tag = "Debug Flow Management",
+    summary = "Migrate debug flows",
+    description = "Migrate all debug flow files to the current file format version in place. \
+    Flow files in older versions are also migrated when they are loaded, this endpoint persists the \
+    migration for all flows at once.",
+    params(MigrateFlowsQuery),
+    responses(
+        (status = http::StatusCode::OK, description = "Migration report", body = flow::MigrationReport),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+pub(super) async fn migrate_flows(
+    State(app_state): State<web::AppState>,
+    Query(query): Query<MigrateFlowsQuery>,
+) -> api::Result<flow::MigrationReport> {
+    let report = app_state
+        .flows_dir()
+        .blocking(move |flows_dir| flows_dir.migrate_flows(query.dry_run))
+        .await
+        .map_err(|e| api::AppError::InternalServerError(e.to_string()))?;
+    Ok(Json(report))
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct ListBackupsResponse {
+    /// Backups of the debug flow, newest first
+    backups: Vec<flow::FlowBackup>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/backups",
+    summary = "List debug flow backups",
+    description = "List the backups of a debug flow. A backup of the previous state is created \
+    every time a debug flow is stored, only the newest backups are kept.",
+    responses(
+        (status = http::StatusCode::OK, description = "List of backups", body = ListBackupsResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn list_backups(
+    State(app_state): State<web::AppState>,
+    Path(id): Path<String>,
+) -> api::Result<ListBackupsResponse> {
+    let backups = app_state
+        .flows_dir()
+        .list_backups(&id)
+        .map_err(|e| api::AppError::InternalServerError(e.to_string()))?;
+    Ok(Json(ListBackupsResponse { backups }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/{id}/backups/{backup_id}/restore",
+    summary = "Restore debug flow backup",
+    description = "Restore a backup of a debug flow. The current state of the debug flow is \
+    backed up before it is replaced.",
+    responses(
+        (status = http::StatusCode::OK, description = "Backup restored", body = FullFlowRequestResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::NOT_FOUND, description = "Backup not found", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn restore_backup(
+    State(app_state): State<web::AppState>,
+    Path((id, backup_id)): Path<(String, String)>,
+) -> api::Result<FullFlowRequestResponse> {
+    let restored = {
+        let (id, backup_id) = (id.clone(), backup_id.clone());
+        app_state
+            .flows_dir()
+            .blocking(move |flows_dir| flows_dir.restore_backup(&id, &backup_id))
+            .await
+    };
+    match restored {
+        Ok(data) => Ok(Json(data.into())),
+        Err(flow::Error::Io(_, io_err)) if io_err.kind() == io::ErrorKind::NotFound => Err(
+            api::AppError::NotFound(format!("Backup '{backup_id}' of debug flow '{id}'")),
+        ),
+        Err(e) => Err(api::AppError::InternalServerError(e.to_string())),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/bisect",
+    summary = "Get bisection",
+    description = "Get the bisection in progress for a debug flow and the commit to test next.",
+    responses(
+        (status = http::StatusCode::OK, description = "Bisection state", body = bisect::BisectStatus),
+        (status = http::StatusCode::NOT_FOUND, description = "No bisection in progress", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn get_bisect(
+    State(app_state): State<web::AppState>,
+    Path(id): Path<String>,
+) -> api::Result<bisect::BisectStatus> {
+    let status = bisect::status(app_state.flows_dir(), app_state.git_actor(), &id).await?;
+    Ok(Json(status))
+}
+
+#[utoipa::path(
+    post,
+    path = "/{id}/bisect",
+    summary = "Start bisection",
+    description = "Start a bisection between a good and a bad revision, given directly or taken \
+    from status nodes of the debug flow. A bisection in progress is replaced. \
+    Every verdict is recorded as a new status node, chained to the previous one.",
+    request_body = bisect::StartBisect,
+    responses(
+        (status = http::StatusCode::OK, description = "Bisection started", body = bisect::BisectStatus),
+        (status = http::StatusCode::BAD_REQUEST, description = "Good or bad revision missing", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::NOT_FOUND, description = "Debug flow, node or revision not found", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn start_bisect(
+    State(app_state): State<web::AppState>,
+    Path(id): Path<String>,
+    Json(start): Json<bisect::StartBisect>,
+) -> api::Result<bisect::BisectStatus> {
+    let status = bisect::start(app_state.flows_dir(), app_state.git_actor(), &id, start).await?;
+    Ok(Json(status))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/{id}/bisect",
+    summary = "Reset bisection",
+    description = "Stop the bisection in progress for a debug flow. Recorded status nodes are kept.",
+    responses(
+        (status = http::StatusCode::OK, description = "Bisection stopped", body = api::ApiStatusResponse),
+        (status = http::StatusCode::NOT_FOUND, description = "No bisection in progress", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn reset_bisect(
+    State(app_state): State<web::AppState>,
+    Path(id): Path<String>,
+) -> api::Result<api::ApiStatusResponse> {
+    bisect::reset(app_state.flows_dir(), &id).await?;
+    Ok(Json(http::StatusCode::OK.into()))
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct BisectVerdictRequest {
+    /// Verdict for the commit
+    verdict: bisect::Verdict,
+    /// Revision the verdict is for, defaults to the commit proposed by the bisection
+    #[serde(default)]
+    revision: Option<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/{id}/bisect/verdict",
+    summary = "Record bisection verdict",
+    description = "Mark a commit as good, bad or skipped. The verdict is recorded as a status node \
+    and the next commit to test is returned. Once the first bad commit is found, it is recorded as \
+    a final status node.",
+    request_body = BisectVerdictRequest,
+    responses(
+        (status = http::StatusCode::OK, description = "Verdict recorded", body = bisect::BisectStatus),
+        (status = http::StatusCode::NOT_FOUND, description = "No bisection in progress", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::CONFLICT, description = "Bisection is finished already", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn bisect_verdict(
+    State(app_state): State<web::AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<BisectVerdictRequest>,
+) -> api::Result<bisect::BisectStatus> {
+    let status = bisect::record_verdict(
+        app_state.flows_dir(),
+        app_state.git_actor(),
+        &id,
+        request.verdict,
+        request.revision,
+    )
+    .await?;
+    Ok(Json(status))
+}
+
+#[derive(Deserialize, ToSchema, Default)]
+#[serde(rename_all = "camelCase")]
+struct RunBisectRequest {
+    /// Time in seconds after which the command is killed for a single commit
+    #[serde(default)]
+    timeout_secs: Option<u64>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/{id}/bisect/run",
+    summary = "Run bisection",
+    description = "Decide the verdicts with the command of the bisection until the first bad \
+    commit is found. The command runs in a temporary worktree for every commit, an exit code of 0 \
+    marks a commit as good, 125 skips it and any other code up to 127 marks it as bad. Resetting the \
+    bisection stops the run.",
+    request_body = RunBisectRequest,
+    responses(
+        (status = http::StatusCode::OK, description = "Bisection finished", body = bisect::BisectStatus),
+        (status = http::StatusCode::BAD_REQUEST, description = "Bisection has no command or the command failed", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::NOT_FOUND, description = "No bisection in progress or bisection reset while running", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn run_bisect(
+    State(app_state): State<web::AppState>,
+    Path(id): Path<String>,
+    request: Option<Json<RunBisectRequest>>,
+) -> api::Result<bisect::BisectStatus> {
+    let request = request.map(|Json(r)| r).unwrap_or_default();
+    let timeout = request.timeout_secs.map(std::time::Duration::from_secs);
+    let status = bisect::run(app_state.flows_dir(), app_state.git_actor(), &id, timeout).await?;
+    Ok(Json(status))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+struct ExportFlowQuery {
+    /// Format to export the debug flow in
+    #[serde(default)]
+    #[param(inline)]
+    format: export::ExportFormat,
+    /// Include the diff stats of the revisions linked to action nodes
+    #[serde(default)]
+    diff_stats: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/export",
+    summary = "Export debug flow",
+    description = "Export a debug flow as a document. The `markdown` format renders a debugging \
+    report, walking the flow from the root node with branching paths as nested sections, including \
+    the linked commits. The `mermaid` and `dot` formats render the graph as a Mermaid flowchart or \
+    Graphviz DOT graph, with nodes shaped by their type and coloured by their state.",
+    params(ExportFlowQuery),
+    responses(
+        (status = http::StatusCode::OK, description = "Exported debug flow", content(
+            (String = "text/markdown"), (String = "text/vnd.mermaid"), (String = "text/vnd.graphviz")
+        )),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::NOT_FOUND, description = "File not found", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn export_flow(
+    State(app_state): State<web::AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<ExportFlowQuery>,
+) -> Result<impl IntoResponse, api::AppError> {
+    let flow = match app_state.flows_dir().get_flow_by_id(&id) {
+        Ok(flow) => flow,
+        Err(flow::Error::Io(_, io_err)) if io_err.kind() == io::ErrorKind::NotFound => {
+            return Err(api::AppError::NotFound(id));
+        }
+        Err(e) => return Err(api::AppError::InternalServerError(e.to_string())),
+    };
+    let msg = actors::git::ExportFlow {
+        data: flow.data().clone(),
+        format: query.format,
+        options: export::ExportOptions {
+            diff_stats: query.diff_stats,
+        },
+    };
+    let document = app_state.git_actor().call(msg).await?;
+    Ok((
+        [(http::header::CONTENT_TYPE, query.format.content_type())],
+        document,
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/revisions",
+    summary = "Check revisions",
+    description = "Find the nodes of a debug flow linked to revisions which are not reachable from \
+    any branch, tag, remote branch or `HEAD` anymore, e.g. after a rebase, an amend or deleting a \
+    branch. Replacement commits are suggested for each of them, found in the reflog, by patch ID or \
+    by summary and author.",
+    responses(
+        (status = http::StatusCode::OK, description = "Nodes with stale revisions", body = Vec<revisions::StaleNode>),
+        (status = http::StatusCode::NOT_FOUND, description = "File not found", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn check_revisions(
+    State(app_state): State<web::AppState>,
+    Path(id): Path<String>,
+) -> api::Result<Vec<revisions::StaleNode>> {
+    let stale = revisions::check(app_state.flows_dir(), app_state.git_actor(), &id).await?;
+    Ok(Json(stale))
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct RewriteRevisionsRequest {
+    /// New revisions of the nodes
+    rewrites: Vec<revisions::RevisionRewrite>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/{id}/revisions",
+    summary = "Rewrite revisions",
+    description = "Link nodes of a debug flow to new revisions in a single save, e.g. to the \
+    replacements suggested for stale revisions. Nothing is changed if any revision or node does not \
+    exist. Returns the nodes which are still linked to stale revisions.",
+    request_body = RewriteRevisionsRequest,
+    responses(
+        (status = http::StatusCode::OK, description = "Nodes with stale revisions", body = Vec<revisions::StaleNode>),
+        (status = http::StatusCode::NOT_FOUND, description = "Debug flow, node or revision not found", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn rewrite_revisions(
+    State(app_state): State<web::AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<RewriteRevisionsRequest>,
+) -> api::Result<Vec<revisions::StaleNode>> {
+    let stale = revisions::rewrite(
+        app_state.flows_dir(),
+        app_state.git_actor(),
+        &id,
+        request.rewrites,
+    )
+    .await?;
+    Ok(Json(stale))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/snapshots",
+    summary = "List snapshots",
+    description = "List the snapshots of uncommitted work taken for the nodes of a debug flow.",
+    responses(
+        (status = http::StatusCode::OK, description = "Snapshots of the debug flow", body = Vec<git2_ox::Snapshot>),
+        (status = http::StatusCode::NOT_FOUND, description = "Debug flow not found", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn list_snapshots(
+    State(app_state): State<web::AppState>,
+    Path(id): Path<String>,
+) -> api::Result<Vec<git2_ox::Snapshot>> {
+    let snapshots = snapshots::list(app_state.flows_dir(), app_state.git_actor(), &id).await?;
+    Ok(Json(snapshots))
+}
+
+#[utoipa::path(
+    post,
+    path = "/{id}/nodes/{node_id}/snapshot",
+    summary = "Snapshot uncommitted work",
+    description = "Record the working tree and the index in a commit, like `git stash create`, and \
+    link the action node to it. The commit is referenced by \
+    `refs/debug-flow/snapshots/<flow-id>/<node-id>`, so no branch is changed. Untracked files are \
+    included unless they are ignored, the debug flow directory is never included. An existing \
+    snapshot of the node is replaced.",
+    responses(
+        (status = http::StatusCode::OK, description = "Snapshot created", body = git2_ox::Snapshot),
+        (status = http::StatusCode::BAD_REQUEST, description = "Node is not an action node", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::NOT_FOUND, description = "Debug flow or node not found", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn create_snapshot(
+    State(app_state): State<web::AppState>,
+    Path((id, node_id)): Path<(String, String)>,
+) -> api::Result<git2_ox::Snapshot> {
+    let snapshot =
+        snapshots::create(app_state.flows_dir(), app_state.git_actor(), &id, &node_id).await?;
+    Ok(Json(snapshot))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/{id}/nodes/{node_id}/snapshot",
+    summary = "Delete snapshot",
+    description = "Delete the snapshot of a node. Its commit is removed by the next garbage \
+    collection of git.",
+    responses(
+        (status = http::StatusCode::NO_CONTENT, description = "Snapshot deleted"),
+        (status = http::StatusCode::NOT_FOUND, description = "Flow, node or snapshot not found", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn delete_snapshot(
+    State(app_state): State<web::AppState>,
+    Path((id, node_id)): Path<(String, String)>,
+) -> Result<http::StatusCode, api::AppError> {
+    snapshots::delete(app_state.flows_dir(), app_state.git_actor(), &id, &node_id).await?;
+    Ok(http::StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+struct RestoreSnapshotQuery {
+    /// Handling of local changes: `refuse` to restore if tracked files have local changes,
+    /// `stash` them or `force` the restore discarding them
+    #[serde(default)]
+    #[param(inline, default = "refuse")]
+    mode: git2_ox::CheckoutMode,
+}
+
+#[utoipa::path(
+    post,
+    path = "/{id}/nodes/{node_id}/snapshot/restore",
+    params(RestoreSnapshotQuery),
+    summary = "Restore snapshot",
+    description = "Restore the working tree and the index recorded in the snapshot of a node. \
+    `HEAD` is detached at the commit which was checked out when the snapshot was taken. Stashed \
+    local changes are kept in the stash. If local changes conflict with the snapshot, the \
+    conflicting paths are listed in the details of the error.",
+    responses(
+        (status = http::StatusCode::OK, description = "Snapshot restored", body = git2_ox::Snapshot),
+        (status = http::StatusCode::NOT_FOUND, description = "Flow, node or snapshot not found", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::CONFLICT, description = "Local changes conflict with the snapshot", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn restore_snapshot(
+    State(app_state): State<web::AppState>,
+    Path((id, node_id)): Path<(String, String)>,
+    Query(query): Query<RestoreSnapshotQuery>,
+) -> api::Result<git2_ox::Snapshot> {
+    let snapshot = snapshots::restore(
+        app_state.flows_dir(),
+        app_state.git_actor(),
+        &id,
+        &node_id,
+        query.mode,
+    )
+    .await?;
+    Ok(Json(snapshot))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/worktrees",
+    summary = "List worktrees",
+    description = "List the worktrees opened for the nodes of a debug flow.",
+    responses(
+        (status = http::StatusCode::OK, description = "Worktrees of the debug flow", body = Vec<worktrees::FlowWorktree>),
+        (status = http::StatusCode::NOT_FOUND, description = "Debug flow not found", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn list_worktrees(
+    State(app_state): State<web::AppState>,
+    Path(id): Path<String>,
+) -> api::Result<Vec<worktrees::FlowWorktree>> {
+    let worktrees =
+        worktrees::list(app_state.flows_dir(), app_state.git_actor(), Some(&id)).await?;
+    Ok(Json(worktrees))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+struct OpenWorktreeQuery {
+    /// Name of a new branch to check out in the worktree, `HEAD` is detached if not set
+    #[param(nullable = false)]
+    branch: Option<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/{id}/nodes/{node_id}/worktree",
+    params(OpenWorktreeQuery),
+    summary = "Open node in worktree",
+    description = "Check out the revision of a node in its own worktree, so the checkout of the \
+    repository and its build state are not touched. The worktree is added next to the repository in \
+    `<repository>.worktrees/<flow-id>/<node-id>` and named after the node. An existing worktree of \
+    the node is returned unchanged.",
+    responses(
+        (status = http::StatusCode::OK, description = "Worktree of the node", body = worktrees::FlowWorktree),
+        (status = http::StatusCode::BAD_REQUEST, description = "Node is not linked to a revision", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::NOT_FOUND, description = "Debug flow, node or revision not found", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn open_worktree(
+    State(app_state): State<web::AppState>,
+    Path((id, node_id)): Path<(String, String)>,
+    Query(query): Query<OpenWorktreeQuery>,
+) -> api::Result<worktrees::FlowWorktree> {
+    let worktree = worktrees::open(
+        app_state.flows_dir(),
+        app_state.git_actor(),
+        &id,
+        &node_id,
+        query.branch,
+    )
+    .await?;
+    Ok(Json(worktree))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+struct RemoveWorktreeQuery {
+    /// Remove the worktree even if it is locked or has local changes
+    #[serde(default)]
+    force: bool,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/{id}/nodes/{node_id}/worktree",
+    params(RemoveWorktreeQuery),
+    summary = "Remove worktree of node",
+    description = "Remove the worktree of a node including its working directory. Locked worktrees \
+    and worktrees with local changes are only removed with `force`, local changes are listed in the \
+    details of the error.",
+    responses(
+        (status = http::StatusCode::NO_CONTENT, description = "Worktree removed"),
+        (status = http::StatusCode::BAD_REQUEST, description = "Worktree is locked", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::NOT_FOUND, description = "Flow, node or worktree not found", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::CONFLICT, description = "Worktree has local changes", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn remove_worktree(
+    State(app_state): State<web::AppState>,
+    Path((id, node_id)): Path<(String, String)>,
+    Query(query): Query<RemoveWorktreeQuery>,
+) -> Result<http::StatusCode, api::AppError> {
+    worktrees::remove(
+        app_state.flows_dir(),
+        app_state.git_actor(),
+        &id,
+        &node_id,
+        query.force,
+    )
+    .await?;
+    Ok(http::StatusCode::NO_CONTENT)
+}
@@ -1,8 +1,10 @@
-use crate::{actors, web, web::api};
+use crate::{actors, flow, runner, snapshots, web, web::api, worktrees};
 
 use axum::extract::{Path, Query, State};
-use axum::{Json, routing};
-use git2_ox::{ReferenceKind, ReferenceKindFilter, ResolvedReference, commit};
+use axum::response::{IntoResponse, Response};
+use axum::{Json, http, routing};
+use futures::StreamExt;
+use git2_ox::{CommitProperties, ReferenceKind, ReferenceKindFilter, ResolvedReference, commit};
 use serde::{Deserialize, Serialize};
 use utoipa::{IntoParams, ToSchema};
 
@@ -13,18 +15,48 @@ pub fn router() -> routing::Router<web::AppState> {
             routing::get(get_revision).post(checkout_revision),
         )
         .route("/commits", routing::get(list_commits))
+        .route("/pickaxe", routing::get(search_pickaxe))
+        .route("/graph", routing::get(get_commit_graph))
         .route("/diff", routing::get(get_diff))
+        .route("/diff/working-tree", routing::get(get_working_tree_diff))
+        .route("/blame", routing::get(get_blame))
+        .route("/tree/{rev}", routing::get(list_tree))
+        .route("/tree/{rev}/{*path}", routing::get(list_tree))
+        .route("/blob/{rev}/{*path}", routing::get(read_blob))
         .route("/tags", routing::get(list_tags).post(create_tag))
         .route("/branches", routing::get(list_branches).post(create_branch))
         .route("/repository/status", routing::get(get_repository_status))
         .route("/references", routing::get(list_references))
+        .route("/run", routing::post(run_command))
+        .route("/snapshots/prune", routing::post(prune_snapshots))
+        .route("/stashes", routing::get(list_stashes).post(save_stash))
+        .route(
+            "/stashes/{stash}",
+            routing::get(get_stash).delete(drop_stash),
+        )
+        .route("/stashes/{stash}/apply", routing::post(apply_stash))
+        .route("/stashes/{stash}/pop", routing::post(pop_stash))
+        .route("/stashes/{stash}/diff", routing::get(get_stash_diff))
+        .route(
+            "/worktrees",
+            routing::get(list_worktrees).post(add_worktree),
+        )
+        .route("/worktrees/prune", routing::post(prune_worktrees))
+        .route(
+            "/worktrees/{name}",
+            routing::get(get_worktree).delete(remove_worktree),
+        )
+        .route("/worktrees/{name}/lock", routing::post(lock_worktree))
+        .route("/worktrees/{name}/unlock", routing::post(unlock_worktree))
 }
 
 #[derive(utoipa::OpenApi)]
 #[openapi(
     paths(
-        get_revision, checkout_revision, list_commits,  list_tags, create_tag, list_branches, create_branch,
-        get_repository_status, get_diff, list_references
+        get_revision, checkout_revision, list_commits, search_pickaxe, get_commit_graph, list_tags, create_tag, list_branches, create_branch,
+        get_repository_status, get_diff, get_working_tree_diff, get_blame, list_tree, read_blob, list_references, run_command, prune_snapshots,
+        list_stashes, save_stash, get_stash, drop_stash, apply_stash, pop_stash, get_stash_diff,
+        list_worktrees, add_worktree, get_worktree, remove_worktree, lock_worktree, unlock_worktree, prune_worktrees
     ),
     tags(
         (name = "Git Repository", description="Git Repository related endpoints")
@@ -64,6 +96,16 @@ async fn get_revision(
     Ok(Json(commit))
 }
 
+#[derive(Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+struct CheckoutQuery {
+    /// Handling of local changes: `refuse` to checkout if tracked files have local changes,
+    /// `stash` them and re-apply them after the checkout, or `force` the checkout discarding them
+    #[serde(default)]
+    #[param(inline, default = "refuse")]
+    mode: git2_ox::CheckoutMode,
+}
+
 #[utoipa::path(
     post,
     path = "/commit/{revision}",
@@ -72,23 +114,30 @@ async fn get_revision(
         description = "The revision of the commit to checkout.\n\n\
             This can be the short hash, full hash, a tag, or any other \
             reference such as `HEAD`, a branch name or a tag name", example = "HEAD"),
+        CheckoutQuery,
     ),
     summary="Checkout commit for a revision",
     description = "Checkout a commit by its revision.
-    The revision can be anything accepted by `git rev-parse`. For a branch it will checkout the HEAD of the branch.",
+    The revision can be anything accepted by `git rev-parse`. For a branch it will checkout the HEAD of the branch.
+    By default the checkout is refused if tracked files have local changes, the changes can also be stashed and \
+    re-applied after the checkout or discarded. If local changes prevent the checkout, or stashed changes conflict \
+    with the revision, the conflicting paths are listed in the details of the error.",
     responses(
         (status = http::StatusCode::OK, description = "Revision checked out successfully", body = commit::Commit),
         (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
         (status = http::StatusCode::NOT_FOUND, description = "Revision not found", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::CONFLICT, description = "Local changes conflict with the checkout", body = api::ApiStatusDetailResponse),
     )
 )]
 async fn checkout_revision(
     State(state): State<web::AppState>,
     Path(commit_id): Path<String>,
+    Query(query): Query<CheckoutQuery>,
 ) -> Result<Json<commit::CommitWithReferences>, api::AppError> {
     let actor = state.git_actor();
     let msg = actors::git::CheckoutRevision {
         revision: commit_id,
+        mode: query.mode,
     };
     let commit = actor.call(msg).await??;
     Ok(Json(commit))
@@ -110,6 +159,60 @@ struct ListCommitsQuery {
     /// or any other reference such a branch name. If empty, the current HEAD is used.
     #[param(nullable = false)]
     head_rev: Option<String>,
+    /// Maximum number of commits to return. If empty, all commits of the range are returned.
+    #[param(nullable = false, minimum = 1)]
+    limit: Option<usize>,
+    /// ID of the commit after which to continue the listing, usually the `nextCursor` of the
+    /// previous page. If empty, the listing starts at the head revision.
+    #[param(nullable = false)]
+    after: Option<String>,
+    /// Only list commits whose author name or email contains this string, ignoring the case
+    #[param(nullable = false)]
+    author: Option<String>,
+    /// Only list commits whose committer name or email contains this string, ignoring the case
+    #[param(nullable = false)]
+    committer: Option<String>,
+    /// Only list commits created at or after this time
+    #[param(nullable = false)]
+    since: Option<chrono::DateTime<chrono::Utc>>,
+    /// Only list commits created at or before this time
+    #[param(nullable = false)]
+    until: Option<chrono::DateTime<chrono::Utc>>,
+    /// Only list commits changing paths matching one of these pathspecs, e.g. `src/net` or
+    /// `*.rs`. Can be given multiple times.
+    #[param(min_items = 1, nullable = false)]
+    path: Option<Vec<String>>,
+    /// Only list commits whose summary or body matches this regular expression
+    #[param(nullable = false)]
+    message: Option<String>,
+}
+
+impl ListCommitsQuery {
+    /// Get the structured filter of the query
+    fn commit_filter(&self) -> Result<git2_ox::CommitFilter, api::AppError> {
+        let mut filter = git2_ox::CommitFilter::new();
+        if let Some(author) = &self.author {
+            filter = filter.author(author);
+        }
+        if let Some(committer) = &self.committer {
+            filter = filter.committer(committer);
+        }
+        if let Some(since) = self.since {
+            filter = filter.since(since);
+        }
+        if let Some(until) = self.until {
+            filter = filter.until(until);
+        }
+        if let Some(paths) = &self.path {
+            filter = filter.paths(paths.clone());
+        }
+        if let Some(message) = &self.message {
+            let message = regex::Regex::new(message)
+                .map_err(|e| api::AppError::BadRequest(format!("Invalid message pattern: {e}")))?;
+            filter = filter.message(message);
+        }
+        Ok(filter)
+    }
 }
 
 #[derive(Serialize, ToSchema)]
@@ -118,37 +221,443 @@ struct ListCommitsResponse {
     /// Array of commits between the base and head commit IDs
     /// in reverse chronological order.
     commits: Vec<git2_ox::CommitWithReferences>,
+    /// ID of the last commit if there are more commits, pass it as `after` to get the next page
+    next_cursor: Option<String>,
 }
 
+/// Content type of responses with one JSON document per line
+const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
 #[utoipa::path(
     get,
     path = "/commits",
     summary = "List commits",
     description = "List the commits in a range similar to `git log`, \
-    the commits are always ordered from newest to oldest in the tree.",
+    the commits are always ordered from newest to oldest in the tree. \
+    Besides the substring `filter` on the ID and summary, commits can be filtered by author, \
+    committer, time, changed paths and a regular expression on the message. \
+    Large ranges can be paged with `limit` and `after`, passing the `nextCursor` of a page as \
+    `after` returns the next page. \
+    If the request accepts `application/x-ndjson`, the commits are streamed one JSON object per \
+    line while the repository is walked.",
     params(ListCommitsQuery),
     responses(
-        (status = http::StatusCode::OK, description = "List of commits", body = ListCommitsResponse),
+        (status = http::StatusCode::OK, description = "List of commits", content(
+            (ListCommitsResponse = "application/json"),
+            (git2_ox::CommitWithReferences = "application/x-ndjson")
+        )),
+        (status = http::StatusCode::BAD_REQUEST, description = "Invalid limit or message pattern", body = api::ApiStatusDetailResponse),
         (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::NOT_FOUND, description = "Revision not found or `after` not part of the range", body = api::ApiStatusDetailResponse),
     )
 )]
 async fn list_commits(
     State(state): State<web::AppState>,
-    Query(query): Query<ListCommitsQuery>,
-) -> Result<Json<ListCommitsResponse>, api::AppError> {
+    headers: http::HeaderMap,
+    axum_extra::extract::Query(query): axum_extra::extract::Query<ListCommitsQuery>,
+) -> Result<Response, api::AppError> {
+    validate_limit(query.limit)?;
+    let commit_filter = query.commit_filter()?;
     let actor = state.git_actor();
     let msg = actors::git::ListCommits {
         base_rev: query.base_rev,
         head_rev: query.head_rev,
         filter: query.filter,
+        after: query.after,
+        commit_filter,
+    };
+    let receiver = actor.call(msg).await??;
+    commits_response(&headers, receiver, query.limit).await
+}
+
+/// Check that a page limit is at least 1
+fn validate_limit(limit: Option<usize>) -> Result<(), api::AppError> {
+    match limit {
+        Some(0) => Err(api::AppError::BadRequest(
+            "The limit must be at least 1".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Respond with the commits received from `receiver`
+///
+/// The commits are streamed as NDJSON if the request accepts it, otherwise a page of at most
+/// `limit` commits is returned with the cursor to the next page.
+async fn commits_response(
+    headers: &http::HeaderMap,
+    mut receiver: actors::git::CommitsReceiver,
+    limit: Option<usize>,
+) -> Result<Response, api::AppError> {
+    let limit = limit.unwrap_or(usize::MAX);
+    let ndjson = headers
+        .get(http::header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains(NDJSON_CONTENT_TYPE));
+    if ndjson {
+        // Errors of the walk before the first commit are still reported with a status code
+        let first = receiver.recv().await.transpose()?;
+        let rest = futures::stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|commit| (commit, receiver))
+        });
+        let lines = futures::stream::iter(first.map(Ok))
+            .chain(rest)
+            .take(limit)
+            .map(|commit| -> Result<Vec<u8>, axum::BoxError> {
+                // Errors after the first commit end the stream, the status code is sent already
+                let mut line = serde_json::to_vec(&commit?)?;
+                line.push(b'\n');
+                Ok(line)
+            });
+        return Ok((
+            [(http::header::CONTENT_TYPE, NDJSON_CONTENT_TYPE)],
+            axum::body::Body::from_stream(lines),
+        )
+            .into_response());
+    }
+
+    let mut commits = Vec::new();
+    let mut has_more = false;
+    while let Some(commit) = receiver.recv().await {
+        if commits.len() == limit {
+            has_more = true;
+            break;
+        }
+        commits.push(commit?);
+    }
+    let next_cursor = has_more
+        .then(|| commits.last().map(|c| c.id().to_string()))
+        .flatten();
+    Ok(Json(ListCommitsResponse {
+        commits,
+        next_cursor,
+    })
+    .into_response())
+}
+
+#[derive(Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+struct PickaxeQuery {
+    /// Find commits changing the number of occurrences of this string in a file, like
+    /// `git log -S`. Mutually exclusive with `regex`.
+    #[param(nullable = false)]
+    string: Option<String>,
+    /// Find commits adding or removing lines matching this regular expression, like
+    /// `git log -G`. Mutually exclusive with `string`.
+    #[param(nullable = false)]
+    regex: Option<String>,
+    /// Only search paths matching one of these pathspecs, e.g. `src/net` or `*.rs`. Can be given
+    /// multiple times.
+    #[param(min_items = 1, nullable = false)]
+    path: Option<Vec<String>>,
+    /// The base revision of the range, this can be short hash, full hash, a tag,
+    /// or any other reference such a branch name. If empty, the first commit is used.
+    #[param(nullable = false)]
+    base_rev: Option<String>,
+    /// The head revision of the range, this can be short hash, full hash, a tag,
+    /// or any other reference such a branch name. If empty, the current HEAD is used.
+    #[param(nullable = false)]
+    head_rev: Option<String>,
+    /// Maximum number of commits to return. If empty, all matching commits are returned.
+    #[param(nullable = false, minimum = 1)]
+    limit: Option<usize>,
+    /// ID of the commit after which to continue the search, usually the `nextCursor` of the
+    /// previous page. If empty, the search starts at the head revision.
+    #[param(nullable = false)]
+    after: Option<String>,
+}
+
+impl PickaxeQuery {
+    /// Get the change to search for
+    fn pickaxe(&self) -> Result<git2_ox::Pickaxe, api::AppError> {
+        match (&self.string, &self.regex) {
+            (Some(string), None) if !string.is_empty() => {
+                Ok(git2_ox::Pickaxe::String(string.clone()))
+            }
+            (None, Some(regex)) => regex::Regex::new(regex)
+                .map(git2_ox::Pickaxe::Regex)
+                .map_err(|e| api::AppError::BadRequest(format!("Invalid regex: {e}"))),
+            _ => Err(api::AppError::BadRequest(
+                "Exactly one of a non-empty string or a regex is required".to_string(),
+            )),
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/pickaxe",
+    summary = "Search commits by changes",
+    description = "Find the commits of a range whose changes add or remove a string or lines \
+    matching a regular expression, similar to `git log -S` and `git log -G`. \
+    Each commit is compared to its first parent, merge commits are not searched. \
+    The results are paged and streamed like the results of listing commits.",
+    params(PickaxeQuery),
+    responses(
+        (status = http::StatusCode::OK, description = "Matching commits", content(
+            (ListCommitsResponse = "application/json"),
+            (git2_ox::CommitWithReferences = "application/x-ndjson")
+        )),
+        (status = http::StatusCode::BAD_REQUEST, description = "Invalid search or limit", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::NOT_FOUND, description = "Revision not found or `after` not part of the range", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn search_pickaxe(
+    State(state): State<web::AppState>,
+    headers: http::HeaderMap,
+    axum_extra::extract::Query(query): axum_extra::extract::Query<PickaxeQuery>,
+) -> Result<Response, api::AppError> {
+    validate_limit(query.limit)?;
+    let commit_filter = git2_ox::CommitFilter::new()
+        .paths(query.path.clone().unwrap_or_default())
+        .pickaxe(query.pickaxe()?);
+    let actor = state.git_actor();
+    let msg = actors::git::ListCommits {
+        base_rev: query.base_rev,
+        head_rev: query.head_rev,
+        filter: None,
+        after: query.after,
+        commit_filter,
+    };
+    let receiver = actor.call(msg).await??;
+    commits_response(&headers, receiver, query.limit).await
+}
+
+fn default_head_revision() -> String {
+    "HEAD".to_string()
+}
+
+#[derive(Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+struct BlameQuery {
+    /// Path of the file relative to the root of the repository
+    path: String,
+    /// The revision of the file to blame, this can be short hash, full hash, a tag,
+    /// or any other reference such a branch name.
+    #[serde(default = "default_head_revision")]
+    #[param(default = "HEAD")]
+    revision: String,
+    /// First line to blame, starting at 1. If empty, blaming from the first line.
+    #[param(nullable = false, minimum = 1)]
+    start_line: Option<usize>,
+    /// Last line to blame. If empty, blaming to the last line.
+    #[param(nullable = false, minimum = 1)]
+    end_line: Option<usize>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct BlameResponse {
+    /// Hunks of lines last changed by the same commit, ordered by line
+    hunks: Vec<git2_ox::BlameHunk>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/blame",
+    summary = "Blame a file",
+    description = "Get the commits which last changed the lines of a file at a revision, similar \
+    to `git blame`. Consecutive lines last changed by the same commit are grouped into hunks. \
+    To blame the old sources of a diff, pass the base revision of the diff.",
+    params(BlameQuery),
+    responses(
+        (status = http::StatusCode::OK, description = "Blame of the file", body = BlameResponse),
+        (status = http::StatusCode::BAD_REQUEST, description = "Invalid line range", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::NOT_FOUND, description = "Revision or file not found", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn get_blame(
+    State(state): State<web::AppState>,
+    Query(query): Query<BlameQuery>,
+) -> Result<Json<BlameResponse>, api::AppError> {
+    let line_range = match (query.start_line, query.end_line) {
+        (None, None) => None,
+        (start, end) => {
+            let start = start.unwrap_or(1);
+            let end = end.unwrap_or(usize::MAX);
+            if start == 0 || end < start {
+                return Err(api::AppError::BadRequest(format!(
+                    "Invalid line range {start}-{end}, lines start at 1"
+                )));
+            }
+            Some(start..=end)
+        }
+    };
+    let actor = state.git_actor();
+    let msg = actors::git::GetBlame {
+        path: query.path,
+        revision: query.revision,
+        line_range,
+    };
+    let hunks = actor.call(msg).await??;
+    Ok(Json(BlameResponse { hunks }))
+}
+
+#[derive(Deserialize)]
+struct TreePath {
+    rev: String,
+    #[serde(default)]
+    path: String,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct ListTreeResponse {
+    /// Entries of the directory, directories first
+    entries: Vec<git2_ox::TreeEntry>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/tree/{rev}/{path}",
+    params(
+        ("rev", description = "The revision of the tree, URL encoded if it contains slashes", example = "HEAD"),
+        ("path", description = "Path of the directory relative to the root of the repository. \
+            Can be omitted together with the preceding slash to list the root directory", example = "src"),
+    ),
+    summary = "List a directory at a revision",
+    description = "List the entries of a directory at a revision without checking it out, \
+    similar to `git ls-tree -l`. Each entry contains its kind, file mode and object ID, files \
+    also their size.",
+    responses(
+        (status = http::StatusCode::OK, description = "Entries of the directory", body = ListTreeResponse),
+        (status = http::StatusCode::BAD_REQUEST, description = "Path is not a directory", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::NOT_FOUND, description = "Revision or path not found", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn list_tree(
+    State(state): State<web::AppState>,
+    Path(TreePath { rev, path }): Path<TreePath>,
+) -> Result<Json<ListTreeResponse>, api::AppError> {
+    let actor = state.git_actor();
+    let msg = actors::git::ListTree {
+        revision: rev,
+        path,
+    };
+    let entries = actor.call(msg).await??;
+    Ok(Json(ListTreeResponse { entries }))
+}
+
+fn default_max_blob_size() -> usize {
+    1024 * 1024
+}
+
+#[derive(Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+struct ReadBlobQuery {
+    /// Maximum number of bytes of the content to return, longer content is truncated
+    #[serde(default = "default_max_blob_size")]
+    #[param(default = 1048576)]
+    max_size: usize,
+}
+
+#[utoipa::path(
+    get,
+    path = "/blob/{rev}/{path}",
+    params(
+        ("rev", description = "The revision of the file, URL encoded if it contains slashes", example = "HEAD"),
+        ("path", description = "Path of the file relative to the root of the repository", example = "README.md"),
+        ReadBlobQuery,
+    ),
+    summary = "Read a file at a revision",
+    description = "Read the content of a file at a revision without checking it out, similar to \
+    `git show <rev>:<path>`. The content of binary files is not returned, long content is \
+    truncated at `maxSize` bytes.",
+    responses(
+        (status = http::StatusCode::OK, description = "Content of the file", body = git2_ox::Blob),
+        (status = http::StatusCode::BAD_REQUEST, description = "Path is not a file", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::NOT_FOUND, description = "Revision or file not found", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn read_blob(
+    State(state): State<web::AppState>,
+    Path(TreePath { rev, path }): Path<TreePath>,
+    Query(query): Query<ReadBlobQuery>,
+) -> Result<Json<git2_ox::Blob>, api::AppError> {
+    let actor = state.git_actor();
+    let msg = actors::git::ReadBlob {
+        revision: rev,
+        path,
+        max_size: Some(query.max_size),
     };
-    let commits = actor.call(msg).await??;
-    Ok(Json(ListCommitsResponse { commits }))
+    let blob = actor.call(msg).await??;
+    Ok(Json(blob))
+}
+
+fn default_graph_limit() -> usize {
+    100
 }
 
 #[derive(Serialize, ToSchema, Deserialize, IntoParams)]
 #[serde(rename_all = "camelCase")]
-struct CommitRangeQuery {
+struct CommitGraphQuery {
+    /// The base revision of the range, this can be short hash, full hash, a tag,
+    /// or any other reference such a branch name. If empty, the first commit is used.
+    #[param(nullable = false)]
+    base_rev: Option<String>,
+    /// The head revision of the range, this can be short hash, full hash, a tag,
+    /// or any other reference such a branch name. If empty, the current HEAD is used.
+    #[param(nullable = false)]
+    head_rev: Option<String>,
+    /// Number of commits to skip from the head of the range
+    #[serde(default)]
+    #[param(default = 0)]
+    offset: usize,
+    /// Maximum number of commits to return
+    #[serde(default = "default_graph_limit")]
+    #[param(default = 100)]
+    limit: usize,
+}
+
+#[utoipa::path(
+    get,
+    path = "/graph",
+    summary = "Get commit graph",
+    description = "Get a page of the commit graph of a range similar to `git log --graph`. \
+    Each row contains a commit with its parent IDs, the column of the commit and the lines to the \
+    next row. Merge and fork points are flagged. The layout is computed from the head of the range, \
+    so consecutive pages line up.",
+    params(CommitGraphQuery),
+    responses(
+        (status = http::StatusCode::OK, description = "Page of the commit graph", body = git2_ox::CommitGraph),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::NOT_FOUND, description = "Revision not found", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn get_commit_graph(
+    State(state): State<web::AppState>,
+    Query(query): Query<CommitGraphQuery>,
+) -> Result<Json<git2_ox::CommitGraph>, api::AppError> {
+    let actor = state.git_actor();
+    let msg = actors::git::GetCommitGraph {
+        base_rev: query.base_rev,
+        head_rev: query.head_rev,
+        offset: query.offset,
+        limit: query.limit,
+    };
+    let graph = actor
+        .call(msg)
+        .await??
+        .await
+        .map_err(|e| api::AppError::InternalServerError(e.to_string()))??;
+    Ok(Json(graph))
+}
+
+fn default_context_lines() -> u32 {
+    3
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+struct DiffRangeQuery {
     /// The base revision of the range, this can be short hash, full hash, a tag,
     /// or any other reference such a branch name. If empty, the first commit is used.
     #[param(nullable = false)]
@@ -159,6 +668,43 @@ struct CommitRangeQuery {
     head_rev: Option<String>,
 }
 
+#[derive(Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+struct DiffOptionsQuery {
+    /// Number of unchanged lines shown around changes
+    #[serde(default = "default_context_lines")]
+    #[param(default = 3)]
+    context_lines: u32,
+    /// Handling of whitespace changes
+    #[serde(default)]
+    #[param(inline, default = "show")]
+    whitespace: git2_ox::Whitespace,
+    /// Whether renamed files are detected
+    #[serde(default = "default_true")]
+    #[param(default = true)]
+    renames: bool,
+    /// Whether copied files are detected
+    #[serde(default)]
+    #[param(default = false)]
+    copies: bool,
+    /// Only diff paths matching one of these pathspecs, e.g. `src/net` or `*.rs`. Can be given
+    /// multiple times.
+    #[param(min_items = 1, nullable = false)]
+    path: Option<Vec<String>>,
+}
+
+impl DiffOptionsQuery {
+    /// Get the options for creating the diff
+    fn diff_options(&self) -> git2_ox::DiffOptions {
+        git2_ox::DiffOptions::new()
+            .context_lines(self.context_lines)
+            .whitespace(self.whitespace)
+            .renames(self.renames)
+            .copies(self.copies)
+            .paths(self.path.clone().unwrap_or_default())
+    }
+}
+
 #[derive(Serialize, ToSchema, IntoParams)]
 #[serde(rename_all = "camelCase")]
 struct ListDiffsResponse {
@@ -173,8 +719,10 @@ struct ListDiffsResponse {
     description = "Get the diff of a commit range. The diff consists of the patch describing the \
     differences between the two revisions, similar to the output of `git diff rev1..rev2`, the stats \
     of this diff similar to `git diff --stat` and a map of old source file contents, these can be used \
-    to expand diffs on the client side.",
-    params(CommitRangeQuery),
+    to expand diffs on the client side. The number of context lines, the handling of whitespace, \
+    the detection of renamed and copied files and the paths can be configured, the applied options \
+    are returned with the diff.",
+    params(DiffRangeQuery, DiffOptionsQuery),
     responses(
         (status = http::StatusCode::OK, description = "List of diffs", body = ListDiffsResponse),
         (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
@@ -182,12 +730,52 @@ struct ListDiffsResponse {
 )]
 async fn get_diff(
     State(state): State<web::AppState>,
-    Query(query): Query<CommitRangeQuery>,
+    axum_extra::extract::Query(range): axum_extra::extract::Query<DiffRangeQuery>,
+    axum_extra::extract::Query(options): axum_extra::extract::Query<DiffOptionsQuery>,
 ) -> Result<Json<ListDiffsResponse>, api::AppError> {
     let actor = state.git_actor();
     let msg = actors::git::GetDiff {
-        base_rev: query.base_rev,
-        head_rev: query.head_rev,
+        base_rev: range.base_rev,
+        head_rev: range.head_rev,
+        options: options.diff_options(),
+    };
+    let diff = actor.call(msg).await??;
+    Ok(Json(ListDiffsResponse { diff }))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+struct WorkingTreeDiffQuery {
+    /// Stages to compare: `unstaged` for the working tree against the index, `staged` for the
+    /// index against `HEAD` and `all` for the working tree against `HEAD`
+    #[serde(default)]
+    #[param(inline, default = "unstaged")]
+    target: git2_ox::WorkingTreeDiff,
+}
+
+#[utoipa::path(
+    get,
+    path = "/diff/working-tree",
+    summary = "Get diff of the working tree",
+    description = "Get the diff of uncommitted changes, similar to the output of `git diff`, \
+    `git diff --cached` or `git diff HEAD` depending on the compared stages. Untracked files are not \
+    included. The diff is returned in the same format and with the same options as the diff of a \
+    commit range.",
+    params(WorkingTreeDiffQuery, DiffOptionsQuery),
+    responses(
+        (status = http::StatusCode::OK, description = "Diff of the working tree", body = ListDiffsResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn get_working_tree_diff(
+    State(state): State<web::AppState>,
+    axum_extra::extract::Query(query): axum_extra::extract::Query<WorkingTreeDiffQuery>,
+    axum_extra::extract::Query(options): axum_extra::extract::Query<DiffOptionsQuery>,
+) -> Result<Json<ListDiffsResponse>, api::AppError> {
+    let actor = state.git_actor();
+    let msg = actors::git::GetWorkingTreeDiff {
+        target: query.target,
+        options: options.diff_options(),
     };
     let diff = actor.call(msg).await??;
     Ok(Json(ListDiffsResponse { diff }))
@@ -345,13 +933,17 @@ struct RepositoryStatusResponse {
     head: git2_ox::CommitWithReferences,
     /// The current branch name, not set if in a detached HEAD state
     current_branch: Option<String>,
+    /// Changed, staged, untracked and conflicted files and the upstream of the current branch
+    working_tree: git2_ox::WorkingTreeStatus,
 }
 
 #[utoipa::path(
     get,
     path = "/repository/status",
     summary = "Get repository status",
-    description = "Get the current status of the repository, including the current HEAD commit and branch.",
+    description = "Get the current status of the repository, including the current HEAD commit and branch, \
+    the status of every changed, staged, untracked or conflicted file similar to `git status` and the \
+    number of commits the current branch is ahead and behind its upstream branch.",
     responses(
         (status = http::StatusCode::OK, description = "Repository status", body = RepositoryStatusResponse),
         (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
@@ -366,6 +958,7 @@ async fn get_repository_status(
     Ok(Json(RepositoryStatusResponse {
         head: status.head,
         current_branch: status.current_branch,
+        working_tree: status.working_tree,
     }))
 }
 
@@ -426,3 +1019,502 @@ async fn list_references(
     let references = actor.call(msg).await??;
     Ok(Json(ListReferencesResponse { references }))
 }
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct RunCommandRequest {
+    /// Revision to run the command on, this can be a short hash, full hash, a tag,
+    /// or any other reference such as a branch name
+    revision: String,
+    /// Shell command to run in the root of the worktree
+    command: String,
+    /// Time in seconds after which the command is killed, no timeout if not set
+    #[serde(default)]
+    timeout_secs: Option<u64>,
+    /// Status node to record the outcome in, the outcome is not recorded if not set
+    #[serde(default)]
+    record: Option<runner::RecordTarget>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct RunCommandResponse {
+    /// Outcome of the command
+    outcome: runner::CommandOutcome,
+    /// ID of the node the outcome was recorded in
+    node_id: Option<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/run",
+    summary = "Run a command at a revision",
+    description = "Run a shell command at a revision in a temporary worktree and capture its outcome. \
+    The checkout of the repository is not touched. If `record` is set, the outcome is stored in \
+    a new or existing status node of a debug flow as `success` or `fail` with the log attached.",
+    request_body = RunCommandRequest,
+    responses(
+        (status = http::StatusCode::OK, description = "Command finished, successfully or not", body = RunCommandResponse),
+        (status = http::StatusCode::NOT_FOUND, description = "Revision, debug flow or node not found", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn run_command(
+    State(state): State<web::AppState>,
+    Json(request): Json<RunCommandRequest>,
+) -> Result<Json<RunCommandResponse>, api::AppError> {
+    if let Some(record) = &request.record {
+        // Fail early instead of after running a possibly long command
+        state
+            .flows_dir()
+            .get_flow_by_id(&record.flow_id)
+            .map_err(|e| match e {
+                flow::Error::Io(..) => {
+                    api::AppError::NotFound(format!("Debug flow '{}'", record.flow_id))
+                }
+                e => api::AppError::InternalServerError(e.to_string()),
+            })?;
+    }
+
+    let actor = state.git_actor();
+    let msg = actors::git::RunCommand {
+        revision: request.revision,
+        command: request.command,
+        timeout: request.timeout_secs.map(std::time::Duration::from_secs),
+    };
+    let outcome = actor
+        .call(msg)
+        .await??
+        .await
+        .map_err(|e| api::AppError::InternalServerError(e.to_string()))?
+        .map_err(|e| api::AppError::InternalServerError(format!("Failed to run command: {e}")))?;
+
+    let node_id = match request.record {
+        Some(record) => {
+            let recorded = outcome.clone();
+            Some(
+                state
+                    .flows_dir()
+                    .blocking(move |flows_dir| {
+                        runner::record_outcome(flows_dir, &record, &recorded)
+                    })
+                    .await
+                    .map_err(|e| match e {
+                        flow::Error::NodeNotFound(_) => api::AppError::NotFound(e.to_string()),
+                        e => api::AppError::InternalServerError(e.to_string()),
+                    })?,
+            )
+        }
+        None => None,
+    };
+    Ok(Json(RunCommandResponse { outcome, node_id }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/snapshots/prune",
+    summary = "Prune snapshots",
+    description = "Delete the snapshots of nodes which do not exist anymore and of deleted debug \
+    flows. Snapshots of debug flows which can not be read are kept. The commits of the deleted \
+    snapshots are removed by the next garbage collection of git, e.g. `git gc --prune=now`.",
+    responses(
+        (status = http::StatusCode::OK, description = "Deleted snapshots", body = Vec<git2_ox::Snapshot>),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn prune_snapshots(
+    State(state): State<web::AppState>,
+) -> Result<Json<Vec<git2_ox::Snapshot>>, api::AppError> {
+    let pruned = snapshots::prune(state.flows_dir(), state.git_actor()).await?;
+    Ok(Json(pruned))
+}
+
+#[utoipa::path(
+    get,
+    path = "/stashes",
+    summary = "List stashes",
+    description = "List the stashes of the repository, the newest first, similar to `git stash list`.",
+    responses(
+        (status = http::StatusCode::OK, description = "Stashes of the repository", body = Vec<git2_ox::Stash>),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn list_stashes(
+    State(state): State<web::AppState>,
+) -> Result<Json<Vec<git2_ox::Stash>>, api::AppError> {
+    let actor = state.git_actor();
+    let stashes = actor.call(actors::git::ListStashes).await??;
+    Ok(Json(stashes))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+struct SaveStashQuery {
+    /// Message of the stash, generated from `HEAD` if not set
+    #[param(nullable = false)]
+    message: Option<String>,
+    /// Whether untracked files are stashed as well
+    #[serde(default)]
+    include_untracked: bool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/stashes",
+    summary = "Stash local changes",
+    description = "Stash the staged and unstaged local changes and reset them to `HEAD`, similar to \
+    `git stash push`. Untracked files are only stashed and removed if `includeUntracked` is set.",
+    params(SaveStashQuery),
+    responses(
+        (status = http::StatusCode::CREATED, description = "Local changes stashed", body = git2_ox::Stash),
+        (status = http::StatusCode::BAD_REQUEST, description = "No local changes to stash", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn save_stash(
+    State(state): State<web::AppState>,
+    Query(query): Query<SaveStashQuery>,
+) -> Result<(http::StatusCode, Json<git2_ox::Stash>), api::AppError> {
+    let actor = state.git_actor();
+    let msg = actors::git::SaveStash {
+        message: query.message,
+        include_untracked: query.include_untracked,
+    };
+    let stash = actor.call(msg).await??;
+    Ok((http::StatusCode::CREATED, Json(stash)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/stashes/{stash}",
+    params(
+        ("stash",
+        description = "The position of the stash in the stash list, e.g. `0` or `stash@{0}`, or the \
+            full or abbreviated ID of its commit", example = "0"),
+    ),
+    summary = "Get stash",
+    description = "Get a single stash. Positions change when stashes are saved or dropped, nodes \
+    should reference a stash by the ID of its commit.",
+    responses(
+        (status = http::StatusCode::OK, description = "Stash exists", body = git2_ox::Stash),
+        (status = http::StatusCode::NOT_FOUND, description = "Stash not found", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn get_stash(
+    State(state): State<web::AppState>,
+    Path(stash): Path<String>,
+) -> Result<Json<git2_ox::Stash>, api::AppError> {
+    let actor = state.git_actor();
+    let stash = actor.call(actors::git::GetStash { stash }).await??;
+    Ok(Json(stash))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/stashes/{stash}",
+    params(
+        ("stash",
+        description = "The position of the stash in the stash list or the ID of its commit", example = "0"),
+    ),
+    summary = "Drop stash",
+    description = "Drop a stash, similar to `git stash drop`. Its commit is removed by the next \
+    garbage collection of git.",
+    responses(
+        (status = http::StatusCode::NO_CONTENT, description = "Stash dropped"),
+        (status = http::StatusCode::NOT_FOUND, description = "Stash not found", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn drop_stash(
+    State(state): State<web::AppState>,
+    Path(stash): Path<String>,
+) -> Result<http::StatusCode, api::AppError> {
+    let actor = state.git_actor();
+    actor.call(actors::git::DropStash { stash }).await??;
+    Ok(http::StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/stashes/{stash}/apply",
+    params(
+        ("stash",
+        description = "The position of the stash in the stash list or the ID of its commit", example = "0"),
+    ),
+    summary = "Apply stash",
+    description = "Apply a stash including its staged changes and keep it, similar to \
+    `git stash apply --index`. The stash is not applied if it would overwrite local changes. \
+    Changes conflicting with `HEAD` are merged with conflict markers. In both cases the conflicting \
+    paths are listed in the details of the error.",
+    responses(
+        (status = http::StatusCode::OK, description = "Stash applied", body = git2_ox::Stash),
+        (status = http::StatusCode::NOT_FOUND, description = "Stash not found", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::CONFLICT, description = "Stash conflicts with local changes or `HEAD`", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn apply_stash(
+    State(state): State<web::AppState>,
+    Path(stash): Path<String>,
+) -> Result<Json<git2_ox::Stash>, api::AppError> {
+    let actor = state.git_actor();
+    let msg = actors::git::ApplyStash { stash, pop: false };
+    let stash = actor.call(msg).await??;
+    Ok(Json(stash))
+}
+
+#[utoipa::path(
+    post,
+    path = "/stashes/{stash}/pop",
+    params(
+        ("stash",
+        description = "The position of the stash in the stash list or the ID of its commit", example = "0"),
+    ),
+    summary = "Pop stash",
+    description = "Apply a stash including its staged changes and drop it, similar to \
+    `git stash pop --index`. If the stash conflicts with local changes or `HEAD`, it is kept and the \
+    conflicting paths are listed in the details of the error.",
+    responses(
+        (status = http::StatusCode::OK, description = "Stash applied and dropped", body = git2_ox::Stash),
+        (status = http::StatusCode::NOT_FOUND, description = "Stash not found", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::CONFLICT, description = "Stash conflicts with local changes or `HEAD`", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn pop_stash(
+    State(state): State<web::AppState>,
+    Path(stash): Path<String>,
+) -> Result<Json<git2_ox::Stash>, api::AppError> {
+    let actor = state.git_actor();
+    let msg = actors::git::ApplyStash { stash, pop: true };
+    let stash = actor.call(msg).await??;
+    Ok(Json(stash))
+}
+
+#[utoipa::path(
+    get,
+    path = "/stashes/{stash}/diff",
+    params(
+        ("stash",
+        description = "The position of the stash in the stash list or the ID of its commit", example = "0"),
+        DiffOptionsQuery,
+    ),
+    summary = "Get diff of stash",
+    description = "Get the diff of a stash to the commit it is based on, including stashed untracked \
+    files, similar to `git stash show --patch --include-untracked`. The diff is returned in the same \
+    format and with the same options as the diff of a commit range.",
+    responses(
+        (status = http::StatusCode::OK, description = "Diff of the stash", body = ListDiffsResponse),
+        (status = http::StatusCode::NOT_FOUND, description = "Stash not found", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn get_stash_diff(
+    State(state): State<web::AppState>,
+    Path(stash): Path<String>,
+    axum_extra::extract::Query(options): axum_extra::extract::Query<DiffOptionsQuery>,
+) -> Result<Json<ListDiffsResponse>, api::AppError> {
+    let actor = state.git_actor();
+    let msg = actors::git::GetStashDiff {
+        stash,
+        options: options.diff_options(),
+    };
+    let diff = actor.call(msg).await??;
+    Ok(Json(ListDiffsResponse { diff }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/worktrees",
+    summary = "List worktrees",
+    description = "List the linked worktrees of the repository, similar to `git worktree list`. \
+    Worktrees opened for a node of a debug flow are listed with the IDs of the debug flow and the \
+    node. The main worktree is not included.",
+    responses(
+        (status = http::StatusCode::OK, description = "Worktrees of the repository", body = Vec<worktrees::FlowWorktree>),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn list_worktrees(
+    State(state): State<web::AppState>,
+) -> Result<Json<Vec<worktrees::FlowWorktree>>, api::AppError> {
+    let worktrees = worktrees::list(state.flows_dir(), state.git_actor(), None).await?;
+    Ok(Json(worktrees))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+struct AddWorktreeQuery {
+    /// Name of the worktree
+    name: String,
+    /// Path of the working directory to create, must not exist yet
+    path: String,
+    /// Revision to check out, this can be a short hash, full hash, a branch or a tag
+    revision: String,
+    /// Name of a new branch to check out in the worktree, `HEAD` is detached if not set
+    #[param(nullable = false)]
+    branch: Option<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/worktrees",
+    summary = "Add worktree",
+    description = "Add a worktree and check out a revision in it, similar to `git worktree add`. \
+    The revision is checked out on a new branch if `branch` is set, otherwise `HEAD` is detached.",
+    params(AddWorktreeQuery),
+    responses(
+        (status = http::StatusCode::CREATED, description = "Worktree added", body = git2_ox::Worktree),
+        (status = http::StatusCode::NOT_FOUND, description = "Revision not found", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn add_worktree(
+    State(state): State<web::AppState>,
+    Query(query): Query<AddWorktreeQuery>,
+) -> Result<(http::StatusCode, Json<git2_ox::Worktree>), api::AppError> {
+    let actor = state.git_actor();
+    let msg = actors::git::AddWorktree {
+        name: query.name,
+        path: query.path.into(),
+        revision: query.revision,
+        branch: query.branch,
+    };
+    let worktree = actor.call(msg).await??;
+    Ok((http::StatusCode::CREATED, Json(worktree)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/worktrees/{name}",
+    params(("name", description = "The name of the worktree")),
+    summary = "Get worktree",
+    description = "Get a single linked worktree by its name.",
+    responses(
+        (status = http::StatusCode::OK, description = "Worktree exists", body = git2_ox::Worktree),
+        (status = http::StatusCode::NOT_FOUND, description = "Worktree not found", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn get_worktree(
+    State(state): State<web::AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<git2_ox::Worktree>, api::AppError> {
+    let actor = state.git_actor();
+    let worktree = actor.call(actors::git::GetWorktree { name }).await??;
+    Ok(Json(worktree))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+struct RemoveWorktreeQuery {
+    /// Remove the worktree even if it is locked or has local changes
+    #[serde(default)]
+    force: bool,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/worktrees/{name}",
+    params(("name", description = "The name of the worktree"), RemoveWorktreeQuery),
+    summary = "Remove worktree",
+    description = "Remove a worktree including its working directory, similar to \
+    `git worktree remove`. Locked worktrees and worktrees with local changes, including untracked \
+    files, are only removed with `force`, local changes are listed in the details of the error.",
+    responses(
+        (status = http::StatusCode::NO_CONTENT, description = "Worktree removed"),
+        (status = http::StatusCode::BAD_REQUEST, description = "Worktree is locked", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::NOT_FOUND, description = "Worktree not found", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::CONFLICT, description = "Worktree has local changes", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn remove_worktree(
+    State(state): State<web::AppState>,
+    Path(name): Path<String>,
+    Query(query): Query<RemoveWorktreeQuery>,
+) -> Result<http::StatusCode, api::AppError> {
+    let actor = state.git_actor();
+    let msg = actors::git::RemoveWorktree {
+        name,
+        force: query.force,
+    };
+    actor.call(msg).await??;
+    Ok(http::StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+struct LockWorktreeQuery {
+    /// Reason for locking the worktree, shown by git
+    #[param(nullable = false)]
+    reason: Option<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/worktrees/{name}/lock",
+    params(("name", description = "The name of the worktree"), LockWorktreeQuery),
+    summary = "Lock worktree",
+    description = "Lock a worktree to protect it from being pruned or removed, similar to \
+    `git worktree lock`.",
+    responses(
+        (status = http::StatusCode::OK, description = "Worktree locked", body = git2_ox::Worktree),
+        (status = http::StatusCode::NOT_FOUND, description = "Worktree not found", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn lock_worktree(
+    State(state): State<web::AppState>,
+    Path(name): Path<String>,
+    Query(query): Query<LockWorktreeQuery>,
+) -> Result<Json<git2_ox::Worktree>, api::AppError> {
+    let actor = state.git_actor();
+    let msg = actors::git::LockWorktree {
+        name,
+        reason: query.reason,
+    };
+    let worktree = actor.call(msg).await??;
+    Ok(Json(worktree))
+}
+
+#[utoipa::path(
+    post,
+    path = "/worktrees/{name}/unlock",
+    params(("name", description = "The name of the worktree")),
+    summary = "Unlock worktree",
+    description = "Unlock a worktree, similar to `git worktree unlock`.",
+    responses(
+        (status = http::StatusCode::OK, description = "Worktree unlocked", body = git2_ox::Worktree),
+        (status = http::StatusCode::NOT_FOUND, description = "Worktree not found", body = api::ApiStatusDetailResponse),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn unlock_worktree(
+    State(state): State<web::AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<git2_ox::Worktree>, api::AppError> {
+    let actor = state.git_actor();
+    let worktree = actor.call(actors::git::UnlockWorktree { name }).await??;
+    Ok(Json(worktree))
+}
+
+#[utoipa::path(
+    post,
+    path = "/worktrees/prune",
+    summary = "Prune worktrees",
+    description = "Remove the administrative data of worktrees whose working directory was deleted, \
+    similar to `git worktree prune`. Locked worktrees are kept.",
+    responses(
+        (status = http::StatusCode::OK, description = "Pruned worktrees", body = Vec<git2_ox::Worktree>),
+        (status = http::StatusCode::INTERNAL_SERVER_ERROR, description = "Internal server error", body = api::ApiStatusDetailResponse),
+    )
+)]
+async fn prune_worktrees(
+    State(state): State<web::AppState>,
+) -> Result<Json<Vec<git2_ox::Worktree>>, api::AppError> {
+    let actor = state.git_actor();
+    let pruned = actor.call(actors::git::PruneWorktrees).await??;
+    Ok(Json(pruned))
+}
@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::actors::git::{self as git_actor, GitActorAddr};
+use crate::flow;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Flow(#[from] flow::Error),
+    #[error(transparent)]
+    Git(#[from] git2_ox::error::Error),
+    #[error(transparent)]
+    Actor(#[from] hannibal::error::ActorError),
+    #[error("Node '{0}' is not linked to a revision")]
+    NoRevision(String),
+}
+
+/// Worktree with the node of a debug flow it was opened for
+#[derive(Serialize, ToSchema, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FlowWorktree {
+    #[serde(flatten)]
+    worktree: git2_ox::Worktree,
+    /// ID of the debug flow, not set if the worktree does not belong to a debug flow
+    flow_id: Option<String>,
+    /// ID of the node the worktree was opened for
+    node_id: Option<String>,
+}
+
+impl FlowWorktree {
+    /// Get the worktree
+    pub fn worktree(&self) -> &git2_ox::Worktree {
+        &self.worktree
+    }
+
+    /// Get the ID of the debug flow the worktree belongs to
+    pub fn flow_id(&self) -> Option<&str> {
+        self.flow_id.as_deref()
+    }
+
+    /// Get the ID of the node the worktree was opened for
+    pub fn node_id(&self) -> Option<&str> {
+        self.node_id.as_deref()
+    }
+}
+
+/// Get the name of the worktree of the node `node_id`
+///
+/// Node IDs are unique across debug flows, so the worktree of a node is found without knowing its
+/// debug flow.
+pub fn worktree_name(node_id: &str) -> String {
+    node_id.to_string()
+}
+
+/// Get the path of the worktree of the node `node_id` of the debug flow `flow_id`
+///
+/// Worktrees are placed next to the repository in `<repository>.worktrees/<flow-id>/<node-id>`, so
+/// they do not show up as untracked files.
+pub fn worktree_path(
+    flows_dir: &flow::FlowsDir,
+    flow_id: &str,
+    node_id: &str,
+) -> Result<PathBuf, Error> {
+    let repo = flows_dir
+        .git_repo()
+        .canonicalize()
+        .map_err(|e| flow::Error::Io(flows_dir.git_repo().to_path_buf(), e))?;
+    let name = repo
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    Ok(repo
+        .with_file_name(format!("{name}.worktrees"))
+        .join(flow_id)
+        .join(node_id))
+}
+
+/// Open the revision of the node `node_id` of the debug flow `flow_id` in its own worktree, on the
+/// new branch `branch` or with a detached `HEAD`
+///
+/// An existing worktree of the node is returned as it is. A worktree whose directory was deleted is
+/// added again.
+pub async fn open(
+    flows_dir: &flow::FlowsDir,
+    git_actor: &GitActorAddr,
+    flow_id: &str,
+    node_id: &str,
+    branch: Option<String>,
+) -> Result<FlowWorktree, Error> {
+    let data = flows_dir.get_flow_by_id(flow_id)?.data().clone();
+    let node = data
+        .reactflow()
+        .node(node_id)
+        .ok_or_else(|| flow::Error::NodeNotFound(node_id.to_string()))?;
+    let name = worktree_name(node_id);
+    let flow_worktree = |worktree| FlowWorktree {
+        worktree,
+        flow_id: Some(flow_id.to_string()),
+        node_id: Some(node_id.to_string()),
+    };
+
+    if let Ok(worktree) = git_actor
+        .call(git_actor::GetWorktree { name: name.clone() })
+        .await?
+    {
+        if !worktree.is_prunable() {
+            return Ok(flow_worktree(worktree));
+        }
+        git_actor
+            .call(git_actor::RemoveWorktree {
+                name: name.clone(),
+                force: true,
+            })
+            .await??;
+    }
+    let revision = node
+        .git()
+        .map(|git| git.rev.clone())
+        .ok_or_else(|| Error::NoRevision(node_id.to_string()))?;
+    let path = worktree_path(flows_dir, flow_id, node_id)?;
+    // libgit2 only creates the working directory itself
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| flow::Error::Io(parent.to_path_buf(), e))?;
+    }
+    let worktree = git_actor
+        .call(git_actor::AddWorktree {
+            name,
+            path,
+            revision,
+            branch,
+        })
+        .await??;
+    Ok(flow_worktree(worktree))
+}
+
+/// List the worktrees of the repository with the debug flows and nodes they belong to, only the
+/// worktrees of the debug flow `flow_id` if it is set
+pub async fn list(
+    flows_dir: &flow::FlowsDir,
+    git_actor: &GitActorAddr,
+    flow_id: Option<&str>,
+) -> Result<Vec<FlowWorktree>, Error> {
+    let flows = match flow_id {
+        Some(flow_id) => vec![flows_dir.get_flow_by_id(flow_id)?],
+        None => flows_dir.flows()?.collect(),
+    };
+    let node_flows: HashMap<String, String> = flows
+        .iter()
+        .flat_map(|flow| {
+            flow.data()
+                .reactflow()
+                .nodes()
+                .iter()
+                .map(|node| (worktree_name(node.id()), flow.id()))
+        })
+        .collect();
+
+    let worktrees = git_actor.call(git_actor::ListWorktrees).await??;
+    Ok(worktrees
+        .into_iter()
+        .filter_map(|worktree| {
+            let node_flow = node_flows.get(worktree.name()).cloned();
+            if node_flow.is_none() && flow_id.is_some() {
+                return None;
+            }
+            let node_id = node_flow.as_ref().map(|_| worktree.name().to_string());
+            Some(FlowWorktree {
+                worktree,
+                flow_id: node_flow,
+                node_id,
+            })
+        })
+        .collect())
+}
+
+/// Remove the worktree of the node `node_id` of the debug flow `flow_id` including its working
+/// directory
+///
+/// Locked worktrees and worktrees with local changes are only removed if `force` is set.
+pub async fn remove(
+    flows_dir: &flow::FlowsDir,
+    git_actor: &GitActorAddr,
+    flow_id: &str,
+    node_id: &str,
+    force: bool,
+) -> Result<(), Error> {
+    // Only remove worktrees of nodes of the debug flow, like `list` only returns those
+    flows_dir
+        .get_flow_by_id(flow_id)?
+        .data()
+        .reactflow()
+        .node(node_id)
+        .ok_or_else(|| flow::Error::NodeNotFound(node_id.to_string()))?;
+    Ok(git_actor
+        .call(git_actor::RemoveWorktree {
+            name: worktree_name(node_id),
+            force,
+        })
+        .await??)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow::graph;
+    use hannibal::prelude::*;
+
+    #[tokio::test]
+    async fn test_open_list_and_remove() {
+        let parent_dir = tempfile::tempdir().unwrap();
+        let repo_path = parent_dir.path().join("repo");
+        let repo = git2::Repository::init(&repo_path).unwrap();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let commit = repo
+            .commit(Some("HEAD"), &signature, &signature, "Initial", &tree, &[])
+            .unwrap()
+            .to_string();
+
+        let flows_dir = flow::FlowsDir::try_new(&repo_path).unwrap();
+        let mut data = flow::FlowData::new("foo");
+        let status = flow::Node::new_status(Default::default(), Default::default());
+        let status_id = data.reactflow_mut().add_node(None, status).unwrap();
+        let action = flow::Node::new_action(
+            graph::ActionNodeData {
+                title: "Try a fix".to_string(),
+                git: Some(graph::GitMetadata::commit(&commit, "Initial")),
+                ..Default::default()
+            },
+            Default::default(),
+        );
+        let action_id = data
+            .reactflow_mut()
+            .add_node(Some(&status_id), action)
+            .unwrap();
+        flows_dir.save_flow(&data).unwrap();
+        let git_actor = git_actor::GitActor::try_from_path(&repo_path)
+            .unwrap()
+            .spawn();
+
+        assert!(matches!(
+            open(&flows_dir, &git_actor, &data.id(), &status_id, None).await,
+            Err(Error::NoRevision(_))
+        ));
+        let opened = open(
+            &flows_dir,
+            &git_actor,
+            &data.id(),
+            &action_id,
+            Some("fix".to_string()),
+        )
+        .await
+        .unwrap();
+        let expected_path = parent_dir
+            .path()
+            .canonicalize()
+            .unwrap()
+            .join("repo.worktrees")
+            .join(data.id())
+            .join(&action_id);
+        assert_eq!(opened.worktree().path(), expected_path);
+        assert_eq!(opened.worktree().branch(), Some("fix"));
+        // Opening the node again returns the existing worktree
+        let reopened = open(&flows_dir, &git_actor, &data.id(), &action_id, None)
+            .await
+            .unwrap();
+        assert_eq!(reopened.worktree().branch(), Some("fix"));
+
+        let listed = list(&flows_dir, &git_actor, None).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].flow_id(), Some(data.id().as_str()));
+        assert_eq!(listed[0].node_id(), Some(action_id.as_str()));
+
+        assert!(matches!(
+            remove(&flows_dir, &git_actor, "bar", &action_id, false).await,
+            Err(Error::Flow(_))
+        ));
+        assert!(matches!(
+            remove(&flows_dir, &git_actor, &data.id(), "baz", false).await,
+            Err(Error::Flow(flow::Error::NodeNotFound(_)))
+        ));
+        assert!(expected_path.exists());
+        remove(&flows_dir, &git_actor, &data.id(), &action_id, false)
+            .await
+            .unwrap();
+        assert!(!expected_path.exists());
+        assert!(
+            list(&flows_dir, &git_actor, Some(&data.id()))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use crate::{Commit, Result, error::Error, utils};
+use std::collections::{HashMap, HashSet};
+
+/// Next step of a bisection
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(tag = "type", rename_all = "camelCase")
+)]
+#[derive(Clone, Debug)]
+pub enum BisectStep {
+    /// A commit to test next
+    #[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+    Next {
+        /// Commit to test next
+        commit: Commit,
+        /// Number of commits which may still be the first bad commit
+        remaining: usize,
+        /// Estimated number of commits left to test, including this one
+        steps_left: u32,
+    },
+    /// The first bad commit was found
+    FirstBad {
+        /// First bad commit
+        commit: Commit,
+    },
+    /// Only skipped commits are left, the first bad commit is one of the candidates
+    Ambiguous {
+        /// Commits which may be the first bad commit
+        candidates: Vec<Commit>,
+    },
+}
+
+/// Find the next step of a bisection
+///
+/// The candidates for the first bad commit are all commits reachable from `bad`, but not from any
+/// of the `good` commits. The commit splitting the candidates into two halves of equal size is
+/// proposed next, `skipped` commits are never proposed.
+pub(crate) fn next_step(
+    repo: &git2::Repository,
+    bad: &str,
+    good: &[&str],
+    skipped: &[&str],
+) -> Result<BisectStep> {
+    let bad_oid = utils::get_commit_for_revision(repo, bad)?.id();
+    let mut revwalk = repo
+        .revwalk()
+        .map_err(|e| Error::from_ctx_and_error("Failed to create revwalk object", e))?;
+    revwalk
+        .set_sorting(git2::Sort::TOPOLOGICAL)
+        .map_err(|e| Error::from_ctx_and_error("Failed to set revwalk sorting", e))?;
+    revwalk.push(bad_oid).map_err(|e| {
+        Error::from_ctx_and_error(format!("Failed to push bad revision '{bad}'"), e)
+    })?;
+    for rev in good {
+        let oid = utils::get_commit_for_revision(repo, rev)?.id();
+        revwalk.hide(oid).map_err(|e| {
+            Error::from_ctx_and_error(format!("Failed to hide good revision '{rev}'"), e)
+        })?;
+    }
+    let candidates = revwalk
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| Error::from_ctx_and_error("Failed to walk candidate commits", e))?;
+    if candidates.is_empty() {
+        return Err(Error::from_ctx_and_error(
+            "Bisection",
+            git2::Error::new(
+                git2::ErrorCode::Invalid,
+                git2::ErrorClass::Invalid,
+                format!("bad revision '{bad}' is an ancestor of a good revision"),
+            ),
+        ));
+    }
+
+    let skipped = skipped
+        .iter()
+        .map(|rev| Ok(utils::get_commit_for_revision(repo, rev)?.id()))
+        .collect::<Result<HashSet<_>>>()?;
+    let testable: Vec<_> = candidates
+        .iter()
+        .filter(|oid| **oid != bad_oid && !skipped.contains(oid))
+        .collect();
+    if testable.is_empty() {
+        if candidates.len() == 1 {
+            return Ok(BisectStep::FirstBad {
+                commit: Commit::try_from_oid(repo, bad_oid)?,
+            });
+        }
+        return Ok(BisectStep::Ambiguous {
+            candidates: candidates
+                .iter()
+                .map(|oid| Commit::try_from_oid(repo, *oid))
+                .collect::<Result<_>>()?,
+        });
+    }
+
+    let weights = ancestor_counts(repo, &candidates)?;
+    let total = candidates.len();
+    let best = testable
+        .into_iter()
+        .max_by_key(|oid| {
+            let weight = weights[*oid];
+            weight.min(total - weight)
+        })
+        .expect("testable commits are not empty");
+    Ok(BisectStep::Next {
+        commit: Commit::try_from_oid(repo, *best)?,
+        remaining: total,
+        steps_left: total.next_power_of_two().trailing_zeros(),
+    })
+}
+
+/// Count the ancestors of each candidate within the candidates, including the candidate itself
+///
+/// `candidates` are sorted topologically, children first. They are visited once, parents first:
+/// like `git bisect`, a commit with a single parent in the range has one ancestor more than its
+/// parent, only merge commits walk their ancestors.
+fn ancestor_counts(
+    repo: &git2::Repository,
+    candidates: &[git2::Oid],
+) -> Result<HashMap<git2::Oid, usize>> {
+    let candidate_set: HashSet<_> = candidates.iter().copied().collect();
+    let mut parents: HashMap<git2::Oid, Vec<git2::Oid>> = HashMap::new();
+    let mut counts = HashMap::new();
+    for oid in candidates.iter().rev() {
+        let commit = utils::get_commit_for_oid(repo, *oid)?;
+        let in_range: Vec<_> = commit
+            .parent_ids()
+            .filter(|p| candidate_set.contains(p))
+            .collect();
+        let count = match in_range.as_slice() {
+            [] => 1,
+            [parent] => counts[parent] + 1,
+            _ => {
+                let mut seen = HashSet::from([*oid]);
+                let mut stack = in_range.clone();
+                while let Some(current) = stack.pop() {
+                    if seen.insert(current) {
+                        stack.extend(&parents[&current]);
+                    }
+                }
+                seen.len()
+            }
+        };
+        parents.insert(*oid, in_range);
+        counts.insert(*oid, count);
+    }
+    Ok(counts)
+}
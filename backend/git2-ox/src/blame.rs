@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use crate::{Commit, Result, error::Error, utils};
+
+/// Lines of a file last changed by the same commit
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "camelCase")
+)]
+#[derive(Clone, Debug)]
+pub struct BlameHunk {
+    /// Commit which last changed the lines
+    commit: Commit,
+    /// First line of the hunk in the blamed file, starting at 1
+    start_line: usize,
+    /// Number of lines of the hunk
+    num_lines: usize,
+    /// Path of the file in the commit which last changed the lines
+    orig_path: String,
+    /// First line of the hunk in the file of the commit which last changed the lines, starting at 1
+    orig_start_line: usize,
+    /// Whether the lines were not changed after the oldest commit searched
+    is_boundary: bool,
+}
+
+impl BlameHunk {
+    /// Get the commit which last changed the lines
+    pub fn commit(&self) -> &Commit {
+        &self.commit
+    }
+
+    /// Get the first line of the hunk in the blamed file, starting at 1
+    pub fn start_line(&self) -> usize {
+        self.start_line
+    }
+
+    /// Get the number of lines of the hunk
+    pub fn num_lines(&self) -> usize {
+        self.num_lines
+    }
+
+    /// Get the path of the file in the commit which last changed the lines
+    pub fn orig_path(&self) -> &str {
+        &self.orig_path
+    }
+
+    /// Get the first line of the hunk in the file of the commit which last changed the lines
+    pub fn orig_start_line(&self) -> usize {
+        self.orig_start_line
+    }
+
+    /// Get whether the lines were not changed after the oldest commit searched
+    pub fn is_boundary(&self) -> bool {
+        self.is_boundary
+    }
+}
+
+/// Count the lines of the file at `path` in `commit`
+fn count_lines(repo: &git2::Repository, commit: &git2::Commit, path: &str) -> Result<usize> {
+    let blob = commit
+        .tree()
+        .and_then(|tree| tree.get_path(Path::new(path)))
+        .and_then(|entry| entry.to_object(repo))
+        .and_then(|object| object.peel_to_blob())
+        .map_err(|e| {
+            Error::from_ctx_and_error(format!("File '{path}' at revision '{}'", commit.id()), e)
+        })?;
+    let content = blob.content();
+    let newlines = content.iter().filter(|&&b| b == b'\n').count();
+    Ok(match content.last() {
+        Some(b'\n') | None => newlines,
+        Some(_) => newlines + 1,
+    })
+}
+
+/// Blame the file at `path` as of `revision`, similar to `git blame <revision> -- <path>`
+///
+/// Only the lines in `line_range` are blamed if set, the lines start at 1. The range is cut at the
+/// end of the file, it is invalid if it starts after the end of the file.
+pub(crate) fn blame(
+    repo: &git2::Repository,
+    path: &str,
+    revision: &str,
+    line_range: Option<RangeInclusive<usize>>,
+) -> Result<Vec<BlameHunk>> {
+    let commit = utils::get_commit_for_revision(repo, revision)?;
+    let mut options = git2::BlameOptions::new();
+    options.newest_commit(commit.id());
+    if let Some(range) = line_range {
+        // libgit2 does not check the range against the length of the file
+        let num_lines = count_lines(repo, &commit, path)?;
+        if *range.start() == 0 || *range.start() > num_lines || range.is_empty() {
+            return Err(Error::from_ctx_and_error(
+                format!(
+                    "line range starting at line {} of file '{path}'",
+                    range.start()
+                ),
+                git2::Error::new(
+                    git2::ErrorCode::Invalid,
+                    git2::ErrorClass::Invalid,
+                    format!("the file has {num_lines} lines"),
+                ),
+            ));
+        }
+        options
+            .min_line(*range.start())
+            .max_line((*range.end()).min(num_lines));
+    }
+    let blame = repo
+        .blame_file(Path::new(path), Some(&mut options))
+        .map_err(|e| {
+            Error::from_ctx_and_error(format!("File '{path}' at revision '{revision}'"), e)
+        })?;
+
+    // Hunks of the same commit are usually scattered over the file
+    let mut commits: HashMap<git2::Oid, Commit> = HashMap::new();
+    let mut hunks = Vec::with_capacity(blame.len());
+    for hunk in blame.iter() {
+        let oid = hunk.final_commit_id();
+        let commit = match commits.get(&oid) {
+            Some(commit) => commit.clone(),
+            None => {
+                let commit = Commit::from(utils::get_commit_for_oid(repo, oid)?);
+                commits.insert(oid, commit.clone());
+                commit
+            }
+        };
+        hunks.push(BlameHunk {
+            commit,
+            start_line: hunk.final_start_line(),
+            num_lines: hunk.lines_in_hunk(),
+            orig_path: hunk
+                .path()
+                .map_or_else(|| path.to_string(), |p| p.to_string_lossy().to_string()),
+            orig_start_line: hunk.orig_start_line(),
+            is_boundary: hunk.is_boundary(),
+        });
+    }
+    Ok(hunks)
+}
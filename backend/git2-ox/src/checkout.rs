@@ -0,0 +1,157 @@
+use crate::{Result, error::Error, stash, utils};
+
+/// Handling of local changes when checking out a revision
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CheckoutMode {
+    /// Refuse to checkout if tracked files have local changes, staged or not, or if untracked
+    /// files would be overwritten
+    #[default]
+    Refuse,
+    /// Stash local changes of tracked files, checkout the revision and re-apply the changes, like
+    /// `git rebase --autostash`. Changes conflicting with the revision are merged with conflict
+    /// markers and kept in the stash, like `git stash pop`.
+    Stash,
+    /// Discard local changes and overwrite untracked files in the way, like `git checkout --force`
+    Force,
+}
+
+/// Get the paths of tracked files with local changes, staged or not
+fn dirty_paths(repo: &git2::Repository) -> Result<Vec<String>> {
+    let mut options = git2::StatusOptions::new();
+    options.include_untracked(false).exclude_submodules(true);
+    let statuses = repo
+        .statuses(Some(&mut options))
+        .map_err(|e| Error::from_ctx_and_error("Failed to get status", e))?;
+    Ok(statuses
+        .iter()
+        .map(|entry| String::from_utf8_lossy(entry.path_bytes()).to_string())
+        .collect())
+}
+
+/// Stash local changes of tracked files with `message`, returns whether anything was stashed
+pub(crate) fn stash_local_changes(repo: &mut git2::Repository, message: &str) -> Result<bool> {
+    if dirty_paths(repo)?.is_empty() {
+        return Ok(false);
+    }
+    let signature = utils::get_signature(repo)?;
+    repo.stash_save(&signature, message, None)
+        .map_err(|e| Error::from_ctx_and_error("Failed to stash local changes", e))?;
+    Ok(true)
+}
+
+/// Build checkout options collecting the paths of conflicting files in `conflicts`
+pub(crate) fn checkout_builder(
+    force: bool,
+    conflicts: &mut Vec<String>,
+) -> git2::build::CheckoutBuilder<'_> {
+    let mut builder = git2::build::CheckoutBuilder::new();
+    if force {
+        builder.force();
+    } else {
+        builder.safe();
+    }
+    builder
+        .notify_on(git2::CheckoutNotificationType::CONFLICT)
+        .notify(|_, path, _, _, _| {
+            if let Some(path) = path {
+                conflicts.push(path.to_string_lossy().to_string());
+            }
+            true
+        });
+    builder
+}
+
+/// Map an error of a checkout to a conflict error listing `conflicts` if it was caused by them
+pub(crate) fn checkout_error(ctx: String, e: git2::Error, conflicts: Vec<String>) -> Error {
+    match e.code() {
+        git2::ErrorCode::Conflict => Error::conflict(ctx, conflicts),
+        _ => Error::from_ctx_and_error(ctx, e),
+    }
+}
+
+/// Checkout `rev` and move `HEAD` to it, handling local changes according to `mode`
+pub(crate) fn checkout_revision(
+    repo: &mut git2::Repository,
+    rev: &str,
+    mode: CheckoutMode,
+) -> Result<()> {
+    let (oid, reference) = {
+        let (object, reference) = repo.revparse_ext(rev).map_err(|e| {
+            Error::from_ctx_and_error(format!("Failed to parse revision '{rev}'"), e)
+        })?;
+        (
+            object.id(),
+            reference.and_then(|r| r.name().map(str::to_string)),
+        )
+    };
+
+    let stashed = match mode {
+        CheckoutMode::Refuse => {
+            let dirty = dirty_paths(repo)?;
+            if !dirty.is_empty() {
+                return Err(Error::conflict(
+                    format!("Revision '{rev}' can not be checked out over local changes"),
+                    dirty,
+                ));
+            }
+            false
+        }
+        CheckoutMode::Stash => stash_local_changes(
+            repo,
+            &format!("debug-flow: autostash before checkout of '{rev}'"),
+        )?,
+        CheckoutMode::Force => false,
+    };
+
+    let mut conflicts = Vec::new();
+    let result = {
+        let object = repo
+            .find_object(oid, None)
+            .map_err(|e| Error::from_ctx_and_error(format!("Revision '{rev}'"), e))?;
+        let mut builder = checkout_builder(mode == CheckoutMode::Force, &mut conflicts);
+        repo.checkout_tree(&object, Some(&mut builder))
+    };
+    if let Err(e) = result {
+        if stashed {
+            // Nothing was checked out, the changes apply to the unchanged working tree again
+            stash::apply_stash(
+                repo,
+                0,
+                true,
+                "Failed to re-apply stashed local changes".to_string(),
+            )?;
+        }
+        return Err(checkout_error(
+            format!("Revision '{rev}' can not be checked out over local changes"),
+            e,
+            conflicts,
+        ));
+    }
+
+    match reference {
+        // gref is an actual reference like branches or tags
+        Some(gref) => repo.set_head(&gref),
+        // this is a commit, not a reference
+        None => repo.set_head_detached(oid),
+    }
+    .map_err(|e| Error::from_ctx_and_error(format!("Failed to set head to revision '{rev}'"), e))?;
+
+    if stashed {
+        stash::apply_stash(
+            repo,
+            0,
+            true,
+            format!(
+                "Revision '{rev}' was checked out, but the stashed local changes conflict with \
+                it and were kept in the stash"
+            ),
+        )?;
+    }
+    Ok(())
+}
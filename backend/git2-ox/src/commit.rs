@@ -12,11 +12,22 @@ pub trait CommitProperties {
     serde(rename_all = "camelCase")
 )]
 #[derive(Clone, Debug)]
-#[allow(dead_code)]
-struct Signature {
+pub struct Signature {
     name: String,
     email: String,
 }
+
+impl Signature {
+    /// Get the name of the signature
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the email address of the signature
+    pub fn email(&self) -> &str {
+        &self.email
+    }
+}
 impl From<git2::Signature<'_>> for Signature {
     fn from(signature: git2::Signature<'_>) -> Self {
         Signature {
@@ -44,7 +55,6 @@ impl From<Git2Time> for chrono::DateTime<chrono::Utc> {
     serde(rename_all = "camelCase")
 )]
 #[derive(Clone, Debug)]
-#[allow(dead_code)]
 pub struct Commit {
     id: String,
     summary: String,
@@ -52,6 +62,8 @@ pub struct Commit {
     time: chrono::DateTime<chrono::Utc>,
     committer: Signature,
     author: Signature,
+    /// IDs of the parent commits, the first parent first
+    parent_ids: Vec<String>,
 }
 
 impl<'repo> From<&git2::Commit<'repo>> for Commit {
@@ -63,6 +75,7 @@ impl<'repo> From<&git2::Commit<'repo>> for Commit {
             time: Git2Time(commit.time()).into(),
             committer: commit.committer().into(),
             author: commit.author().into(),
+            parent_ids: commit.parent_ids().map(|id| id.to_string()).collect(),
         }
     }
 }
@@ -83,6 +96,33 @@ impl CommitProperties for Commit {
     }
 }
 
+impl Commit {
+    /// Get the body of the commit message, without the summary
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+
+    /// Get the commit time
+    pub fn time(&self) -> chrono::DateTime<chrono::Utc> {
+        self.time
+    }
+
+    /// Get the author of the commit
+    pub fn author(&self) -> &Signature {
+        &self.author
+    }
+
+    /// Get the committer of the commit
+    pub fn committer(&self) -> &Signature {
+        &self.committer
+    }
+
+    /// Get the IDs of the parent commits, the first parent first
+    pub fn parent_ids(&self) -> &[String] {
+        &self.parent_ids
+    }
+}
+
 impl<'repo> Commit {
     /// Try to create a `Commit` from an revision string
     /// * `repo` - Reference to the repository
@@ -106,7 +146,6 @@ impl<'repo> Commit {
     serde(rename_all = "camelCase")
 )]
 #[derive(Clone, Debug)]
-#[allow(dead_code)]
 pub struct CommitWithReferences {
     #[cfg_attr(feature = "serde", serde(flatten))]
     commit: Commit,
@@ -125,6 +164,16 @@ impl CommitProperties for CommitWithReferences {
 }
 
 impl CommitWithReferences {
+    /// Get the commit
+    pub fn commit(&self) -> &Commit {
+        &self.commit
+    }
+
+    /// Get the references pointing to the commit
+    pub fn references(&self) -> &[ReferenceMetadata] {
+        &self.references
+    }
+
     pub fn try_from_git2_commit_and_references(
         commit: &git2::Commit,
         references: Option<&ReferenceMetadatas>,
@@ -0,0 +1,237 @@
+use crate::{Result, error::Error};
+
+/// Change searched in the diffs of commits, similar to the pickaxe options of `git log`
+#[derive(Clone, Debug)]
+pub enum Pickaxe {
+    /// Commits changing the number of occurrences of the string in a file, like `git log -S`
+    String(String),
+    /// Commits adding or removing a line matching the regular expression, like `git log -G`
+    Regex(regex::Regex),
+}
+
+impl Pickaxe {
+    /// Check whether `diff` contains the searched change
+    fn matches(&self, repo: &git2::Repository, diff: &git2::Diff) -> Result<bool> {
+        match self {
+            Pickaxe::String(needle) => {
+                for delta in diff.deltas() {
+                    let old = blob_content(repo, delta.old_file())?;
+                    let new = blob_content(repo, delta.new_file())?;
+                    if let (Some(old), Some(new)) = (old, new)
+                        && count_occurrences(&old, needle.as_bytes())
+                            != count_occurrences(&new, needle.as_bytes())
+                    {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            Pickaxe::Regex(regex) => {
+                let mut found = false;
+                let result = diff.foreach(
+                    &mut |_, _| true,
+                    None,
+                    None,
+                    Some(&mut |_, _, line| {
+                        if matches!(line.origin(), '+' | '-')
+                            && regex.is_match(&String::from_utf8_lossy(line.content()))
+                        {
+                            found = true;
+                        }
+                        // Stop at the first match
+                        !found
+                    }),
+                );
+                match result {
+                    Err(_) if found => Ok(true),
+                    Err(e) => Err(Error::from_ctx_and_error("Failed to iterate diff", e)),
+                    Ok(()) => Ok(false),
+                }
+            }
+        }
+    }
+}
+
+/// Get the content of the blob of `file`, empty if the file does not exist and `None` if it is
+/// binary
+fn blob_content(repo: &git2::Repository, file: git2::DiffFile) -> Result<Option<Vec<u8>>> {
+    if file.id().is_zero() {
+        return Ok(Some(Vec::new()));
+    }
+    let blob = repo
+        .find_blob(file.id())
+        .map_err(|e| Error::from_ctx_and_error("Failed to get blob", e))?;
+    Ok((!blob.is_binary()).then(|| blob.content().to_vec()))
+}
+
+/// Count the non-overlapping occurrences of `needle` in `haystack`
+fn count_occurrences(haystack: &[u8], needle: &[u8]) -> usize {
+    if needle.is_empty() {
+        return 0;
+    }
+    let mut count = 0;
+    let mut i = 0;
+    while i + needle.len() <= haystack.len() {
+        if haystack[i..].starts_with(needle) {
+            count += 1;
+            i += needle.len();
+        } else {
+            i += 1;
+        }
+    }
+    count
+}
+
+/// Filter for the commits listed by [`crate::Repository::iter_commits`]
+///
+/// All criteria which are set must match. Names and emails are matched as case-insensitive
+/// substrings, times are compared with the commit time.
+#[derive(Clone, Debug, Default)]
+pub struct CommitFilter {
+    author: Option<String>,
+    committer: Option<String>,
+    since: Option<chrono::DateTime<chrono::Utc>>,
+    until: Option<chrono::DateTime<chrono::Utc>>,
+    paths: Vec<String>,
+    message: Option<regex::Regex>,
+    pickaxe: Option<Pickaxe>,
+}
+
+impl CommitFilter {
+    /// Create a filter matching all commits
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only match commits whose author name or email contains `author`
+    pub fn author(mut self, author: impl Into<String>) -> Self {
+        self.author = Some(author.into().to_lowercase());
+        self
+    }
+
+    /// Only match commits whose committer name or email contains `committer`
+    pub fn committer(mut self, committer: impl Into<String>) -> Self {
+        self.committer = Some(committer.into().to_lowercase());
+        self
+    }
+
+    /// Only match commits created at or after `since`
+    pub fn since(mut self, since: chrono::DateTime<chrono::Utc>) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    /// Only match commits created at or before `until`
+    pub fn until(mut self, until: chrono::DateTime<chrono::Utc>) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    /// Only match commits changing a path matching one of the pathspecs `paths`
+    ///
+    /// Merge commits only match if they change these paths compared to each of their parents, so
+    /// merges taking the paths unchanged from a parent are skipped like by `git log -- <paths>`.
+    /// Unlike `git log`, the commits of all parents are still listed.
+    pub fn paths(mut self, paths: Vec<String>) -> Self {
+        self.paths = paths;
+        self
+    }
+
+    /// Only match commits whose summary or body matches `message`
+    pub fn message(mut self, message: regex::Regex) -> Self {
+        self.message = Some(message);
+        self
+    }
+
+    /// Only match commits whose diff contains the change searched by `pickaxe`, limited to the
+    /// paths if set. Merge commits never match, like in `git log`.
+    pub fn pickaxe(mut self, pickaxe: Pickaxe) -> Self {
+        self.pickaxe = Some(pickaxe);
+        self
+    }
+
+    /// Check whether `commit` matches the filter
+    ///
+    /// The paths and the pickaxe are checked last as they require a diff of the commit.
+    pub(crate) fn matches(&self, repo: &git2::Repository, commit: &git2::Commit) -> Result<bool> {
+        let signature_matches = |filter: &Option<String>, signature: git2::Signature| {
+            filter.as_ref().is_none_or(|f| {
+                [signature.name(), signature.email()]
+                    .iter()
+                    .flatten()
+                    .any(|s| s.to_lowercase().contains(f))
+            })
+        };
+        if !signature_matches(&self.author, commit.author())
+            || !signature_matches(&self.committer, commit.committer())
+        {
+            return Ok(false);
+        }
+
+        let time = chrono::DateTime::from_timestamp(commit.time().seconds(), 0);
+        if self.since.is_some_and(|s| time.is_none_or(|t| t < s))
+            || self.until.is_some_and(|u| time.is_none_or(|t| t > u))
+        {
+            return Ok(false);
+        }
+
+        if let Some(message) = &self.message {
+            let summary = commit.summary().unwrap_or_default();
+            let body = commit.body().unwrap_or_default();
+            if !message.is_match(summary) && !message.is_match(body) {
+                return Ok(false);
+            }
+        }
+
+        if self.paths.is_empty() && self.pickaxe.is_none() {
+            return Ok(true);
+        }
+        if self.pickaxe.is_some() && commit.parent_count() > 1 {
+            return Ok(false);
+        }
+        let diff = self.diff_to_parent(repo, commit, 0)?;
+        if diff.deltas().len() == 0 {
+            return Ok(false);
+        }
+        // Merges taking the paths unchanged from any parent are skipped, see `paths`
+        for parent in 1..commit.parent_count() {
+            if self.diff_to_parent(repo, commit, parent)?.deltas().len() == 0 {
+                return Ok(false);
+            }
+        }
+        match &self.pickaxe {
+            Some(pickaxe) => pickaxe.matches(repo, &diff),
+            None => Ok(true),
+        }
+    }
+
+    /// Get the diff of `commit` to its parent with index `parent`, limited to the paths if set
+    ///
+    /// Root commits are diffed to the empty tree.
+    fn diff_to_parent<'repo>(
+        &self,
+        repo: &'repo git2::Repository,
+        commit: &git2::Commit,
+        parent: usize,
+    ) -> Result<git2::Diff<'repo>> {
+        let tree = commit
+            .tree()
+            .map_err(|e| Error::from_ctx_and_error("Failed to get tree of commit", e))?;
+        let parent_tree = match commit.parent_count() {
+            0 => None,
+            _ => Some(
+                commit
+                    .parent(parent)
+                    .and_then(|p| p.tree())
+                    .map_err(|e| Error::from_ctx_and_error("Failed to get tree of parent", e))?,
+            ),
+        };
+
+        let mut options = git2::DiffOptions::new();
+        for path in &self.paths {
+            options.pathspec(path);
+        }
+        repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut options))
+            .map_err(|e| Error::from_ctx_and_error("Failed to diff commit", e))
+    }
+}
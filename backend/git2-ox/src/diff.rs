@@ -8,6 +8,7 @@ use crate::{Result, error};
     derive(serde::Serialize),
     serde(rename_all = "camelCase")
 )]
+#[derive(Clone, Debug)]
 pub struct DiffStats {
     /// Number of files changed
     files_changed: usize,
@@ -49,6 +50,121 @@ impl DiffStats {
     }
 }
 
+/// Handling of whitespace changes in a diff
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Whitespace {
+    /// Show all whitespace changes
+    #[default]
+    Show,
+    /// Ignore whitespace changes at the end of lines, like `git diff --ignore-space-at-eol`
+    IgnoreAtEol,
+    /// Ignore changes in the amount of whitespace, like `git diff -b`
+    IgnoreChange,
+    /// Ignore all whitespace, like `git diff -w`
+    IgnoreAll,
+}
+
+/// Options for creating a diff
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "camelCase")
+)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiffOptions {
+    /// Number of unchanged lines shown around changes
+    context_lines: u32,
+    /// Handling of whitespace changes
+    whitespace: Whitespace,
+    /// Whether renamed files are detected
+    renames: bool,
+    /// Whether copied files are detected
+    copies: bool,
+    /// Pathspecs the diff is limited to, all paths if empty
+    paths: Vec<String>,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            context_lines: 3,
+            whitespace: Whitespace::default(),
+            renames: true,
+            copies: false,
+            paths: Vec::new(),
+        }
+    }
+}
+
+impl DiffOptions {
+    /// Create the default options: 3 context lines, showing whitespace changes and detecting
+    /// renames, like `git diff -M`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the number of unchanged lines shown around changes
+    pub fn context_lines(mut self, context_lines: u32) -> Self {
+        self.context_lines = context_lines;
+        self
+    }
+
+    /// Set the handling of whitespace changes
+    pub fn whitespace(mut self, whitespace: Whitespace) -> Self {
+        self.whitespace = whitespace;
+        self
+    }
+
+    /// Set whether renamed files are detected
+    pub fn renames(mut self, renames: bool) -> Self {
+        self.renames = renames;
+        self
+    }
+
+    /// Set whether copied files are detected, like `git diff -C`
+    pub fn copies(mut self, copies: bool) -> Self {
+        self.copies = copies;
+        self
+    }
+
+    /// Limit the diff to paths matching one of the pathspecs `paths`
+    pub fn paths(mut self, paths: Vec<String>) -> Self {
+        self.paths = paths;
+        self
+    }
+
+    /// Get the `git2` options for creating the diff
+    pub(crate) fn to_git2_diff_options(&self) -> git2::DiffOptions {
+        let mut options = git2::DiffOptions::new();
+        options
+            .context_lines(self.context_lines)
+            .ignore_whitespace_eol(self.whitespace == Whitespace::IgnoreAtEol)
+            .ignore_whitespace_change(self.whitespace == Whitespace::IgnoreChange)
+            .ignore_whitespace(self.whitespace == Whitespace::IgnoreAll);
+        for path in &self.paths {
+            options.pathspec(path);
+        }
+        options
+    }
+
+    /// Get the `git2` options for detecting renamed and copied files, `None` if both are disabled
+    pub(crate) fn to_git2_find_options(&self) -> Option<git2::DiffFindOptions> {
+        if !self.renames && !self.copies {
+            return None;
+        }
+        let mut options = git2::DiffFindOptions::new();
+        options.renames(self.renames).copies(self.copies);
+        Some(options)
+    }
+}
+
 type Path = String;
 type FileContent = String;
 
@@ -67,6 +183,8 @@ pub struct Diff {
     stats: DiffStats,
     /// Map of old source paths to the old content
     old_sources: FilesContent,
+    /// Options the diff was created with
+    options: DiffOptions,
 }
 
 impl Diff {
@@ -82,7 +200,15 @@ impl Diff {
         &self.old_sources
     }
 
-    pub fn try_from_repo_and_diff(repo: &git2::Repository, diff: &git2::Diff) -> Result<Self> {
+    pub fn options(&self) -> &DiffOptions {
+        &self.options
+    }
+
+    pub fn try_from_repo_and_diff(
+        repo: &git2::Repository,
+        diff: &git2::Diff,
+        options: DiffOptions,
+    ) -> Result<Self> {
         let mut patch_output = String::new();
         let mut total_num_lines: usize = 0;
         let mut old_files: hash_map::HashMap<Path, FileContent> = hash_map::HashMap::new();
@@ -140,6 +266,7 @@ impl Diff {
                 total_num_lines,
             ),
             old_sources: old_files,
+            options,
         })
     }
 }
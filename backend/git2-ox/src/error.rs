@@ -22,6 +22,20 @@ impl ErrorCtx {
     }
 }
 
+/// Error of an operation refused because of local changes
+#[derive(thiserror::Error, Debug)]
+pub struct ConflictCtx {
+    pub ctx: String,
+    /// Paths of the files with conflicting local changes
+    pub paths: Vec<String>,
+}
+
+impl Display for ConflictCtx {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.ctx, self.paths.join(", "))
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Generic Error: {0}")]
@@ -30,6 +44,8 @@ pub enum Error {
     NotFound(ErrorCtx),
     #[error("Invalid {0}")]
     Invalid(ErrorCtx),
+    #[error("Conflict: {0}")]
+    Conflict(ConflictCtx),
 }
 
 impl Error {
@@ -41,6 +57,13 @@ impl Error {
         }
     }
 
+    pub(crate) fn conflict(ctx: impl Into<String>, paths: Vec<String>) -> Self {
+        Error::Conflict(ConflictCtx {
+            ctx: ctx.into(),
+            paths,
+        })
+    }
+
     pub(crate) fn from_ctx(ctx: impl Into<String>) -> Self {
         Error::from_ctx_and_error(ctx, git2::Error::from_str("Unknown"))
     }
@@ -0,0 +1,272 @@
+use crate::reference::ReferencesMap;
+use crate::{CommitWithReferences, Result, error::Error, utils};
+use std::collections::HashMap;
+
+/// Line from a column of a row of the commit graph to a column of the next row
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "camelCase")
+)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GraphEdge {
+    /// Column in the row of the edge
+    pub from: usize,
+    /// Column in the next row
+    pub to: usize,
+}
+
+/// Row of the commit graph, containing a single commit
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "camelCase")
+)]
+#[derive(Clone, Debug)]
+pub struct GraphRow {
+    /// Commit of the row
+    commit: CommitWithReferences,
+    /// Column of the commit
+    column: usize,
+    /// Lines to the next row, lines from the commit to its parents start at the column of the commit
+    edges: Vec<GraphEdge>,
+    /// Whether the commit has multiple parents
+    is_merge: bool,
+    /// Whether multiple commits in the graph have the commit as parent
+    is_fork: bool,
+}
+
+impl GraphRow {
+    /// Get the commit of the row
+    pub fn commit(&self) -> &CommitWithReferences {
+        &self.commit
+    }
+
+    /// Get the column of the commit
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    /// Get the lines to the next row
+    pub fn edges(&self) -> &[GraphEdge] {
+        &self.edges
+    }
+
+    /// Get whether the commit has multiple parents
+    pub fn is_merge(&self) -> bool {
+        self.is_merge
+    }
+
+    /// Get whether multiple commits in the graph have the commit as parent
+    pub fn is_fork(&self) -> bool {
+        self.is_fork
+    }
+}
+
+/// Page of the commit graph
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "camelCase")
+)]
+#[derive(Clone, Debug)]
+pub struct CommitGraph {
+    /// Rows of the page, from newest to oldest
+    rows: Vec<GraphRow>,
+    /// Number of columns required to draw the rows of the page
+    columns: usize,
+    /// Whether there are more rows after this page
+    has_more: bool,
+}
+
+impl CommitGraph {
+    /// Get the rows of the page, from newest to oldest
+    pub fn rows(&self) -> &[GraphRow] {
+        &self.rows
+    }
+
+    /// Get the number of columns required to draw the rows of the page
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    /// Get whether there are more rows after this page
+    pub fn has_more(&self) -> bool {
+        self.has_more
+    }
+}
+
+/// Lines leaving a row, the columns they end at are only known once the next commit is placed
+struct RowLines {
+    /// Column of the commit
+    column: usize,
+    /// Lanes passing the commit, with the lane they continue in
+    passing: Vec<(usize, usize)>,
+    /// Lanes leading to the parents of the commit
+    parents: Vec<usize>,
+}
+
+impl RowLines {
+    /// Get the edges of the row, `next` is the next commit with its column if it is expected by a
+    /// lane, all lanes expecting it converge in its column
+    fn edges(
+        &self,
+        lanes: &[Option<git2::Oid>],
+        next: Option<(git2::Oid, usize)>,
+    ) -> Vec<GraphEdge> {
+        let target = |lane: usize| match next {
+            Some((oid, column)) if lanes.get(lane) == Some(&Some(oid)) => column,
+            _ => lane,
+        };
+        let passing = self.passing.iter().map(|&(from, lane)| GraphEdge {
+            from,
+            to: target(lane),
+        });
+        let parents = self.parents.iter().map(|&lane| GraphEdge {
+            from: self.column,
+            to: target(lane),
+        });
+        passing.chain(parents).collect()
+    }
+}
+
+/// Get the first free lane, adding a lane if all are in use
+fn free_lane(lanes: &mut Vec<Option<git2::Oid>>) -> usize {
+    match lanes.iter().position(Option::is_none) {
+        Some(lane) => lane,
+        None => {
+            lanes.push(None);
+            lanes.len() - 1
+        }
+    }
+}
+
+/// Lay out the commits between `base_rev` and `head_rev` in lanes, similar to `git log --graph`
+///
+/// Each lane holds the commit expected next in it. A commit is placed in the lane expecting it,
+/// the lane then continues with its first parent, further parents get their own lanes. Lanes of
+/// other children of the commit end at the commit. The layout always starts at the newest commit,
+/// so pages of the same range line up. Rows before `offset` are only laid out, not returned.
+///
+/// * `offset` - Number of rows to skip
+/// * `limit` - Maximum number of rows to return
+pub(crate) fn commit_graph(
+    repo: &git2::Repository,
+    base_rev: Option<&str>,
+    head_rev: Option<&str>,
+    offset: usize,
+    limit: usize,
+) -> Result<CommitGraph> {
+    let mut revwalk = utils::revwalk_for_range(repo, base_rev, head_rev)?;
+    revwalk
+        .set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)
+        .map_err(|e| Error::from_ctx_and_error("Failed to sort revwalk", e))?;
+    let base = base_rev
+        .map(|rev| utils::get_commit_for_revision(repo, rev).map(|c| c.id()))
+        .transpose()?;
+    // Whether a parent is excluded by the range, only parents of walked commits are checked
+    let mut hidden: HashMap<git2::Oid, bool> = HashMap::new();
+    let mut is_hidden = |parent: git2::Oid| match base {
+        Some(base) => *hidden.entry(parent).or_insert_with(|| {
+            parent == base || repo.graph_descendant_of(base, parent).unwrap_or(false)
+        }),
+        None => false,
+    };
+    let ref_map = ReferencesMap::try_from(repo)?;
+
+    let mut lanes: Vec<Option<git2::Oid>> = Vec::new();
+    let mut children: HashMap<git2::Oid, usize> = HashMap::new();
+    let mut previous: Option<RowLines> = None;
+    let mut rows = Vec::new();
+    let mut has_more = false;
+    // One commit more than requested is walked to find the edges of the last row
+    for (index, oid) in revwalk.take(offset + limit + 1).enumerate() {
+        let oid = oid.map_err(|e| Error::from_ctx_and_error("Failed to walk commits", e))?;
+        let expecting_lane = lanes.iter().position(|l| *l == Some(oid));
+        if let Some(lines) = previous.take()
+            && index > offset
+        {
+            let row: &mut GraphRow = rows.last_mut().expect("Row of previous commit");
+            row.edges = lines.edges(&lanes, expecting_lane.map(|lane| (oid, lane)));
+        }
+        if index == offset + limit {
+            has_more = true;
+            break;
+        }
+
+        let commit = utils::get_commit_for_oid(repo, oid)?;
+        let column = expecting_lane.unwrap_or_else(|| free_lane(&mut lanes));
+        for lane in lanes.iter_mut() {
+            if *lane == Some(oid) {
+                *lane = None;
+            }
+        }
+        let mut passing: Vec<_> = lanes
+            .iter()
+            .enumerate()
+            .filter_map(|(lane, l)| l.map(|_| (lane, lane)))
+            .collect();
+        let mut parents = Vec::new();
+        for (i, parent) in commit.parent_ids().enumerate() {
+            // Parents excluded by the range do not get a lane
+            if is_hidden(parent) {
+                continue;
+            }
+            *children.entry(parent).or_default() += 1;
+            let lane = match lanes.iter().position(|l| *l == Some(parent)) {
+                // Keep the first parent in the column of the commit if it is further left, the
+                // lane already expecting it moves over
+                Some(lane) if i == 0 && lane > column => {
+                    lanes[lane] = None;
+                    for (from, passing_lane) in passing.iter_mut() {
+                        if *from == lane {
+                            *passing_lane = column;
+                        }
+                    }
+                    column
+                }
+                Some(lane) => lane,
+                None if i == 0 => column,
+                None => free_lane(&mut lanes),
+            };
+            lanes[lane] = Some(parent);
+            parents.push(lane);
+        }
+        while lanes.last() == Some(&None) {
+            lanes.pop();
+        }
+        let lines = RowLines {
+            column,
+            passing,
+            parents,
+        };
+
+        if index >= offset {
+            rows.push(GraphRow {
+                commit: CommitWithReferences::try_from_git2_commit_and_references(
+                    &commit,
+                    ref_map.get_references_for_commit_oid(oid),
+                )?,
+                column,
+                edges: lines.edges(&lanes, None),
+                is_merge: commit.parent_count() > 1,
+                is_fork: children.get(&oid).is_some_and(|&n| n > 1),
+            });
+        }
+        previous = Some(lines);
+    }
+
+    let columns = rows
+        .iter()
+        .flat_map(|r| std::iter::once(r.column).chain(r.edges.iter().flat_map(|e| [e.from, e.to])))
+        .max()
+        .map_or(0, |c| c + 1);
+    Ok(CommitGraph {
+        rows,
+        columns,
+        has_more,
+    })
+}
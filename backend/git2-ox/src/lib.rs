@@ -1,17 +1,39 @@
+pub mod bisect;
+pub mod blame;
 pub mod branch;
+pub mod checkout;
 pub mod commit;
+pub mod commit_filter;
 pub mod diff;
 pub mod error;
+pub mod graph;
 pub mod reference;
 pub mod repository;
+pub mod revision_check;
+pub mod snapshot;
+pub mod stash;
+pub mod status;
 pub mod tag;
+pub mod tree;
 pub mod utils;
+pub mod worktree;
 
+pub use bisect::BisectStep;
+pub use blame::BlameHunk;
 pub use branch::Branch;
+pub use checkout::CheckoutMode;
 pub use commit::{Commit, CommitProperties, CommitWithReferences};
-pub use diff::Diff;
+pub use commit_filter::{CommitFilter, Pickaxe};
+pub use diff::{Diff, DiffOptions, Whitespace};
+pub use graph::{CommitGraph, GraphEdge, GraphRow};
 pub use reference::{ReferenceKind, ReferenceMetadata, ResolvedReference};
 pub use repository::{ReferenceKindFilter, Repository};
+pub use revision_check::{ReplacementCandidate, ReplacementReason, RevisionCheck, RevisionState};
+pub use snapshot::Snapshot;
+pub use stash::Stash;
+pub use status::{FileChange, FileStatus, UpstreamStatus, WorkingTreeDiff, WorkingTreeStatus};
 pub use tag::TaggedCommit;
+pub use tree::{Blob, TreeEntry, TreeEntryKind};
+pub use worktree::Worktree;
 
 type Result<T> = std::result::Result<T, error::Error>;
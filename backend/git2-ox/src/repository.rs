@@ -1,8 +1,24 @@
+use crate::bisect;
+use crate::blame;
+use crate::checkout::{self, CheckoutMode};
 use crate::commit::{CommitProperties, CommitWithReferences};
+use crate::commit_filter::{CommitFilter, Pickaxe};
+use crate::diff::DiffOptions;
 use crate::error::Error;
+use crate::graph;
 use crate::reference::ReferencesMap;
-use crate::{Branch, Commit, Diff, ReferenceKind, ResolvedReference, Result, TaggedCommit, utils};
-use std::path::Path;
+use crate::revision_check;
+use crate::snapshot;
+use crate::stash;
+use crate::status::{self, WorkingTreeDiff, WorkingTreeStatus};
+use crate::tree;
+use crate::worktree;
+use crate::{
+    BisectStep, BlameHunk, Blob, Branch, Commit, CommitGraph, Diff, ReferenceKind,
+    ResolvedReference, Result, RevisionCheck, Snapshot, Stash, TaggedCommit, TreeEntry, Worktree,
+    utils,
+};
+use std::path::{Path, PathBuf};
 
 pub struct Repository {
     repo: git2::Repository,
@@ -41,30 +57,197 @@ impl Repository {
         }
     }
 
+    /// Get the directory git runs the hooks of the repository from
+    ///
+    /// This is `core.hooksPath` if it is configured, relative paths are relative to the working
+    /// directory. Otherwise it is the `hooks` directory in the git directory, which is shared by all
+    /// worktrees.
+    pub fn hooks_path(&self) -> Result<PathBuf> {
+        let config = self
+            .repo
+            .config()
+            .map_err(|e| Error::from_ctx_and_error("Failed to open config", e))?;
+        match config.get_path("core.hooksPath") {
+            Ok(path) if path.is_relative() => Ok(self
+                .repo
+                .workdir()
+                .unwrap_or_else(|| self.repo.path())
+                .join(path)),
+            Ok(path) => Ok(path),
+            Err(e) if e.code() == git2::ErrorCode::NotFound => {
+                Ok(self.repo.commondir().join("hooks"))
+            }
+            Err(e) => Err(Error::from_ctx_and_error(
+                "Failed to read core.hooksPath",
+                e,
+            )),
+        }
+    }
+
+    /// Get the working directory of the main worktree, also when the repository was opened in a
+    /// linked worktree
+    ///
+    /// Like `git rev-parse --git-common-dir`, the main worktree is found through the git directory
+    /// shared by all worktrees.
+    pub fn main_workdir(&self) -> Result<PathBuf> {
+        let ctx = "Failed to open main worktree";
+        let main = git2::Repository::open(self.repo.commondir())
+            .map_err(|e| Error::from_ctx_and_error(ctx, e))?;
+        main.workdir().map(Path::to_path_buf).ok_or_else(|| {
+            Error::from_ctx_and_error(
+                ctx,
+                git2::Error::new(
+                    git2::ErrorCode::Invalid,
+                    git2::ErrorClass::Repository,
+                    "the repository is bare",
+                ),
+            )
+        })
+    }
+
     /// Returns an iterator over Commits in the repository from `head_rev` to `base_rev`
     ///
     /// * `base_rev` - Base revision until which to iterate. Iterating to initial commit if set to `None`
     /// * `head_rev` - Head revision from which to iterate. Iterating from current `HEAD` if set to `None`
+    /// * `filter` - Filter for the commits, commits not matching it are skipped
     pub fn iter_commits(
         &self,
         base_rev: Option<&str>,
         head_rev: Option<&str>,
+        filter: CommitFilter,
+    ) -> Result<impl Iterator<Item = Result<CommitWithReferences>>> {
+        self.iter_commits_after(base_rev, head_rev, None, filter)
+    }
+
+    /// Returns an iterator over Commits in the repository from `head_rev` to `base_rev`, starting
+    /// after the commit `after`
+    ///
+    /// This continues a listing where a previous page ended. Fails with [`Error::NotFound`] if
+    /// `after` is not part of the range.
+    ///
+    /// * `base_rev` - Base revision until which to iterate. Iterating to initial commit if set to `None`
+    /// * `head_rev` - Head revision from which to iterate. Iterating from current `HEAD` if set to `None`
+    /// * `after` - Revision of the commit after which to start. Starting at `head_rev` if set to `None`
+    /// * `filter` - Filter for the commits, commits not matching it are skipped
+    pub fn iter_commits_after(
+        &self,
+        base_rev: Option<&str>,
+        head_rev: Option<&str>,
+        after: Option<&str>,
+        filter: CommitFilter,
     ) -> Result<impl Iterator<Item = Result<CommitWithReferences>>> {
-        let revwalk = utils::revwalk_for_range(&self.repo, base_rev, head_rev)?;
+        let mut revwalk = utils::revwalk_for_range(&self.repo, base_rev, head_rev)?;
+        if let Some(after) = after {
+            let after_oid = utils::get_commit_for_revision(&self.repo, after)?.id();
+            // Only the IDs are walked to skip the commits of previous pages, which is cheap
+            let mut found = false;
+            for oid in revwalk.by_ref() {
+                let oid =
+                    oid.map_err(|e| Error::from_ctx_and_error("Failed to get oid object", e))?;
+                if oid == after_oid {
+                    found = true;
+                    break;
+                }
+            }
+            if !found {
+                return Err(Error::from_ctx_and_error(
+                    format!("Commit '{after}' in range"),
+                    git2::Error::new(
+                        git2::ErrorCode::NotFound,
+                        git2::ErrorClass::Object,
+                        "commit is not part of the range",
+                    ),
+                ));
+            }
+        }
         let ref_map = ReferencesMap::try_from(&self.repo)?;
-        Ok(revwalk.map(move |oid_result| {
+        let commit_if_matching = move |oid: git2::Oid| {
+            let commit = utils::get_commit_for_oid(&self.repo, oid)?;
+            if !filter.matches(&self.repo, &commit)? {
+                return Ok(None);
+            }
+            CommitWithReferences::try_from_git2_commit_and_references(
+                &commit,
+                ref_map.get_references_for_commit_oid(oid),
+            )
+            .map(Some)
+        };
+        Ok(revwalk.filter_map(move |oid_result| {
             oid_result
                 .map_err(|e| Error::from_ctx_and_error("Failed to get oid object", e))
-                .and_then(|oid| {
-                    CommitWithReferences::try_from_oid_and_references(
-                        &self.repo,
-                        oid,
-                        ref_map.get_references_for_commit_oid(oid),
-                    )
-                })
+                .and_then(&commit_if_matching)
+                .transpose()
         }))
     }
 
+    /// Returns an iterator over the commits from `head_rev` to `base_rev` whose diff contains the
+    /// change searched by `pickaxe`, similar to `git log -S` and `git log -G`
+    ///
+    /// * `base_rev` - Base revision until which to iterate. Iterating to initial commit if set to `None`
+    /// * `head_rev` - Head revision from which to iterate. Iterating from current `HEAD` if set to `None`
+    /// * `pickaxe` - Change to search for
+    /// * `paths` - Pathspecs to limit the search to. Searching all paths if empty
+    pub fn iter_pickaxe_commits(
+        &self,
+        base_rev: Option<&str>,
+        head_rev: Option<&str>,
+        pickaxe: Pickaxe,
+        paths: Vec<String>,
+    ) -> Result<impl Iterator<Item = Result<CommitWithReferences>>> {
+        let filter = CommitFilter::new().paths(paths).pickaxe(pickaxe);
+        self.iter_commits(base_rev, head_rev, filter)
+    }
+
+    /// Get the commits which last changed the lines of the file at `path` as of `revision`,
+    /// similar to `git blame`
+    ///
+    /// * `path` - Path of the file relative to the root of the repository
+    /// * `revision` - Revision of the file to blame
+    /// * `line_range` - Lines to blame, starting at 1. Blaming all lines if set to `None`
+    pub fn blame(
+        &self,
+        path: &str,
+        revision: &str,
+        line_range: Option<std::ops::RangeInclusive<usize>>,
+    ) -> Result<Vec<BlameHunk>> {
+        blame::blame(&self.repo, path, revision, line_range)
+    }
+
+    /// List the entries of the directory at `path` as of `revision`, similar to `git ls-tree -l`
+    ///
+    /// Directories are listed first, entries of the same kind are ordered by name.
+    ///
+    /// * `revision` - Revision of the tree
+    /// * `path` - Path of the directory relative to the root of the repository, the root if empty
+    pub fn list_tree(&self, revision: &str, path: &str) -> Result<Vec<TreeEntry>> {
+        tree::list_tree(&self.repo, revision, path)
+    }
+
+    /// Read the file at `path` as of `revision`, similar to `git show <revision>:<path>`
+    ///
+    /// * `revision` - Revision of the file
+    /// * `path` - Path of the file relative to the root of the repository
+    /// * `max_size` - Maximum number of bytes of the content to return. Returning all if set to `None`
+    pub fn read_blob(&self, revision: &str, path: &str, max_size: Option<usize>) -> Result<Blob> {
+        tree::read_blob(&self.repo, revision, path, max_size)
+    }
+
+    /// Get a page of the commit graph from `head_rev` to `base_rev`, similar to `git log --graph`
+    ///
+    /// * `base_rev` - Base revision until which to iterate. Iterating to initial commit if set to `None`
+    /// * `head_rev` - Head revision from which to iterate. Iterating from current `HEAD` if set to `None`
+    /// * `offset` - Number of commits to skip
+    /// * `limit` - Maximum number of commits to return
+    pub fn commit_graph(
+        &self,
+        base_rev: Option<&str>,
+        head_rev: Option<&str>,
+        offset: usize,
+        limit: usize,
+    ) -> Result<CommitGraph> {
+        graph::commit_graph(&self.repo, base_rev, head_rev, offset, limit)
+    }
+
     /// Get a commit for a revision
     ///
     /// * `rev` - Revision to get the commit for. This can be the short hash, full hash, a tag, or any other
@@ -82,30 +265,17 @@ impl Repository {
     ///
     /// * `rev` - Revision to checkout. This can be the short hash, full hash, a tag, or any other
     ///   reference such as `HEAD`, a branch name or a tag name
-    pub fn checkout_revision(&self, rev: &str) -> Result<CommitWithReferences> {
-        let (object, reference) = self.repo.revparse_ext(rev).map_err(|e| {
-            Error::from_ctx_and_error(format!("Failed to parse revision '{rev}'"), e)
-        })?;
-
-        self.repo.checkout_tree(&object, None).map_err(|e| {
-            Error::from_ctx_and_error(format!("Failed to checkout revision '{rev}'"), e)
-        })?;
-
-        match reference {
-            // gref is an actual reference like branches or tags
-            Some(gref) => self.repo.set_head(gref.name().unwrap_or_default()),
-            // this is a commit, not a reference
-            None => self.repo.set_head_detached(object.id()),
-        }
-        .map_err(|e| {
-            Error::from_ctx_and_error(format!("Failed to set head to revision '{rev}'"), e)
-        })?;
+    /// * `mode` - Handling of local changes. Fails with [`Error::Conflict`] listing the
+    ///   conflicting paths if the local changes prevent the checkout
+    pub fn checkout_revision(
+        &mut self,
+        rev: &str,
+        mode: CheckoutMode,
+    ) -> Result<CommitWithReferences> {
+        checkout::checkout_revision(&mut self.repo, rev, mode)?;
 
         let ref_map = ReferencesMap::try_from(&self.repo)?;
 
-        // self.repo
-        //     .set_head(obj.Ok
-        //     .map_err(|e| Error::from_ctx_and_error(format!("Failed to set head to {rev}"), e))?;
         let commit = Commit::try_from_revision(&self.repo, rev)?;
         CommitWithReferences::from_commit_and_references(
             &commit,
@@ -117,6 +287,7 @@ impl Repository {
         &self,
         base_rev: Option<&str>,
         head_rev: Option<&str>,
+        options: &DiffOptions,
     ) -> Result<git2::Diff<'_>> {
         let head = head_rev.unwrap_or("HEAD");
         let tree = utils::get_tree_for_revision(&self.repo, head)?;
@@ -131,23 +302,168 @@ impl Repository {
 
         let mut diff = self
             .repo
-            .diff_tree_to_tree(base_tree.as_ref(), Some(&tree), None)
+            .diff_tree_to_tree(
+                base_tree.as_ref(),
+                Some(&tree),
+                Some(&mut options.to_git2_diff_options()),
+            )
             .map_err(|e| {
                 Error::from_ctx_and_error(format!("Failed to diff tree {base_rev:?} to {head}"), e)
             })?;
 
-        // Enable rename detection with DiffFindOptions
-        let mut find_opts = git2::DiffFindOptions::new();
-        find_opts.renames(true);
         // Transform a diff marking file renames, copies, etc.
-        diff.find_similar(Some(&mut find_opts))
-            .map_err(|e| Error::from_ctx_and_error("Failed to find similar files in diff", e))?;
+        if let Some(mut find_opts) = options.to_git2_find_options() {
+            diff.find_similar(Some(&mut find_opts)).map_err(|e| {
+                Error::from_ctx_and_error("Failed to find similar files in diff", e)
+            })?;
+        }
         Ok(diff)
     }
 
-    pub fn diff(&self, base_rev: Option<&str>, head_rev: Option<&str>) -> Result<Diff> {
-        let diff = self.git2_diff_for_revisions(base_rev, head_rev)?;
-        Diff::try_from_repo_and_diff(self.repo(), &diff)
+    /// Get the diff from `base_rev` to `head_rev`, similar to `git diff base_rev..head_rev`
+    ///
+    /// * `base_rev` - Base revision of the diff. Diffing against an empty tree if set to `None`
+    /// * `head_rev` - Head revision of the diff. Using the current `HEAD` if set to `None`
+    /// * `options` - Options for creating the diff, returned with the diff
+    pub fn diff(
+        &self,
+        base_rev: Option<&str>,
+        head_rev: Option<&str>,
+        options: DiffOptions,
+    ) -> Result<Diff> {
+        let diff = self.git2_diff_for_revisions(base_rev, head_rev, &options)?;
+        Diff::try_from_repo_and_diff(self.repo(), &diff, options)
+    }
+
+    /// Get the diff of the stages `target` of the working tree, similar to `git diff`,
+    /// `git diff --cached` and `git diff HEAD`
+    ///
+    /// * `target` - Stages to compare
+    /// * `options` - Options for creating the diff, returned with the diff
+    pub fn diff_working_tree(&self, target: WorkingTreeDiff, options: DiffOptions) -> Result<Diff> {
+        let mut diff = status::git2_diff_for_working_tree(
+            &self.repo,
+            target,
+            &mut options.to_git2_diff_options(),
+        )?;
+        if let Some(mut find_opts) = options.to_git2_find_options() {
+            diff.find_similar(Some(&mut find_opts)).map_err(|e| {
+                Error::from_ctx_and_error("Failed to find similar files in diff", e)
+            })?;
+        }
+        Diff::try_from_repo_and_diff(self.repo(), &diff, options)
+    }
+
+    /// Get the status of the index and the working tree, similar to `git status`
+    ///
+    /// Ignored files are not included.
+    pub fn status(&self) -> Result<WorkingTreeStatus> {
+        status::status(&self.repo)
+    }
+
+    /// Create a snapshot of the working tree and the index, similar to `git stash create`
+    ///
+    /// The snapshot is stored as a commit referenced by `refs/debug-flow/snapshots/<name>`, an
+    /// existing snapshot with the same name is replaced. Untracked files are included unless they
+    /// are ignored or below one of the `excluded` paths relative to the root of the repository.
+    /// The working tree, the index and `HEAD` are not changed.
+    pub fn create_snapshot(
+        &self,
+        name: &str,
+        message: &str,
+        excluded: &[String],
+    ) -> Result<Snapshot> {
+        snapshot::create_snapshot(&self.repo, name, message, excluded)
+    }
+
+    /// List the snapshots whose name starts with `prefix`, ordered by name
+    pub fn list_snapshots(&self, prefix: &str) -> Result<Vec<Snapshot>> {
+        snapshot::list_snapshots(&self.repo, prefix)
+    }
+
+    /// Delete the snapshot `name`
+    ///
+    /// The commits of the snapshot are removed by the next garbage collection of git, e.g.
+    /// `git gc --prune=now`.
+    pub fn delete_snapshot(&self, name: &str) -> Result<()> {
+        snapshot::delete_snapshot(&self.repo, name)
+    }
+
+    /// Restore the working tree and the index recorded in the snapshot `name`
+    ///
+    /// `HEAD` is detached at the commit checked out when the snapshot was created, local changes
+    /// are handled according to `mode`. Stashed local changes are kept in the stash. Fails with
+    /// [`Error::Conflict`] if local changes conflict with the snapshot.
+    pub fn restore_snapshot(&mut self, name: &str, mode: CheckoutMode) -> Result<Snapshot> {
+        snapshot::restore_snapshot(&mut self.repo, name, mode)
+    }
+
+    /// List the stashes, the newest first, similar to `git stash list`
+    pub fn list_stashes(&self) -> Result<Vec<Stash>> {
+        stash::list_stashes(&self.repo)
+    }
+
+    /// Find a stash by its position in the stash list, e.g. `1` or `stash@{1}`, or by the full or
+    /// abbreviated ID of its commit
+    pub fn find_stash(&self, stash: &str) -> Result<Stash> {
+        stash::find_stash(&self.repo, stash)
+    }
+
+    /// Stash the local changes and reset them, similar to `git stash push`
+    ///
+    /// * `message` - Message of the stash, git generates one from `HEAD` if not set
+    /// * `include_untracked` - Whether untracked files are stashed and removed as well
+    pub fn save_stash(&mut self, message: Option<&str>, include_untracked: bool) -> Result<Stash> {
+        stash::save_stash(&mut self.repo, message, include_untracked)
+    }
+
+    /// Apply the stash at `index` including its staged changes and keep it, similar to
+    /// `git stash apply --index`
+    ///
+    /// Conflicting changes are merged with conflict markers and reported as conflict error.
+    pub fn apply_stash(&mut self, index: usize) -> Result<()> {
+        stash::apply_stash(
+            &mut self.repo,
+            index,
+            false,
+            format!("Stash '{index}' conflicts with local changes"),
+        )
+    }
+
+    /// Apply the stash at `index` including its staged changes and drop it, similar to
+    /// `git stash pop --index`
+    ///
+    /// Conflicting changes are merged with conflict markers and reported as conflict error, the
+    /// stash is kept in this case.
+    pub fn pop_stash(&mut self, index: usize) -> Result<()> {
+        stash::apply_stash(
+            &mut self.repo,
+            index,
+            true,
+            format!("Stash '{index}' conflicts with local changes and was kept"),
+        )
+    }
+
+    /// Drop the stash at `index`, similar to `git stash drop`
+    pub fn drop_stash(&mut self, index: usize) -> Result<()> {
+        stash::drop_stash(&mut self.repo, index)
+    }
+
+    /// Get the diff of the stash at `index` to the commit it is based on, including untracked
+    /// files, similar to `git stash show --patch --include-untracked`
+    ///
+    /// * `index` - Position of the stash in the stash list
+    /// * `options` - Options for creating the diff, returned with the diff
+    pub fn diff_stash(&self, index: usize, options: DiffOptions) -> Result<Diff> {
+        let stash = self.find_stash(&index.to_string())?;
+        let mut diff =
+            stash::git2_diff_for_stash(&self.repo, &stash, &mut options.to_git2_diff_options())?;
+        if let Some(mut find_opts) = options.to_git2_find_options() {
+            diff.find_similar(Some(&mut find_opts)).map_err(|e| {
+                Error::from_ctx_and_error("Failed to find similar files in diff", e)
+            })?;
+        }
+        Diff::try_from_repo_and_diff(self.repo(), &diff, options)
     }
 
     /// Returns an iterator over tags in the repository which names contain `filter`
@@ -194,6 +510,100 @@ impl Repository {
         Ok(Branch::from_name_and_commit(name, &commit))
     }
 
+    /// Find the next step of a bisection between `good` and `bad` revisions
+    ///
+    /// * `bad` - Revision known to be bad
+    /// * `good` - Revisions known to be good
+    /// * `skipped` - Revisions which cannot be tested and are never proposed
+    pub fn bisect(&self, bad: &str, good: &[&str], skipped: &[&str]) -> Result<BisectStep> {
+        bisect::next_step(&self.repo, bad, good, skipped)
+    }
+
+    /// Check whether `revisions` still resolve to commits reachable from a reference
+    ///
+    /// Revisions become unreachable when the commits are amended or rebased or when their branch is
+    /// deleted, they disappear on the next garbage collection. Replacements are suggested for those
+    /// revisions, found in the reflog, by patch ID or by summary and author.
+    ///
+    /// * `revisions` - Revisions with the summary of the commit they were linked to
+    pub fn check_revisions(&self, revisions: &[(&str, &str)]) -> Result<Vec<RevisionCheck>> {
+        revision_check::check_revisions(&self.repo, revisions)
+    }
+
+    /// List the linked worktrees ordered by name, similar to `git worktree list`
+    ///
+    /// The main worktree of the repository is not included.
+    pub fn list_worktrees(&self) -> Result<Vec<Worktree>> {
+        worktree::list_worktrees(&self.repo)
+    }
+
+    /// Get the linked worktree `name`
+    pub fn find_worktree(&self, name: &str) -> Result<Worktree> {
+        worktree::find_worktree(&self.repo, name)
+    }
+
+    /// Add a worktree named `name` at `path` and check out `revision` in it, similar to
+    /// `git worktree add`
+    ///
+    /// * `name` - Name of the worktree
+    /// * `path` - Path of the working directory to create, must not exist yet
+    /// * `revision` - Revision to check out in the worktree
+    /// * `branch` - Name of a new branch at `revision` to check out, `HEAD` is detached if not set
+    pub fn add_worktree(
+        &self,
+        name: &str,
+        path: &Path,
+        revision: &str,
+        branch: Option<&str>,
+    ) -> Result<Worktree> {
+        worktree::add_worktree(&self.repo, name, path, revision, branch)
+    }
+
+    /// Add a worktree named `name` at `path` with a detached `HEAD` at `revision`
+    ///
+    /// libgit2 always checks out a branch in new worktrees, a temporary branch named `name` is
+    /// created for this and deleted again once the `HEAD` of the worktree is detached.
+    ///
+    /// * `name` - Name of the worktree
+    /// * `path` - Path of the working directory to create, must not exist yet
+    /// * `revision` - Revision to check out in the worktree
+    pub fn add_detached_worktree(
+        &self,
+        name: &str,
+        path: &Path,
+        revision: &str,
+    ) -> Result<Worktree> {
+        self.add_worktree(name, path, revision, None)
+    }
+
+    /// Lock the worktree `name` to protect it from being pruned or removed, similar to
+    /// `git worktree lock`
+    pub fn lock_worktree(&self, name: &str, reason: Option<&str>) -> Result<Worktree> {
+        worktree::lock_worktree(&self.repo, name, reason)
+    }
+
+    /// Unlock the worktree `name`, similar to `git worktree unlock`
+    pub fn unlock_worktree(&self, name: &str) -> Result<Worktree> {
+        worktree::unlock_worktree(&self.repo, name)
+    }
+
+    /// Remove the administrative data of worktrees whose working directory is missing, similar to
+    /// `git worktree prune`
+    ///
+    /// Locked worktrees are kept. Returns the pruned worktrees.
+    pub fn prune_worktrees(&self) -> Result<Vec<Worktree>> {
+        worktree::prune_worktrees(&self.repo)
+    }
+
+    /// Remove the worktree `name` including its working directory, similar to
+    /// `git worktree remove`
+    ///
+    /// Locked worktrees and worktrees with local changes, including untracked files, are refused
+    /// unless `force` is set.
+    pub fn remove_worktree(&self, name: &str, force: bool) -> Result<()> {
+        worktree::remove_worktree(&self.repo, name, force)
+    }
+
     /// Return an iterator over local branches containing `filter`
     ///
     pub fn iter_branches(&self) -> Result<impl Iterator<Item = Branch>> {
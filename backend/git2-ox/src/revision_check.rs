@@ -0,0 +1,397 @@
+use crate::{Commit, Result, error::Error, snapshot, stash};
+use std::collections::{HashMap, HashSet};
+
+/// Maximum number of reachable commits searched for replacements of stale revisions
+const MAX_SEARCHED_COMMITS: usize = 10_000;
+
+/// State of a revision
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "lowercase")
+)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RevisionState {
+    /// The revision resolves to a commit reachable from a branch, tag, remote branch or `HEAD`
+    Reachable,
+    /// The revision resolves to a commit which is not reachable from any reference, it is deleted
+    /// by the next garbage collection
+    Unreachable,
+    /// The revision does not resolve to a commit
+    Missing,
+}
+
+/// Reason a commit is suggested as replacement for a stale revision, ordered by reliability
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "camelCase")
+)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ReplacementReason {
+    /// The reflog records amending or rebasing the stale commit to the commit
+    Reflog,
+    /// The commit introduces the same changes as the stale commit
+    PatchId,
+    /// The commit has the same summary and author as the stale commit
+    SummaryAndAuthor,
+    /// The commit has the same summary, only used if the stale commit does not exist anymore
+    Summary,
+}
+
+/// Commit suggested as replacement for a stale revision
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "camelCase")
+)]
+#[derive(Clone, Debug)]
+pub struct ReplacementCandidate {
+    /// Suggested commit
+    commit: Commit,
+    /// Reasons the commit is suggested, the most reliable reason first
+    reasons: Vec<ReplacementReason>,
+}
+
+impl ReplacementCandidate {
+    /// Get the suggested commit
+    pub fn commit(&self) -> &Commit {
+        &self.commit
+    }
+
+    /// Get the reasons the commit is suggested, the most reliable reason first
+    pub fn reasons(&self) -> &[ReplacementReason] {
+        &self.reasons
+    }
+}
+
+/// Result of checking a revision
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "camelCase")
+)]
+#[derive(Clone, Debug)]
+pub struct RevisionCheck {
+    /// Checked revision
+    revision: String,
+    /// State of the revision
+    state: RevisionState,
+    /// Replacements for the revision if it is not reachable, the best candidate first
+    candidates: Vec<ReplacementCandidate>,
+}
+
+impl RevisionCheck {
+    /// Get the checked revision
+    pub fn revision(&self) -> &str {
+        &self.revision
+    }
+
+    /// Get the state of the revision
+    pub fn state(&self) -> RevisionState {
+        self.state
+    }
+
+    /// Get the replacements for the revision, the best candidate first
+    pub fn candidates(&self) -> &[ReplacementCandidate] {
+        &self.candidates
+    }
+}
+
+/// Revision which is not reachable anymore
+struct StaleRevision<'repo> {
+    /// Index of the revision in the checked revisions
+    index: usize,
+    /// ID of the commit, if the revision is a commit ID or still resolves to a commit
+    oid: Option<git2::Oid>,
+    /// Commit the revision resolves to
+    commit: Option<git2::Commit<'repo>>,
+    /// Summary of the commit
+    summary: String,
+    /// Patch ID of the commit
+    patch_id: Option<git2::Oid>,
+    /// Suggested replacements and the reasons they are suggested
+    candidates: HashMap<git2::Oid, Vec<ReplacementReason>>,
+}
+
+impl StaleRevision<'_> {
+    fn add_candidate(&mut self, oid: git2::Oid, reason: ReplacementReason) {
+        let reasons = self.candidates.entry(oid).or_default();
+        if !reasons.contains(&reason) {
+            reasons.push(reason);
+        }
+    }
+}
+
+/// Check whether `revisions` resolve to commits reachable from any branch, tag, remote branch or
+/// `HEAD` and suggest replacements for the ones which do not
+///
+/// Each revision is passed with the summary of the commit it was linked to, which is used to find
+/// replacements for commits which do not exist anymore.
+pub(crate) fn check_revisions(
+    repo: &git2::Repository,
+    revisions: &[(&str, &str)],
+) -> Result<Vec<RevisionCheck>> {
+    let tips = reference_tips(repo)?;
+    let mut checks = Vec::with_capacity(revisions.len());
+    let mut stale = Vec::new();
+    for (revision, summary) in revisions {
+        let commit = repo
+            .revparse_single(revision)
+            .and_then(|o| o.peel_to_commit())
+            .ok();
+        let state = match &commit {
+            None => RevisionState::Missing,
+            Some(c) if is_reachable(repo, &tips, c.id()) => RevisionState::Reachable,
+            Some(_) => RevisionState::Unreachable,
+        };
+        if state != RevisionState::Reachable {
+            stale.push(StaleRevision {
+                index: checks.len(),
+                oid: commit
+                    .as_ref()
+                    .map(|c| c.id())
+                    .or_else(|| git2::Oid::from_str(revision).ok()),
+                summary: commit
+                    .as_ref()
+                    .and_then(|c| c.summary())
+                    .unwrap_or(summary)
+                    .to_string(),
+                patch_id: commit
+                    .as_ref()
+                    .and_then(|c| patch_id(repo, c).ok().flatten()),
+                commit,
+                candidates: HashMap::new(),
+            });
+        }
+        checks.push(RevisionCheck {
+            revision: revision.to_string(),
+            state,
+            candidates: Vec::new(),
+        });
+    }
+    if stale.is_empty() {
+        return Ok(checks);
+    }
+
+    add_reflog_candidates(repo, &tips, &mut stale);
+    add_matching_candidates(repo, &tips, &mut stale)?;
+
+    for s in stale {
+        let mut candidates: Vec<_> = s
+            .candidates
+            .into_iter()
+            .filter_map(|(oid, mut reasons)| {
+                reasons.sort();
+                Some(ReplacementCandidate {
+                    commit: Commit::try_from_oid(repo, oid).ok()?,
+                    reasons,
+                })
+            })
+            .collect();
+        candidates.sort_by(|a, b| {
+            a.reasons[0]
+                .cmp(&b.reasons[0])
+                .then(b.reasons.len().cmp(&a.reasons.len()))
+                .then(b.commit.time().cmp(&a.commit.time()))
+        });
+        checks[s.index].candidates = candidates;
+    }
+    Ok(checks)
+}
+
+/// Get the commits the branches, tags, remote branches and `HEAD` point to
+///
+/// Snapshots and stashes are tips as well, nodes are linked to their commits which are not
+/// reachable from any branch.
+fn reference_tips(repo: &git2::Repository) -> Result<HashSet<git2::Oid>> {
+    let references = repo
+        .references()
+        .map_err(|e| Error::from_ctx_and_error("Failed to get references", e))?;
+    let mut tips: HashSet<_> = references
+        .flatten()
+        .filter(|r| {
+            r.is_branch()
+                || r.is_tag()
+                || r.is_remote()
+                || r.name()
+                    .is_some_and(|n| n.starts_with(snapshot::SNAPSHOTS_REF_PREFIX))
+        })
+        .filter_map(|r| Some(r.peel_to_commit().ok()?.id()))
+        .collect();
+    if let Ok(head) = repo.head().and_then(|h| h.peel_to_commit()) {
+        tips.insert(head.id());
+    }
+    // Only the newest stash is referenced, older stashes are kept in the reflog
+    if let Ok(reflog) = repo.reflog(stash::STASH_REF) {
+        tips.extend(reflog.iter().map(|entry| entry.id_new()));
+    }
+    Ok(tips)
+}
+
+fn is_reachable(repo: &git2::Repository, tips: &HashSet<git2::Oid>, oid: git2::Oid) -> bool {
+    tips.contains(&oid)
+        || tips
+            .iter()
+            .any(|tip| repo.graph_descendant_of(*tip, oid).unwrap_or(false))
+}
+
+/// Get the patch ID of the changes introduced by `commit`, `None` for merge commits and commits
+/// without changes
+fn patch_id(repo: &git2::Repository, commit: &git2::Commit) -> Result<Option<git2::Oid>> {
+    if commit.parent_count() > 1 {
+        return Ok(None);
+    }
+    let parent_tree = match commit.parent(0) {
+        Ok(parent) => Some(
+            parent
+                .tree()
+                .map_err(|e| Error::from_ctx_and_error("Failed to get tree of parent", e))?,
+        ),
+        Err(_) => None,
+    };
+    let tree = commit
+        .tree()
+        .map_err(|e| Error::from_ctx_and_error("Failed to get tree of commit", e))?;
+    let diff = repo
+        .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)
+        .map_err(|e| Error::from_ctx_and_error("Failed to diff commit", e))?;
+    if diff.deltas().len() == 0 {
+        return Ok(None);
+    }
+    diff.patchid(None)
+        .map(Some)
+        .map_err(|e| Error::from_ctx_and_error("Failed to get patch ID", e))
+}
+
+/// Add the commits the stale commits were amended or rebased to according to the reflogs
+///
+/// Rebases are only recorded for the tip of the rebased branch, other rebased commits are found
+/// by their patch ID.
+fn add_reflog_candidates(
+    repo: &git2::Repository,
+    tips: &HashSet<git2::Oid>,
+    stale: &mut [StaleRevision],
+) {
+    let mut names = vec!["HEAD".to_string()];
+    if let Ok(references) = repo.references_glob("refs/heads/*") {
+        names.extend(
+            references
+                .flatten()
+                .filter_map(|r| r.name().map(String::from)),
+        );
+    }
+    let mut rewrites: HashMap<git2::Oid, Vec<git2::Oid>> = HashMap::new();
+    for name in names {
+        let Ok(reflog) = repo.reflog(&name) else {
+            continue;
+        };
+        for entry in reflog.iter() {
+            let (id_old, id_new) = (entry.id_old(), entry.id_new());
+            if id_old.is_zero() || id_old == id_new {
+                continue;
+            }
+            let message = entry.message().unwrap_or_default();
+            // git records amends as "commit (amend)", libgit2 as "commit", in both cases the new
+            // commit is not a child of the old one
+            let is_amend = message.starts_with("commit")
+                && repo
+                    .find_commit(id_new)
+                    .is_ok_and(|c| !c.parent_ids().any(|p| p == id_old));
+            let is_rebase = message.starts_with("rebase") && message.contains("(finish)");
+            if is_amend || is_rebase {
+                rewrites.entry(id_old).or_default().push(id_new);
+            }
+        }
+    }
+
+    for s in stale {
+        let Some(oid) = s.oid else {
+            continue;
+        };
+        // Follow chains of rewrites, e.g. for commits which were amended multiple times
+        let mut queue = vec![oid];
+        let mut seen = HashSet::new();
+        while let Some(oid) = queue.pop() {
+            if !seen.insert(oid) {
+                continue;
+            }
+            for new_oid in rewrites.get(&oid).into_iter().flatten() {
+                if is_reachable(repo, tips, *new_oid) {
+                    s.add_candidate(*new_oid, ReplacementReason::Reflog);
+                }
+                queue.push(*new_oid);
+            }
+        }
+    }
+}
+
+/// Add the reachable commits with the same patch ID or the same summary and author
+///
+/// Rewritten commits are always newer than the commits they replace, so only reachable commits
+/// newer than the oldest stale commit are searched.
+fn add_matching_candidates(
+    repo: &git2::Repository,
+    tips: &HashSet<git2::Oid>,
+    stale: &mut [StaleRevision],
+) -> Result<()> {
+    let mut revwalk = repo
+        .revwalk()
+        .map_err(|e| Error::from_ctx_and_error("Failed to create revwalk object", e))?;
+    revwalk
+        .set_sorting(git2::Sort::TIME)
+        .map_err(|e| Error::from_ctx_and_error("Failed to sort revwalk", e))?;
+    for tip in tips {
+        revwalk
+            .push(*tip)
+            .map_err(|e| Error::from_ctx_and_error("Failed to push reference", e))?;
+    }
+    // Commits which do not exist anymore have no time, all commits are searched for them
+    let oldest = stale
+        .iter()
+        .map(|s| s.commit.as_ref().map(|c| c.time().seconds()))
+        .collect::<Option<Vec<_>>>()
+        .and_then(|times| times.into_iter().min());
+
+    for oid in revwalk.take(MAX_SEARCHED_COMMITS) {
+        let oid = oid.map_err(|e| Error::from_ctx_and_error("Failed to walk commits", e))?;
+        let commit = repo
+            .find_commit(oid)
+            .map_err(|e| Error::from_ctx_and_error("Failed to find commit", e))?;
+        if oldest.is_some_and(|oldest| commit.time().seconds() < oldest) {
+            break;
+        }
+        // Computing the patch ID requires a diff, only do it if needed
+        let mut commit_patch_id = None;
+        for s in stale.iter_mut() {
+            let same_summary = !s.summary.is_empty() && commit.summary() == Some(&s.summary);
+            let same_author = s.commit.as_ref().map(|stale_commit| {
+                let (author, stale_author) = (commit.author(), stale_commit.author());
+                author.name_bytes() == stale_author.name_bytes()
+                    && author.email_bytes() == stale_author.email_bytes()
+            });
+            match same_author {
+                Some(same_author) => {
+                    if same_summary && same_author {
+                        s.add_candidate(oid, ReplacementReason::SummaryAndAuthor);
+                    }
+                    if let Some(stale_patch_id) = s.patch_id {
+                        if commit_patch_id.is_none() {
+                            commit_patch_id = Some(patch_id(repo, &commit)?);
+                        }
+                        if commit_patch_id == Some(Some(stale_patch_id)) {
+                            s.add_candidate(oid, ReplacementReason::PatchId);
+                        }
+                    }
+                }
+                None if same_summary => s.add_candidate(oid, ReplacementReason::Summary),
+                None => {}
+            }
+        }
+    }
+    Ok(())
+}
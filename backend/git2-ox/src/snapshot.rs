@@ -0,0 +1,262 @@
+use std::path::Path;
+
+use crate::checkout::{self, CheckoutMode};
+use crate::{Commit, Result, error::Error, utils};
+
+/// Prefix of the references of snapshots, hidden from branches and tags
+pub const SNAPSHOTS_REF_PREFIX: &str = "refs/debug-flow/snapshots/";
+
+/// Commit recording the working tree and the index, similar to a stash
+///
+/// The tree of the commit is the working tree including untracked files, its first parent is the
+/// commit checked out when the snapshot was created and its second parent a commit of the index.
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "camelCase")
+)]
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    /// Name of the snapshot, the reference without the snapshot prefix
+    name: String,
+    /// Full name of the reference of the snapshot
+    reference: String,
+    /// ID of the commit checked out when the snapshot was created
+    base_id: String,
+    /// Commit of the snapshot
+    commit: Commit,
+}
+
+impl Snapshot {
+    /// Get the name of the snapshot
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the full name of the reference of the snapshot
+    pub fn reference(&self) -> &str {
+        &self.reference
+    }
+
+    /// Get the ID of the commit checked out when the snapshot was created
+    pub fn base_id(&self) -> &str {
+        &self.base_id
+    }
+
+    /// Get the commit of the snapshot
+    pub fn commit(&self) -> &Commit {
+        &self.commit
+    }
+
+    fn try_from_commit(name: &str, commit: &git2::Commit) -> Result<Self> {
+        let base = commit.parent_id(0).map_err(|e| {
+            Error::from_ctx_and_error(format!("Snapshot '{name}' has no base commit"), e)
+        })?;
+        Ok(Self {
+            name: name.to_string(),
+            reference: snapshot_reference(name),
+            base_id: base.to_string(),
+            commit: Commit::from(commit.clone()),
+        })
+    }
+}
+
+/// Get the full name of the reference of the snapshot `name`
+fn snapshot_reference(name: &str) -> String {
+    format!("{SNAPSHOTS_REF_PREFIX}{name}")
+}
+
+/// Get the commit of the snapshot `name`
+fn find_snapshot_commit<'repo>(
+    repo: &'repo git2::Repository,
+    name: &str,
+) -> Result<git2::Commit<'repo>> {
+    repo.find_reference(&snapshot_reference(name))
+        .and_then(|reference| reference.peel_to_commit())
+        .map_err(|e| Error::from_ctx_and_error(format!("Snapshot '{name}'"), e))
+}
+
+/// Write the trees of the index and of the working tree, untracked files below `excluded` paths
+/// are not included
+///
+/// The index of the repository is only changed in memory and read from disk again afterwards.
+fn write_trees(repo: &git2::Repository, excluded: &[String]) -> Result<(git2::Oid, git2::Oid)> {
+    let mut index = repo
+        .index()
+        .map_err(|e| Error::from_ctx_and_error("Failed to get index", e))?;
+    let result = (|| {
+        let index_tree = index.write_tree()?;
+        let mut skip_excluded = |path: &Path, _: &[u8]| {
+            let excluded = excluded.iter().any(|e| path.starts_with(e));
+            if excluded { 1 } else { 0 }
+        };
+        index.add_all(
+            ["*"],
+            git2::IndexAddOption::DEFAULT,
+            Some(&mut skip_excluded),
+        )?;
+        index.update_all(["*"], None)?;
+        let worktree_tree = index.write_tree()?;
+        Ok((index_tree, worktree_tree))
+    })();
+    // Discard the changes made in memory
+    index
+        .read(true)
+        .map_err(|e| Error::from_ctx_and_error("Failed to read index", e))?;
+    result.map_err(|e| Error::from_ctx_and_error("Failed to write trees of the working tree", e))
+}
+
+/// Create a snapshot of the working tree and the index named `name`, like `git stash create`
+///
+/// An existing snapshot with the same name is replaced. The working tree, the index and `HEAD` are
+/// not changed.
+pub(crate) fn create_snapshot(
+    repo: &git2::Repository,
+    name: &str,
+    message: &str,
+    excluded: &[String],
+) -> Result<Snapshot> {
+    let reference = snapshot_reference(name);
+    if !git2::Reference::is_valid_name(&reference) {
+        return Err(Error::from_ctx_and_error(
+            format!("snapshot name '{name}'"),
+            git2::Error::new(
+                git2::ErrorCode::Invalid,
+                git2::ErrorClass::Reference,
+                "the name is not a valid reference name",
+            ),
+        ));
+    }
+    let head = utils::get_commit_for_revision(repo, "HEAD")?;
+    let (index_tree, worktree_tree) = write_trees(repo, excluded)?;
+    let find_tree = |oid| {
+        repo.find_tree(oid)
+            .map_err(|e| Error::from_ctx_and_error("Failed to find tree", e))
+    };
+
+    let signature = utils::get_signature(repo)?;
+    let short_id = head.as_object().short_id().ok();
+    let short_id = short_id
+        .as_ref()
+        .and_then(|s| s.as_str())
+        .unwrap_or_default();
+    let index_commit = repo
+        .commit(
+            None,
+            &signature,
+            &signature,
+            &format!("index on {short_id}"),
+            &find_tree(index_tree)?,
+            &[&head],
+        )
+        .and_then(|oid| repo.find_commit(oid))
+        .map_err(|e| Error::from_ctx_and_error("Failed to commit index", e))?;
+    let commit = repo
+        .commit(
+            None,
+            &signature,
+            &signature,
+            message,
+            &find_tree(worktree_tree)?,
+            &[&head, &index_commit],
+        )
+        .and_then(|oid| repo.find_commit(oid))
+        .map_err(|e| Error::from_ctx_and_error("Failed to commit working tree", e))?;
+
+    repo.reference(&reference, commit.id(), true, message)
+        .map_err(|e| {
+            Error::from_ctx_and_error(format!("Failed to create reference '{reference}'"), e)
+        })?;
+    Snapshot::try_from_commit(name, &commit)
+}
+
+/// List the snapshots whose name starts with `prefix`, ordered by name
+pub(crate) fn list_snapshots(repo: &git2::Repository, prefix: &str) -> Result<Vec<Snapshot>> {
+    let references = repo
+        .references()
+        .map_err(|e| Error::from_ctx_and_error("Failed to get references", e))?;
+    let mut snapshots = Vec::new();
+    for reference in references.flatten() {
+        let Some(name) = reference
+            .name()
+            .and_then(|n| n.strip_prefix(SNAPSHOTS_REF_PREFIX))
+            .filter(|n| n.starts_with(prefix))
+        else {
+            continue;
+        };
+        let commit = reference
+            .peel_to_commit()
+            .map_err(|e| Error::from_ctx_and_error(format!("Snapshot '{name}'"), e))?;
+        snapshots.push(Snapshot::try_from_commit(name, &commit)?);
+    }
+    snapshots.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(snapshots)
+}
+
+/// Delete the snapshot `name`, its commits are removed by the next garbage collection of git
+pub(crate) fn delete_snapshot(repo: &git2::Repository, name: &str) -> Result<()> {
+    repo.find_reference(&snapshot_reference(name))
+        .and_then(|mut reference| reference.delete())
+        .map_err(|e| Error::from_ctx_and_error(format!("Snapshot '{name}'"), e))
+}
+
+/// Restore the working tree and the index of the snapshot `name`, `HEAD` is detached at the
+/// commit checked out when the snapshot was created
+///
+/// Local changes are handled according to `mode`, stashed local changes are kept in the stash as
+/// the snapshot replaces them.
+pub(crate) fn restore_snapshot(
+    repo: &mut git2::Repository,
+    name: &str,
+    mode: CheckoutMode,
+) -> Result<Snapshot> {
+    let (snapshot, tree, index_commit) = {
+        let commit = find_snapshot_commit(repo, name)?;
+        let index_commit = commit.parent_id(1).map_err(|e| {
+            Error::from_ctx_and_error(format!("Snapshot '{name}' has no index commit"), e)
+        })?;
+        let snapshot = Snapshot::try_from_commit(name, &commit)?;
+        (snapshot, commit.tree_id(), index_commit)
+    };
+    let base_mode = match mode {
+        CheckoutMode::Stash => {
+            checkout::stash_local_changes(
+                repo,
+                &format!("debug-flow: local changes before restoring snapshot '{name}'"),
+            )?;
+            CheckoutMode::Refuse
+        }
+        mode => mode,
+    };
+    checkout::checkout_revision(repo, &snapshot.base_id, base_mode)?;
+
+    let mut conflicts = Vec::new();
+    let result = {
+        let tree = repo
+            .find_tree(tree)
+            .map_err(|e| Error::from_ctx_and_error(format!("Snapshot '{name}'"), e))?;
+        let mut builder = checkout::checkout_builder(mode == CheckoutMode::Force, &mut conflicts);
+        builder.update_index(false);
+        repo.checkout_tree(tree.as_object(), Some(&mut builder))
+    };
+    if let Err(e) = result {
+        return Err(checkout::checkout_error(
+            format!("Snapshot '{name}' can not be restored over local changes"),
+            e,
+            conflicts,
+        ));
+    }
+
+    let tree = repo
+        .find_commit(index_commit)
+        .and_then(|commit| commit.tree())
+        .map_err(|e| Error::from_ctx_and_error(format!("Index of snapshot '{name}'"), e))?;
+    repo.index()
+        .and_then(|mut index| {
+            index.read_tree(&tree)?;
+            index.write()
+        })
+        .map_err(|e| Error::from_ctx_and_error("Failed to restore index", e))?;
+    Ok(snapshot)
+}
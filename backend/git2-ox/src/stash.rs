@@ -0,0 +1,225 @@
+use crate::checkout;
+use crate::{Commit, CommitProperties, Result, error::Error, utils};
+
+/// Reference of the newest stash, older stashes are in its reflog
+pub(crate) const STASH_REF: &str = "refs/stash";
+
+/// Entry of the stash list, similar to a line of `git stash list`
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "camelCase")
+)]
+#[derive(Clone, Debug)]
+pub struct Stash {
+    /// Position in the stash list, `0` is the newest stash. Positions change when stashes are
+    /// saved or dropped, the ID of the commit does not.
+    index: usize,
+    /// Message of the stash, e.g. `On main: message`
+    message: String,
+    /// ID of the commit checked out when the changes were stashed
+    base_id: String,
+    /// Whether untracked files were stashed as well
+    has_untracked: bool,
+    /// Commit of the stash
+    commit: Commit,
+}
+
+impl Stash {
+    /// Get the position in the stash list, `0` is the newest stash
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Get the message of the stash
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Get the ID of the commit checked out when the changes were stashed
+    pub fn base_id(&self) -> &str {
+        &self.base_id
+    }
+
+    /// Get whether untracked files were stashed as well
+    pub fn has_untracked(&self) -> bool {
+        self.has_untracked
+    }
+
+    /// Get the commit of the stash
+    pub fn commit(&self) -> &Commit {
+        &self.commit
+    }
+}
+
+/// List the stashes, the newest first
+pub(crate) fn list_stashes(repo: &git2::Repository) -> Result<Vec<Stash>> {
+    let reflog = repo
+        .reflog(STASH_REF)
+        .map_err(|e| Error::from_ctx_and_error("Failed to read stash list", e))?;
+    let mut stashes = Vec::with_capacity(reflog.len());
+    for (index, entry) in reflog.iter().enumerate() {
+        let commit = utils::get_commit_for_oid(repo, entry.id_new())?;
+        let base_id = commit
+            .parent_id(0)
+            .map_err(|e| Error::from_ctx_and_error(format!("Stash '{index}'"), e))?;
+        stashes.push(Stash {
+            index,
+            message: String::from_utf8_lossy(entry.message_bytes().unwrap_or_default()).to_string(),
+            base_id: base_id.to_string(),
+            has_untracked: commit.parent_count() > 2,
+            commit: Commit::from(commit),
+        });
+    }
+    Ok(stashes)
+}
+
+/// Find a stash by its position, e.g. `1` or `stash@{1}`, or by the full or abbreviated ID of its
+/// commit
+pub(crate) fn find_stash(repo: &git2::Repository, stash: &str) -> Result<Stash> {
+    let index = stash
+        .strip_prefix("stash@{")
+        .and_then(|s| s.strip_suffix('}'))
+        .unwrap_or(stash)
+        .parse::<usize>()
+        .ok();
+    let stashes = list_stashes(repo)?;
+    // Abbreviated commit IDs may consist of digits only, they are used if there is no such position
+    let by_index = index.and_then(|index| stashes.iter().find(|s| s.index == index));
+    by_index
+        .or_else(|| {
+            stashes
+                .iter()
+                .find(|s| stash.len() >= 4 && s.commit.id().starts_with(stash))
+        })
+        .cloned()
+        .ok_or_else(|| {
+            Error::from_ctx_and_error(
+                format!("Stash '{stash}'"),
+                git2::Error::new(
+                    git2::ErrorCode::NotFound,
+                    git2::ErrorClass::Stash,
+                    "no such stash",
+                ),
+            )
+        })
+}
+
+/// Stash the local changes with `message`, including untracked files if `include_untracked` is
+/// set. Returns the new stash.
+pub(crate) fn save_stash(
+    repo: &mut git2::Repository,
+    message: Option<&str>,
+    include_untracked: bool,
+) -> Result<Stash> {
+    let signature = utils::get_signature(repo)?;
+    let flags = match include_untracked {
+        true => git2::StashFlags::INCLUDE_UNTRACKED,
+        false => git2::StashFlags::DEFAULT,
+    };
+    repo.stash_save2(&signature, message, Some(flags))
+        .map_err(|e| match e.code() {
+            git2::ErrorCode::NotFound => Error::from_ctx_and_error(
+                "stash",
+                git2::Error::new(
+                    git2::ErrorCode::Invalid,
+                    git2::ErrorClass::Stash,
+                    "there are no local changes to stash",
+                ),
+            ),
+            _ => Error::from_ctx_and_error("Failed to stash local changes", e),
+        })?;
+    find_stash(repo, "0")
+}
+
+/// Apply the stash at `index` including its staged changes, like `git stash apply --index`, and
+/// drop it if `drop` is set
+///
+/// Like `git stash pop`, conflicting changes are merged with conflict markers and the stash is kept.
+/// The stash is not applied at all if it would overwrite local changes.
+pub(crate) fn apply_stash(
+    repo: &mut git2::Repository,
+    index: usize,
+    drop: bool,
+    ctx: String,
+) -> Result<()> {
+    let mut apply = |reinstantiate_index| {
+        let mut conflicts = Vec::new();
+        let result = {
+            let mut options = git2::StashApplyOptions::new();
+            if reinstantiate_index {
+                options.reinstantiate_index();
+            }
+            options.checkout_options(checkout::checkout_builder(false, &mut conflicts));
+            repo.stash_apply(index, Some(&mut options))
+        };
+        result.map_err(|e| (e, conflicts))
+    };
+    // Staged changes conflicting with the index can not be restored, they are merged into the
+    // working tree instead like `git stash pop` without `--index` does
+    let result = match apply(true) {
+        Err((e, conflicts)) if e.code() == git2::ErrorCode::Conflict && conflicts.is_empty() => {
+            apply(false)
+        }
+        result => result,
+    };
+    if let Err((e, conflicts)) = result {
+        return Err(checkout::checkout_error(ctx, e, conflicts));
+    }
+
+    let index_file = repo
+        .index()
+        .map_err(|e| Error::from_ctx_and_error("Failed to get index", e))?;
+    if index_file.has_conflicts() {
+        let paths = index_file
+            .conflicts()
+            .map_err(|e| Error::from_ctx_and_error("Failed to get conflicts", e))?
+            .filter_map(|conflict| conflict.ok())
+            .filter_map(|conflict| conflict.our.or(conflict.their).or(conflict.ancestor))
+            .map(|entry| String::from_utf8_lossy(&entry.path).to_string())
+            .collect();
+        return Err(Error::conflict(ctx, paths));
+    }
+    if drop {
+        drop_stash(repo, index)?;
+    }
+    Ok(())
+}
+
+/// Drop the stash at `index`
+pub(crate) fn drop_stash(repo: &mut git2::Repository, index: usize) -> Result<()> {
+    repo.stash_drop(index)
+        .map_err(|e| Error::from_ctx_and_error(format!("Failed to drop stash '{index}'"), e))
+}
+
+/// Get the diff of `stash` to the commit it is based on, like
+/// `git stash show --patch --include-untracked`
+pub(crate) fn git2_diff_for_stash<'repo>(
+    repo: &'repo git2::Repository,
+    stash: &Stash,
+    options: &mut git2::DiffOptions,
+) -> Result<git2::Diff<'repo>> {
+    let ctx = format!("Stash '{}'", stash.index);
+    let commit = utils::get_commit_for_revision(repo, stash.commit.id())?;
+    let tree = commit
+        .tree()
+        .map_err(|e| Error::from_ctx_and_error(&ctx, e))?;
+    let parent_tree = |n| {
+        commit
+            .parent(n)
+            .and_then(|parent| parent.tree())
+            .map_err(|e| Error::from_ctx_and_error(&ctx, e))
+    };
+
+    let mut diff = repo
+        .diff_tree_to_tree(Some(&parent_tree(0)?), Some(&tree), Some(options))
+        .map_err(|e| Error::from_ctx_and_error("Failed to diff stash", e))?;
+    // Untracked files are stored in a commit without parents, the third parent of the stash
+    if stash.has_untracked {
+        repo.diff_tree_to_tree(None, Some(&parent_tree(2)?), Some(options))
+            .and_then(|untracked| diff.merge(&untracked))
+            .map_err(|e| Error::from_ctx_and_error("Failed to diff untracked files of stash", e))?;
+    }
+    Ok(diff)
+}
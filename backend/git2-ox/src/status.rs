@@ -0,0 +1,299 @@
+use crate::{Result, error::Error};
+
+/// Change of a file compared to the previous stage
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "camelCase")
+)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileChange {
+    /// The file was added
+    Added,
+    /// The content of the file was modified
+    Modified,
+    /// The file was deleted
+    Deleted,
+    /// The file was renamed, the content may be modified as well
+    Renamed,
+    /// The type of the file changed, e.g. from a file to a symbolic link
+    TypeChange,
+}
+
+/// Status of a file in the index and the working tree, similar to a line of `git status --short`
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "camelCase")
+)]
+#[derive(Clone, Debug)]
+pub struct FileStatus {
+    /// Path of the file relative to the root of the repository
+    path: String,
+    /// Path of the file before it was renamed
+    old_path: Option<String>,
+    /// Change staged in the index compared to `HEAD`
+    staged: Option<FileChange>,
+    /// Change in the working tree compared to the index
+    unstaged: Option<FileChange>,
+    /// Whether the file is not tracked
+    is_untracked: bool,
+    /// Whether the file has merge conflicts
+    is_conflicted: bool,
+}
+
+impl FileStatus {
+    /// Get the path of the file relative to the root of the repository
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Get the path of the file before it was renamed
+    pub fn old_path(&self) -> Option<&str> {
+        self.old_path.as_deref()
+    }
+
+    /// Get the change staged in the index compared to `HEAD`
+    pub fn staged(&self) -> Option<FileChange> {
+        self.staged
+    }
+
+    /// Get the change in the working tree compared to the index
+    pub fn unstaged(&self) -> Option<FileChange> {
+        self.unstaged
+    }
+
+    /// Get whether the file is not tracked
+    pub fn is_untracked(&self) -> bool {
+        self.is_untracked
+    }
+
+    /// Get whether the file has merge conflicts
+    pub fn is_conflicted(&self) -> bool {
+        self.is_conflicted
+    }
+
+    fn from_status_entry(entry: &git2::StatusEntry) -> Self {
+        let status = entry.status();
+        let staged = if status.is_index_new() {
+            Some(FileChange::Added)
+        } else if status.is_index_renamed() {
+            Some(FileChange::Renamed)
+        } else if status.is_index_modified() {
+            Some(FileChange::Modified)
+        } else if status.is_index_deleted() {
+            Some(FileChange::Deleted)
+        } else if status.is_index_typechange() {
+            Some(FileChange::TypeChange)
+        } else {
+            None
+        };
+        let unstaged = if status.is_wt_renamed() {
+            Some(FileChange::Renamed)
+        } else if status.is_wt_modified() {
+            Some(FileChange::Modified)
+        } else if status.is_wt_deleted() {
+            Some(FileChange::Deleted)
+        } else if status.is_wt_typechange() {
+            Some(FileChange::TypeChange)
+        } else {
+            None
+        };
+
+        // Renames are reported with the old and the new file of the staged or unstaged change
+        let delta = entry.head_to_index().or_else(|| entry.index_to_workdir());
+        let path_of = |file: git2::DiffFile| file.path().map(|p| p.to_string_lossy().to_string());
+        let (old_path, path) = match delta {
+            Some(delta)
+                if staged == Some(FileChange::Renamed) || unstaged == Some(FileChange::Renamed) =>
+            {
+                (path_of(delta.old_file()), path_of(delta.new_file()))
+            }
+            _ => (None, None),
+        };
+        let path = path
+            .or_else(|| entry.path().map(str::to_string))
+            .unwrap_or_else(|| String::from_utf8_lossy(entry.path_bytes()).to_string());
+
+        Self {
+            path,
+            old_path,
+            staged,
+            unstaged,
+            is_untracked: status.is_wt_new(),
+            is_conflicted: status.is_conflicted(),
+        }
+    }
+}
+
+/// Number of commits the current branch is ahead and behind its upstream branch
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "camelCase")
+)]
+#[derive(Clone, Debug)]
+pub struct UpstreamStatus {
+    /// Short name of the upstream branch, e.g. `origin/main`
+    name: String,
+    /// Number of commits on the current branch which are not on the upstream branch
+    ahead: usize,
+    /// Number of commits on the upstream branch which are not on the current branch
+    behind: usize,
+}
+
+impl UpstreamStatus {
+    /// Get the short name of the upstream branch
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the number of commits on the current branch which are not on the upstream branch
+    pub fn ahead(&self) -> usize {
+        self.ahead
+    }
+
+    /// Get the number of commits on the upstream branch which are not on the current branch
+    pub fn behind(&self) -> usize {
+        self.behind
+    }
+}
+
+/// Status of the index and the working tree
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "camelCase")
+)]
+#[derive(Clone, Debug)]
+pub struct WorkingTreeStatus {
+    /// Files with changes, untracked files and files with conflicts, ordered by path
+    files: Vec<FileStatus>,
+    /// Upstream of the current branch, not set in a detached `HEAD` state or without upstream
+    upstream: Option<UpstreamStatus>,
+}
+
+impl WorkingTreeStatus {
+    /// Get the files with changes, untracked files and files with conflicts
+    pub fn files(&self) -> &[FileStatus] {
+        &self.files
+    }
+
+    /// Get the upstream of the current branch
+    pub fn upstream(&self) -> Option<&UpstreamStatus> {
+        self.upstream.as_ref()
+    }
+
+    /// Check whether there are no changes, untracked files or conflicts
+    pub fn is_clean(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+/// Get the upstream of the current branch with the number of commits ahead and behind
+fn upstream_status(repo: &git2::Repository) -> Result<Option<UpstreamStatus>> {
+    let Ok(head) = repo.head() else {
+        return Ok(None);
+    };
+    if !head.is_branch() {
+        return Ok(None);
+    }
+    let branch = git2::Branch::wrap(head);
+    let upstream = match branch.upstream() {
+        Ok(upstream) => upstream,
+        Err(e) if e.code() == git2::ErrorCode::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(Error::from_ctx_and_error(
+                "Failed to get upstream branch",
+                e,
+            ));
+        }
+    };
+    let (Some(local), Some(remote)) = (branch.get().target(), upstream.get().target()) else {
+        return Ok(None);
+    };
+    let (ahead, behind) = repo
+        .graph_ahead_behind(local, remote)
+        .map_err(|e| Error::from_ctx_and_error("Failed to compare with upstream branch", e))?;
+    Ok(Some(UpstreamStatus {
+        name: String::from_utf8_lossy(upstream.get().shorthand_bytes()).to_string(),
+        ahead,
+        behind,
+    }))
+}
+
+/// Get the status of the index and the working tree, similar to `git status`
+pub(crate) fn status(repo: &git2::Repository) -> Result<WorkingTreeStatus> {
+    let mut options = git2::StatusOptions::new();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .renames_head_to_index(true)
+        .renames_index_to_workdir(true);
+    let statuses = repo
+        .statuses(Some(&mut options))
+        .map_err(|e| Error::from_ctx_and_error("Failed to get status", e))?;
+    let mut files: Vec<_> = statuses
+        .iter()
+        .filter(|entry| !entry.status().is_ignored())
+        .map(|entry| FileStatus::from_status_entry(&entry))
+        .collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(WorkingTreeStatus {
+        files,
+        upstream: upstream_status(repo)?,
+    })
+}
+
+/// Stages compared by a diff of the working tree
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WorkingTreeDiff {
+    /// Changes in the working tree which are not staged, like `git diff`
+    #[default]
+    Unstaged,
+    /// Changes staged in the index, like `git diff --cached`
+    Staged,
+    /// All changes in the working tree compared to `HEAD`, like `git diff HEAD`
+    All,
+}
+
+/// Get the tree of `HEAD`, `None` if `HEAD` does not point to a commit yet
+fn head_tree(repo: &git2::Repository) -> Result<Option<git2::Tree<'_>>> {
+    match repo.head() {
+        Ok(head) => head
+            .peel_to_tree()
+            .map(Some)
+            .map_err(|e| Error::from_ctx_and_error("Failed to get tree of HEAD", e)),
+        Err(e) if e.code() == git2::ErrorCode::UnbornBranch => Ok(None),
+        Err(e) => Err(Error::from_ctx_and_error("Failed to get HEAD", e)),
+    }
+}
+
+/// Get the diff of the stages `target` of the working tree
+pub(crate) fn git2_diff_for_working_tree<'repo>(
+    repo: &'repo git2::Repository,
+    target: WorkingTreeDiff,
+    options: &mut git2::DiffOptions,
+) -> Result<git2::Diff<'repo>> {
+    let diff = match target {
+        WorkingTreeDiff::Unstaged => repo.diff_index_to_workdir(None, Some(options)),
+        WorkingTreeDiff::Staged => {
+            repo.diff_tree_to_index(head_tree(repo)?.as_ref(), None, Some(options))
+        }
+        WorkingTreeDiff::All => {
+            repo.diff_tree_to_workdir_with_index(head_tree(repo)?.as_ref(), Some(options))
+        }
+    };
+    diff.map_err(|e| Error::from_ctx_and_error("Failed to diff working tree", e))
+}
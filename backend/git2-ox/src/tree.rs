@@ -0,0 +1,246 @@
+use std::path::Path;
+
+use crate::{Result, error::Error, utils};
+
+/// Kind of an entry of a tree
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "lowercase")
+)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TreeEntryKind {
+    /// Directory
+    Tree,
+    /// File or symbolic link
+    Blob,
+    /// Submodule, the ID is the ID of the checked out commit
+    Commit,
+}
+
+/// Entry of a tree, similar to a line of `git ls-tree -l`
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "camelCase")
+)]
+#[derive(Clone, Debug)]
+pub struct TreeEntry {
+    /// Name of the entry
+    name: String,
+    /// Path of the entry relative to the root of the repository
+    path: String,
+    /// Kind of the entry
+    kind: TreeEntryKind,
+    /// File mode in octal notation, e.g. `100644` for files, `100755` for executables and
+    /// `120000` for symbolic links
+    mode: String,
+    /// ID of the object of the entry
+    id: String,
+    /// Size of the blob in bytes, only set for blobs
+    size: Option<usize>,
+}
+
+impl TreeEntry {
+    /// Get the name of the entry
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the path of the entry relative to the root of the repository
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Get the kind of the entry
+    pub fn kind(&self) -> TreeEntryKind {
+        self.kind
+    }
+
+    /// Get the file mode in octal notation
+    pub fn mode(&self) -> &str {
+        &self.mode
+    }
+
+    /// Get the ID of the object of the entry
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Get the size of the blob in bytes, `None` if the entry is not a blob
+    pub fn size(&self) -> Option<usize> {
+        self.size
+    }
+}
+
+/// Content of a file at a revision
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "camelCase")
+)]
+#[derive(Clone, Debug)]
+pub struct Blob {
+    /// ID of the blob
+    id: String,
+    /// Path of the file relative to the root of the repository
+    path: String,
+    /// Size of the blob in bytes
+    size: usize,
+    /// Whether the content is binary, the content of binary blobs is not returned
+    is_binary: bool,
+    /// Content of the blob, invalid UTF-8 sequences are replaced. `None` for binary blobs
+    content: Option<String>,
+    /// Whether the content was cut at the size limit
+    is_truncated: bool,
+}
+
+impl Blob {
+    /// Get the ID of the blob
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Get the path of the file relative to the root of the repository
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Get the size of the blob in bytes
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Get whether the content is binary
+    pub fn is_binary(&self) -> bool {
+        self.is_binary
+    }
+
+    /// Get the content of the blob, `None` for binary blobs
+    pub fn content(&self) -> Option<&str> {
+        self.content.as_deref()
+    }
+
+    /// Get whether the content was cut at the size limit
+    pub fn is_truncated(&self) -> bool {
+        self.is_truncated
+    }
+}
+
+/// Normalize `path` to a path relative to the root of the tree without leading or trailing slashes
+fn normalize_path(path: &str) -> &str {
+    path.trim_matches('/')
+}
+
+/// Get the object at `path` in the tree of `revision`
+fn get_object_at_path<'repo>(
+    repo: &'repo git2::Repository,
+    revision: &str,
+    path: &str,
+) -> Result<git2::Object<'repo>> {
+    let tree = utils::get_tree_for_revision(repo, revision)?;
+    if path.is_empty() {
+        return Ok(tree.into_object());
+    }
+    tree.get_path(Path::new(path))
+        .and_then(|entry| entry.to_object(repo))
+        .map_err(|e| {
+            Error::from_ctx_and_error(format!("Path '{path}' at revision '{revision}'"), e)
+        })
+}
+
+/// Error for a path which is not of the expected kind
+fn unexpected_kind(revision: &str, path: &str, expected: &str) -> Error {
+    Error::from_ctx_and_error(
+        format!("path '{path}' at revision '{revision}'"),
+        git2::Error::new(
+            git2::ErrorCode::Invalid,
+            git2::ErrorClass::Object,
+            format!("the path is not a {expected}"),
+        ),
+    )
+}
+
+/// List the entries of the directory at `path` in the tree of `revision`, directories first
+pub(crate) fn list_tree(
+    repo: &git2::Repository,
+    revision: &str,
+    path: &str,
+) -> Result<Vec<TreeEntry>> {
+    let path = normalize_path(path);
+    let object = get_object_at_path(repo, revision, path)?;
+    let tree = object
+        .as_tree()
+        .ok_or_else(|| unexpected_kind(revision, path, "directory"))?;
+    let odb = repo
+        .odb()
+        .map_err(|e| Error::from_ctx_and_error("Failed to open object database", e))?;
+
+    let mut entries = Vec::with_capacity(tree.len());
+    for entry in tree.iter() {
+        let name = String::from_utf8_lossy(entry.name_bytes()).to_string();
+        let (kind, size) = match entry.kind() {
+            Some(git2::ObjectType::Tree) => (TreeEntryKind::Tree, None),
+            Some(git2::ObjectType::Commit) => (TreeEntryKind::Commit, None),
+            _ => {
+                // Only the header is read, the content of the blob is not needed for the size
+                let (size, _) = odb
+                    .read_header(entry.id())
+                    .map_err(|e| Error::from_ctx_and_error(format!("Blob '{}'", entry.id()), e))?;
+                (TreeEntryKind::Blob, Some(size))
+            }
+        };
+        entries.push(TreeEntry {
+            path: match path {
+                "" => name.clone(),
+                _ => format!("{path}/{name}"),
+            },
+            name,
+            kind,
+            mode: format!("{:06o}", entry.filemode()),
+            id: entry.id().to_string(),
+            size,
+        });
+    }
+    entries.sort_by_key(|e| e.kind != TreeEntryKind::Tree);
+    Ok(entries)
+}
+
+/// Read the file at `path` in the tree of `revision`
+///
+/// The content is cut after `max_size` bytes if set.
+pub(crate) fn read_blob(
+    repo: &git2::Repository,
+    revision: &str,
+    path: &str,
+    max_size: Option<usize>,
+) -> Result<Blob> {
+    let path = normalize_path(path);
+    let object = get_object_at_path(repo, revision, path)?;
+    let blob = object
+        .as_blob()
+        .ok_or_else(|| unexpected_kind(revision, path, "file"))?;
+
+    let is_binary = blob.is_binary();
+    let bytes = blob.content();
+    let limit = max_size.unwrap_or(usize::MAX).min(bytes.len());
+    let content = (!is_binary).then(|| match std::str::from_utf8(&bytes[..limit]) {
+        Ok(content) => content.to_string(),
+        // Do not report a character cut at the limit as invalid
+        Err(e) if limit < bytes.len() && e.error_len().is_none() => {
+            String::from_utf8_lossy(&bytes[..e.valid_up_to()]).to_string()
+        }
+        Err(_) => String::from_utf8_lossy(&bytes[..limit]).to_string(),
+    });
+    Ok(Blob {
+        id: blob.id().to_string(),
+        path: path.to_string(),
+        size: bytes.len(),
+        is_binary,
+        content,
+        is_truncated: !is_binary && limit < bytes.len(),
+    })
+}
@@ -77,3 +77,11 @@ pub fn revwalk_for_range<'repo>(
 
     Ok(revwalk)
 }
+
+/// Get the signature of the user configured in the repository, falling back to a generic
+/// `debug-flow` signature if no user is configured
+pub fn get_signature(repo: &git2::Repository) -> Result<git2::Signature<'static>> {
+    repo.signature()
+        .or_else(|_| git2::Signature::now("debug-flow", "debug-flow@localhost"))
+        .map_err(|e| Error::from_ctx_and_error("Failed to create signature", e))
+}
@@ -0,0 +1,261 @@
+use crate::{Commit, Result, error::Error, utils};
+use std::path::{Path, PathBuf};
+
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "camelCase")
+)]
+#[derive(Clone, Debug)]
+pub struct Worktree {
+    /// Name of the worktree
+    name: String,
+    /// Path of the working directory of the worktree
+    #[cfg_attr(feature = "utoipa", schema(value_type = String))]
+    path: PathBuf,
+    /// Commit checked out in the worktree, not set if the worktree cannot be opened
+    head: Option<Commit>,
+    /// Short name of the branch checked out in the worktree, not set if `HEAD` is detached
+    branch: Option<String>,
+    /// Whether the worktree is locked and can not be pruned or removed
+    is_locked: bool,
+    /// Reason given when the worktree was locked
+    lock_reason: Option<String>,
+    /// Whether the working directory of the worktree is missing, so it can be pruned
+    is_prunable: bool,
+}
+
+impl Worktree {
+    /// Get the name of the worktree
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the path of the working directory of the worktree
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get the commit checked out in the worktree
+    pub fn head(&self) -> Option<&Commit> {
+        self.head.as_ref()
+    }
+
+    /// Get the short name of the branch checked out in the worktree
+    pub fn branch(&self) -> Option<&str> {
+        self.branch.as_deref()
+    }
+
+    /// Get whether the worktree is locked
+    pub fn is_locked(&self) -> bool {
+        self.is_locked
+    }
+
+    /// Get the reason given when the worktree was locked
+    pub fn lock_reason(&self) -> Option<&str> {
+        self.lock_reason.as_deref()
+    }
+
+    /// Get whether the working directory of the worktree is missing
+    pub fn is_prunable(&self) -> bool {
+        self.is_prunable
+    }
+
+    /// Create a worktree from a `git2::Worktree`
+    pub fn try_from_git2_worktree(worktree: &git2::Worktree) -> Result<Self> {
+        let name = worktree
+            .name()
+            .ok_or_else(|| Error::from_ctx("Invalid UTF-8 in worktree name"))?
+            .to_string();
+        let head = git2::Repository::open_from_worktree(worktree)
+            .ok()
+            .and_then(|repo| {
+                let head = repo.head().ok()?;
+                let branch = head
+                    .is_branch()
+                    .then(|| head.shorthand().map(str::to_string))
+                    .flatten();
+                let commit = head.peel_to_commit().ok()?;
+                Some((Commit::from(commit), branch))
+            });
+        let (head, branch) = head.unzip();
+        let (is_locked, lock_reason) = match worktree.is_locked() {
+            Ok(git2::WorktreeLockStatus::Locked(reason)) => (true, reason),
+            _ => (false, None),
+        };
+        Ok(Self {
+            name,
+            path: worktree.path().to_path_buf(),
+            head,
+            branch: branch.flatten(),
+            is_locked,
+            lock_reason,
+            is_prunable: worktree.validate().is_err(),
+        })
+    }
+}
+
+/// Find the linked worktree `name`
+fn find_git2_worktree(repo: &git2::Repository, name: &str) -> Result<git2::Worktree> {
+    repo.find_worktree(name)
+        .map_err(|e| Error::from_ctx_and_error(format!("Worktree '{name}'"), e))
+}
+
+/// Get the linked worktree `name`
+pub(crate) fn find_worktree(repo: &git2::Repository, name: &str) -> Result<Worktree> {
+    Worktree::try_from_git2_worktree(&find_git2_worktree(repo, name)?)
+}
+
+/// List the linked worktrees ordered by name, the main worktree is not included
+pub(crate) fn list_worktrees(repo: &git2::Repository) -> Result<Vec<Worktree>> {
+    let names = repo
+        .worktrees()
+        .map_err(|e| Error::from_ctx_and_error("Failed to list worktrees", e))?;
+    let mut worktrees = names
+        .iter()
+        .flatten()
+        .map(|name| find_worktree(repo, name))
+        .collect::<Result<Vec<_>>>()?;
+    worktrees.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(worktrees)
+}
+
+/// Add the worktree `name` at `path` and check out `revision` in it, on the new branch `branch` or
+/// with a detached `HEAD`
+///
+/// libgit2 always checks out a branch in new worktrees, for a detached `HEAD` a temporary branch
+/// named `name` is created and deleted again once the `HEAD` of the worktree is detached.
+pub(crate) fn add_worktree(
+    repo: &git2::Repository,
+    name: &str,
+    path: &Path,
+    revision: &str,
+    branch: Option<&str>,
+) -> Result<Worktree> {
+    let commit = utils::get_commit_for_revision(repo, revision)?;
+    let branch_name = branch.unwrap_or(name);
+    let mut branch_ref = repo.branch(branch_name, &commit, false).map_err(|e| {
+        Error::from_ctx_and_error(
+            format!("Failed to create branch '{branch_name}' for worktree '{name}'"),
+            e,
+        )
+    })?;
+
+    let result = (|| {
+        let mut opts = git2::WorktreeAddOptions::new();
+        opts.reference(Some(branch_ref.get()));
+        let worktree = repo.worktree(name, path, Some(&opts)).map_err(|e| {
+            Error::from_ctx_and_error(format!("Failed to add worktree '{name}'"), e)
+        })?;
+        if branch.is_none() {
+            let worktree_repo = git2::Repository::open_from_worktree(&worktree).map_err(|e| {
+                Error::from_ctx_and_error(format!("Failed to open worktree '{name}'"), e)
+            })?;
+            worktree_repo.set_head_detached(commit.id()).map_err(|e| {
+                Error::from_ctx_and_error(format!("Failed to detach HEAD of worktree '{name}'"), e)
+            })?;
+        }
+        Worktree::try_from_git2_worktree(&worktree)
+    })();
+
+    // The temporary branch is always deleted, a new branch only if the worktree was not added
+    if (branch.is_none() || result.is_err())
+        && let Err(e) = branch_ref.delete()
+    {
+        log::warn!("Failed to delete branch '{branch_name}' of worktree '{name}': {e}");
+    }
+    result
+}
+
+/// Lock the worktree `name` to protect it from being pruned or removed, `reason` is shown by git
+pub(crate) fn lock_worktree(
+    repo: &git2::Repository,
+    name: &str,
+    reason: Option<&str>,
+) -> Result<Worktree> {
+    let worktree = find_git2_worktree(repo, name)?;
+    worktree
+        .lock(reason)
+        .map_err(|e| Error::from_ctx_and_error(format!("Failed to lock worktree '{name}'"), e))?;
+    Worktree::try_from_git2_worktree(&worktree)
+}
+
+/// Unlock the worktree `name`
+pub(crate) fn unlock_worktree(repo: &git2::Repository, name: &str) -> Result<Worktree> {
+    let worktree = find_git2_worktree(repo, name)?;
+    worktree
+        .unlock()
+        .map_err(|e| Error::from_ctx_and_error(format!("Failed to unlock worktree '{name}'"), e))?;
+    Worktree::try_from_git2_worktree(&worktree)
+}
+
+/// Remove the administrative data of worktrees whose working directory is missing, like
+/// `git worktree prune`. Locked worktrees are kept. Returns the pruned worktrees.
+pub(crate) fn prune_worktrees(repo: &git2::Repository) -> Result<Vec<Worktree>> {
+    let mut pruned = Vec::new();
+    for worktree in list_worktrees(repo)? {
+        if !worktree.is_prunable || worktree.is_locked {
+            continue;
+        }
+        find_git2_worktree(repo, &worktree.name)?
+            .prune(None)
+            .map_err(|e| {
+                Error::from_ctx_and_error(
+                    format!("Failed to prune worktree '{}'", worktree.name),
+                    e,
+                )
+            })?;
+        pruned.push(worktree);
+    }
+    Ok(pruned)
+}
+
+/// Get the paths of files with local changes in `worktree`, including untracked files
+fn dirty_paths(worktree: &git2::Worktree) -> Result<Vec<String>> {
+    let Ok(repo) = git2::Repository::open_from_worktree(worktree) else {
+        // A missing working directory has no local changes
+        return Ok(Vec::new());
+    };
+    let mut options = git2::StatusOptions::new();
+    options.include_untracked(true).exclude_submodules(true);
+    let statuses = repo
+        .statuses(Some(&mut options))
+        .map_err(|e| Error::from_ctx_and_error("Failed to get status of worktree", e))?;
+    Ok(statuses
+        .iter()
+        .map(|entry| String::from_utf8_lossy(entry.path_bytes()).to_string())
+        .collect())
+}
+
+/// Remove the worktree `name` including its working directory, like `git worktree remove`
+///
+/// Locked worktrees and worktrees with local changes are only removed if `force` is set.
+pub(crate) fn remove_worktree(repo: &git2::Repository, name: &str, force: bool) -> Result<()> {
+    let worktree = find_git2_worktree(repo, name)?;
+    if !force {
+        if let Ok(git2::WorktreeLockStatus::Locked(reason)) = worktree.is_locked() {
+            let reason = reason.map(|r| format!(": {r}")).unwrap_or_default();
+            return Err(Error::from_ctx_and_error(
+                format!("worktree '{name}'"),
+                git2::Error::new(
+                    git2::ErrorCode::Invalid,
+                    git2::ErrorClass::Worktree,
+                    format!("the worktree is locked{reason}"),
+                ),
+            ));
+        }
+        let dirty = dirty_paths(&worktree)?;
+        if !dirty.is_empty() {
+            return Err(Error::conflict(
+                format!("Worktree '{name}' has local changes"),
+                dirty,
+            ));
+        }
+    }
+    let mut opts = git2::WorktreePruneOptions::new();
+    opts.valid(true).locked(force).working_tree(true);
+    worktree
+        .prune(Some(&mut opts))
+        .map_err(|e| Error::from_ctx_and_error(format!("Failed to remove worktree '{name}'"), e))
+}
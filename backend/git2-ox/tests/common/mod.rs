@@ -20,6 +20,10 @@ impl TempRepository {
         &self.repo
     }
 
+    pub fn repo_mut(&mut self) -> &mut git2_ox::Repository {
+        &mut self.repo
+    }
+
     pub fn path(&self) -> &std::path::Path {
         self.temp_dir.path()
     }
@@ -1,3 +1,6 @@
+// The iterators of the repository are converted explicitly in the older tests
+#![allow(clippy::useless_conversion)]
+
 mod common;
 use git2_ox::CommitProperties;
 
@@ -79,7 +82,7 @@ fn test_list_commits() {
     // Iterate from HEAD to base of repo
     assert_eq!(
         t.repo()
-            .iter_commits(None, None)
+            .iter_commits(None, None, git2_ox::CommitFilter::new())
             .unwrap()
             .into_iter()
             .map(|r| {
@@ -92,7 +95,7 @@ fn test_list_commits() {
     // Iterate from HEAD to base of repo
     assert_eq!(
         t.repo()
-            .iter_commits(None, Some("HEAD"))
+            .iter_commits(None, Some("HEAD"), git2_ox::CommitFilter::new())
             .unwrap()
             .into_iter()
             .map(|r| {
@@ -106,7 +109,7 @@ fn test_list_commits() {
     // Iterate from HEAD of main to base of repo
     assert_eq!(
         t.repo()
-            .iter_commits(None, Some(&branch_name))
+            .iter_commits(None, Some(&branch_name), git2_ox::CommitFilter::new())
             .unwrap()
             .into_iter()
             .map(|r| {
@@ -120,7 +123,11 @@ fn test_list_commits() {
     // Iterate from HEAD of main to base of repo
     assert_eq!(
         t.repo()
-            .iter_commits(Some(&commit_ids[commit_ids.len() - 2]), None)
+            .iter_commits(
+                Some(&commit_ids[commit_ids.len() - 2]),
+                None,
+                git2_ox::CommitFilter::new()
+            )
             .unwrap()
             .into_iter()
             .map(|r| {
@@ -132,6 +139,154 @@ fn test_list_commits() {
     );
 }
 
+#[test]
+fn test_list_commits_after() {
+    let t = common::TempRepository::try_init().unwrap();
+    let mut commit_ids: Vec<_> = (0..5)
+        .map(|_| t.create_and_commit_random_file().1)
+        .collect();
+    commit_ids.reverse();
+
+    let ids_after = |base: Option<&str>, after: Option<&str>| {
+        t.repo()
+            .iter_commits_after(base, None, after, git2_ox::CommitFilter::new())
+            .map(|commits| {
+                commits
+                    .map(|c| c.unwrap().id().to_string())
+                    .collect::<Vec<_>>()
+            })
+    };
+    assert_eq!(ids_after(None, None).unwrap(), commit_ids);
+    assert_eq!(
+        ids_after(None, Some(&commit_ids[1])).unwrap(),
+        &commit_ids[2..]
+    );
+    assert!(ids_after(None, Some(&commit_ids[4])).unwrap().is_empty());
+
+    // The commit to start after must be part of the range
+    assert!(matches!(
+        ids_after(Some(&commit_ids[2]), Some(&commit_ids[3])),
+        Err(git2_ox::error::Error::NotFound(_))
+    ));
+}
+
+#[test]
+fn test_list_commits_filtered() {
+    use git2_ox::CommitFilter;
+
+    let t = common::TempRepository::try_init().unwrap();
+    let repo = t.repo().repo();
+    let commit = |path: &str, author: &str, time: i64, message: &str| {
+        let file_path = t.path().join(path);
+        std::fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+        std::fs::write(&file_path, message).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(std::path::Path::new(path)).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let author = git2::Signature::new(
+            author,
+            &format!("{}@example.com", author.to_lowercase()),
+            &git2::Time::new(time, 0),
+        )
+        .unwrap();
+        let committer =
+            git2::Signature::new("CI", "ci@example.com", &git2::Time::new(time, 0)).unwrap();
+        let parent = repo.head().ok().map(|h| h.peel_to_commit().unwrap());
+        let parents: Vec<_> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &author, &committer, message, &tree, &parents)
+            .unwrap()
+            .to_string()
+    };
+    let first = commit(
+        "src/net/socket.rs",
+        "Alice",
+        1000,
+        "Add socket\n\nFixes #12",
+    );
+    let second = commit("src/ui/view.rs", "Bob", 2000, "Add view");
+    let third = commit("src/net/retry.rs", "Alice", 3000, "Retry connections");
+    let (first, second, third) = (first.as_str(), second.as_str(), third.as_str());
+
+    let ids = |filter: CommitFilter| {
+        t.repo()
+            .iter_commits(None, None, filter)
+            .unwrap()
+            .map(|c| c.unwrap().id().to_string())
+            .collect::<Vec<_>>()
+    };
+    let at = |seconds| chrono::DateTime::from_timestamp(seconds, 0).unwrap();
+
+    assert_eq!(ids(CommitFilter::new()), [third, second, first]);
+    assert_eq!(ids(CommitFilter::new().author("alice")), [third, first]);
+    assert_eq!(ids(CommitFilter::new().author("bob@")), [second]);
+    assert_eq!(ids(CommitFilter::new().committer("ci")).len(), 3);
+    assert!(ids(CommitFilter::new().committer("alice")).is_empty());
+    assert_eq!(
+        ids(CommitFilter::new().since(at(2000)).until(at(2999))),
+        [second]
+    );
+    assert_eq!(
+        ids(CommitFilter::new().paths(vec!["src/net".to_string()])),
+        [third, first]
+    );
+    assert_eq!(
+        ids(CommitFilter::new().paths(vec!["src/*/view.rs".to_string()])),
+        [second]
+    );
+    let message = regex::Regex::new(r"#\d+").unwrap();
+    assert_eq!(ids(CommitFilter::new().message(message)), [first]);
+    assert_eq!(
+        ids(CommitFilter::new()
+            .author("alice")
+            .since(at(1500))
+            .paths(vec!["src/net".to_string()])),
+        [third]
+    );
+
+    // A side branch adding a file merged after the third commit
+    let with_theme = |base: &str, parents: &[&str], time: i64, message: &str| {
+        let base = repo
+            .find_commit(git2::Oid::from_str(base).unwrap())
+            .unwrap();
+        let mut builder = repo.treebuilder(Some(&base.tree().unwrap())).unwrap();
+        let blob = repo.blob(b"dark").unwrap();
+        builder.insert("theme.rs", blob, 0o100644).unwrap();
+        let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+        let signature =
+            git2::Signature::new("Carol", "carol@example.com", &git2::Time::new(time, 0)).unwrap();
+        let parents: Vec<_> = parents
+            .iter()
+            .map(|id| repo.find_commit(git2::Oid::from_str(id).unwrap()).unwrap())
+            .collect();
+        let parents: Vec<_> = parents.iter().collect();
+        repo.commit(None, &signature, &signature, message, &tree, &parents)
+            .unwrap()
+            .to_string()
+    };
+    let side = with_theme(first, &[first], 4000, "Add theme");
+    let merge = with_theme(third, &[third, &side], 5000, "Merge theme");
+    repo.reference(
+        "refs/heads/main",
+        git2::Oid::from_str(&merge).unwrap(),
+        true,
+        "merge",
+    )
+    .unwrap();
+    repo.set_head("refs/heads/main").unwrap();
+    let (side, merge) = (side.as_str(), merge.as_str());
+
+    // The merge is skipped for paths it takes unchanged from one of its parents
+    assert_eq!(
+        ids(CommitFilter::new().paths(vec!["theme.rs".to_string()])),
+        [side]
+    );
+    assert_eq!(
+        ids(CommitFilter::new().paths(vec!["src/ui".to_string(), "theme.rs".to_string()])),
+        [merge, side, second]
+    );
+}
+
 #[test]
 fn test_list_commits_multiple_branches() {
     let t = common::TempRepository::try_init().unwrap();
@@ -166,7 +321,11 @@ fn test_list_commits_multiple_branches() {
 
     assert_eq!(
         t.repo()
-            .iter_commits(None, Some(&default_branch_name))
+            .iter_commits(
+                None,
+                Some(&default_branch_name),
+                git2_ox::CommitFilter::new()
+            )
             .unwrap()
             .into_iter()
             .map(|r| {
@@ -178,7 +337,7 @@ fn test_list_commits_multiple_branches() {
     );
     assert_eq!(
         t.repo()
-            .iter_commits(None, Some(branch_name))
+            .iter_commits(None, Some(branch_name), git2_ox::CommitFilter::new())
             .unwrap()
             .into_iter()
             .map(|r| {
@@ -190,7 +349,7 @@ fn test_list_commits_multiple_branches() {
     );
     assert_eq!(
         t.repo()
-            .iter_commits(None, None)
+            .iter_commits(None, None, git2_ox::CommitFilter::new())
             .unwrap()
             .into_iter()
             .map(|r| {
@@ -308,3 +467,1011 @@ fn test_iter_references() {
     expected_ref_names.insert(default_branch_name);
     assert_eq!(HashSet::from_iter(existing_ref_names), expected_ref_names);
 }
+
+#[test]
+fn test_add_and_remove_detached_worktree() {
+    let t = common::TempRepository::try_init().unwrap();
+    let (first_file, first_commit) = t.create_and_commit_random_file();
+    let (second_file, _) = t.create_and_commit_random_file();
+
+    let worktree_dir = tempfile::tempdir().unwrap();
+    let worktree_path = worktree_dir.path().join("wt");
+    let worktree = t
+        .repo()
+        .add_detached_worktree("wt", &worktree_path, &first_commit)
+        .unwrap();
+    assert_eq!(worktree.name(), "wt");
+    assert_eq!(
+        worktree.head().unwrap().id().to_string(),
+        first_commit.to_string()
+    );
+    assert!(worktree_path.join(&first_file).is_file());
+    assert!(!worktree_path.join(&second_file).exists());
+    // The temporary branch is deleted again
+    assert!(t.repo().iter_branches().unwrap().all(|b| b.name() != "wt"));
+
+    t.repo().remove_worktree("wt", false).unwrap();
+    assert!(!worktree_path.exists());
+    assert!(t.repo().remove_worktree("wt", false).is_err());
+}
+
+#[test]
+fn test_manage_worktrees() {
+    let t = common::TempRepository::try_init().unwrap();
+    let (file, first_commit) = t.create_and_commit_random_file();
+    let worktree_dir = tempfile::tempdir().unwrap();
+    let path = |name: &str| worktree_dir.path().join(name);
+
+    let worktree = t
+        .repo()
+        .add_worktree("feature", &path("feature"), &first_commit, Some("fix"))
+        .unwrap();
+    assert_eq!(worktree.branch(), Some("fix"));
+    assert!(t.repo().iter_branches().unwrap().any(|b| b.name() == "fix"));
+    let detached = t
+        .repo()
+        .add_worktree("detached", &path("detached"), "HEAD", None)
+        .unwrap();
+    assert_eq!(detached.branch(), None);
+    let names: Vec<_> = t
+        .repo()
+        .list_worktrees()
+        .unwrap()
+        .iter()
+        .map(|w| w.name().to_string())
+        .collect();
+    assert_eq!(names, ["detached", "feature"]);
+
+    // Locked worktrees and worktrees with local changes are only removed by force
+    let locked = t
+        .repo()
+        .lock_worktree("feature", Some("keep build"))
+        .unwrap();
+    assert!(locked.is_locked());
+    assert_eq!(locked.lock_reason(), Some("keep build"));
+    assert!(matches!(
+        t.repo().remove_worktree("feature", false),
+        Err(git2_ox::error::Error::Invalid(_))
+    ));
+    assert!(!t.repo().unlock_worktree("feature").unwrap().is_locked());
+    std::fs::write(path("feature").join(&file), "changed").unwrap();
+    match t.repo().remove_worktree("feature", false) {
+        Err(git2_ox::error::Error::Conflict(conflict)) => assert_eq!(conflict.paths, [file]),
+        other => panic!("expected a conflict, got {other:?}"),
+    }
+    t.repo().remove_worktree("feature", true).unwrap();
+    assert!(!path("feature").exists());
+
+    // Worktrees whose directory is missing are pruned unless they are locked
+    std::fs::remove_dir_all(path("detached")).unwrap();
+    assert!(t.repo().find_worktree("detached").unwrap().is_prunable());
+    t.repo().lock_worktree("detached", None).unwrap();
+    assert!(t.repo().prune_worktrees().unwrap().is_empty());
+    t.repo().unlock_worktree("detached").unwrap();
+    let pruned = t.repo().prune_worktrees().unwrap();
+    assert_eq!(pruned.len(), 1);
+    assert!(t.repo().list_worktrees().unwrap().is_empty());
+    assert!(matches!(
+        t.repo().find_worktree("detached"),
+        Err(git2_ox::error::Error::NotFound(_))
+    ));
+}
+
+#[test]
+fn test_bisect() {
+    use git2_ox::BisectStep;
+
+    let t = common::TempRepository::try_init().unwrap();
+    let commits: Vec<_> = (0..8)
+        .map(|_| t.create_and_commit_random_file().1)
+        .collect();
+    // The fifth commit introduced the bug
+    let first_bad = &commits[4];
+    let mut good = vec![commits[0].clone()];
+    let mut bad = commits[7].clone();
+
+    let mut steps = 0;
+    let found = loop {
+        let good_revs: Vec<_> = good.iter().map(String::as_str).collect();
+        match t.repo().bisect(&bad, &good_revs, &[]).unwrap() {
+            BisectStep::Next { commit, .. } => {
+                let index = commits.iter().position(|c| c == commit.id()).unwrap();
+                if index >= 4 {
+                    bad = commit.id().to_string();
+                } else {
+                    good.push(commit.id().to_string());
+                }
+            }
+            BisectStep::FirstBad { commit } => break commit.id().to_string(),
+            BisectStep::Ambiguous { .. } => panic!("Nothing was skipped"),
+        }
+        steps += 1;
+    };
+    assert_eq!(&found, first_bad);
+    assert!(steps <= 3);
+
+    // Only skipped commits left
+    match t
+        .repo()
+        .bisect(&commits[5], &[&commits[3]], &[&commits[4]])
+        .unwrap()
+    {
+        BisectStep::Ambiguous { candidates } => assert_eq!(candidates.len(), 2),
+        step => panic!("Unexpected step {step:?}"),
+    }
+    assert!(t.repo().bisect(&commits[0], &[&commits[3]], &[]).is_err());
+}
+
+#[test]
+fn test_bisect_merges() {
+    use git2_ox::BisectStep;
+
+    let t = common::TempRepository::try_init().unwrap();
+    let repo = t.repo().repo();
+    let tree = repo
+        .find_tree(repo.index().unwrap().write_tree().unwrap())
+        .unwrap();
+    let signature = git2::Signature::now("test", "test@example.com").unwrap();
+    let commit = |message: &str, parents: &[&git2::Commit]| {
+        let oid = repo
+            .commit(None, &signature, &signature, message, &tree, parents)
+            .unwrap();
+        repo.find_commit(oid).unwrap()
+    };
+    // G <- A1 <- A2 <- A3 <- M <- D1 <- D2
+    //  \                    /
+    //   <- B1 <- B2 <- B3 <-
+    let g = commit("G", &[]);
+    let a1 = commit("A1", &[&g]);
+    let a2 = commit("A2", &[&a1]);
+    let a3 = commit("A3", &[&a2]);
+    let b1 = commit("B1", &[&g]);
+    let b2 = commit("B2", &[&b1]);
+    let b3 = commit("B3", &[&b2]);
+    let m = commit("M", &[&a3, &b3]);
+    let d1 = commit("D1", &[&m]);
+    let d2 = commit("D2", &[&d1]);
+
+    // The merge has seven ancestors in the range of nine commits, the tips of the branches with
+    // three ancestors split the range best
+    let good = g.id().to_string();
+    match t
+        .repo()
+        .bisect(&d2.id().to_string(), &[good.as_str()], &[])
+        .unwrap()
+    {
+        BisectStep::Next {
+            commit, remaining, ..
+        } => {
+            assert_eq!(remaining, 9);
+            let tips = [a3.id().to_string(), b3.id().to_string()];
+            assert!(tips.iter().any(|tip| tip == commit.id()));
+        }
+        step => panic!("Unexpected step {step:?}"),
+    }
+}
+
+#[test]
+fn test_hooks_path() {
+    let t = common::TempRepository::try_init().unwrap();
+    assert_eq!(
+        t.repo().hooks_path().unwrap(),
+        t.repo().repo().path().join("hooks")
+    );
+
+    t.repo()
+        .repo()
+        .config()
+        .unwrap()
+        .set_str("core.hooksPath", "githooks")
+        .unwrap();
+    assert_eq!(
+        t.repo().hooks_path().unwrap(),
+        t.repo().repo().workdir().unwrap().join("githooks")
+    );
+}
+
+#[test]
+fn test_main_workdir() {
+    let t = common::TempRepository::try_init().unwrap();
+    let (_, commit) = t.create_and_commit_random_file();
+    let main_workdir = t.repo().main_workdir().unwrap();
+    assert_eq!(
+        main_workdir.canonicalize().unwrap(),
+        t.path().canonicalize().unwrap()
+    );
+
+    let worktree_dir = tempfile::tempdir().unwrap();
+    let worktree_path = worktree_dir.path().join("wt");
+    t.repo()
+        .add_detached_worktree("wt", &worktree_path, &commit)
+        .unwrap();
+    let worktree = git2_ox::Repository::try_open(&worktree_path).unwrap();
+    assert_eq!(
+        worktree.main_workdir().unwrap().canonicalize().unwrap(),
+        t.path().canonicalize().unwrap()
+    );
+}
+
+#[test]
+fn test_check_revisions() {
+    use git2_ox::{ReplacementReason, RevisionState};
+
+    let t = common::TempRepository::try_init().unwrap();
+    let (_, first_id) = t.create_and_commit_random_file();
+    let (_, second_id) = t.create_and_commit_random_file();
+    let first_summary = t
+        .repo()
+        .get_commit_for_revision(&first_id)
+        .unwrap()
+        .summary()
+        .to_string();
+    // Reword the second commit, this keeps its changes
+    let second = t
+        .repo()
+        .repo()
+        .find_commit(git2::Oid::from_str(&second_id).unwrap())
+        .unwrap();
+    let amended_id = second
+        .amend(Some("HEAD"), None, None, None, Some("Reworded"), None)
+        .unwrap()
+        .to_string();
+    let missing_id = "0123456789012345678901234567890123456789";
+
+    let checks = t
+        .repo()
+        .check_revisions(&[
+            (&first_id, ""),
+            (&second_id, ""),
+            (missing_id, &first_summary),
+        ])
+        .unwrap();
+    assert_eq!(checks[0].state(), RevisionState::Reachable);
+    assert!(checks[0].candidates().is_empty());
+
+    assert_eq!(checks[1].state(), RevisionState::Unreachable);
+    let candidate = &checks[1].candidates()[0];
+    assert_eq!(candidate.commit().id(), amended_id);
+    assert_eq!(
+        candidate.reasons(),
+        &[ReplacementReason::Reflog, ReplacementReason::PatchId]
+    );
+
+    assert_eq!(checks[2].state(), RevisionState::Missing);
+    assert_eq!(checks[2].candidates().len(), 1);
+    assert_eq!(checks[2].candidates()[0].commit().id(), first_id);
+    assert_eq!(
+        checks[2].candidates()[0].reasons(),
+        &[ReplacementReason::Summary]
+    );
+}
+
+#[test]
+fn test_check_revisions_of_snapshots_and_stashes() {
+    use git2_ox::RevisionState;
+
+    let mut t = common::TempRepository::try_init().unwrap();
+    let (file, _) = t.create_and_commit_random_file();
+    std::fs::write(t.path().join(&file), "snapshot").unwrap();
+    let snapshot = t
+        .repo()
+        .create_snapshot("flow/node", "Snapshot", &[])
+        .unwrap();
+    let older_stash = t.repo_mut().save_stash(None, false).unwrap();
+    std::fs::write(t.path().join(&file), "stash").unwrap();
+    let newer_stash = t.repo_mut().save_stash(None, false).unwrap();
+
+    let checks = t
+        .repo()
+        .check_revisions(&[
+            (snapshot.commit().id(), ""),
+            (older_stash.commit().id(), ""),
+            (newer_stash.commit().id(), ""),
+        ])
+        .unwrap();
+    for check in checks {
+        assert_eq!(check.state(), RevisionState::Reachable);
+        assert!(check.candidates().is_empty());
+    }
+}
+
+#[test]
+fn test_commit_graph() {
+    use git2_ox::GraphEdge;
+
+    let t = common::TempRepository::try_init().unwrap();
+    let repo = t.repo().repo();
+    let tree = repo
+        .find_tree(repo.index().unwrap().write_tree().unwrap())
+        .unwrap();
+    // Explicit commit times keep the order of the walk stable
+    let commit = |update_ref: Option<&str>, message: &str, time: i64, parents: &[&git2::Commit]| {
+        let signature =
+            git2::Signature::new("test", "test@example.com", &git2::Time::new(time, 0)).unwrap();
+        let oid = repo
+            .commit(update_ref, &signature, &signature, message, &tree, parents)
+            .unwrap();
+        repo.find_commit(oid).unwrap()
+    };
+    // A <- B <---- M
+    //  \           /
+    //   <- C <----
+    let a = commit(Some("HEAD"), "A", 1000, &[]);
+    let b = commit(Some("HEAD"), "B", 1001, &[&a]);
+    let c = commit(None, "C", 1002, &[&a]);
+    let m = commit(Some("HEAD"), "M", 1003, &[&b, &c]);
+
+    let graph = t.repo().commit_graph(None, None, 0, 10).unwrap();
+    assert!(!graph.has_more());
+    assert_eq!(graph.columns(), 2);
+    let ids: Vec<_> = graph
+        .rows()
+        .iter()
+        .map(|r| r.commit().commit().id())
+        .collect();
+    let expected_ids: Vec<_> = [&m, &c, &b, &a]
+        .iter()
+        .map(|c| c.id().to_string())
+        .collect();
+    assert_eq!(ids, expected_ids);
+    assert_eq!(
+        graph.rows()[0].commit().commit().parent_ids(),
+        &[b.id().to_string(), c.id().to_string()]
+    );
+
+    let edge = |from, to| GraphEdge { from, to };
+    let rows = graph.rows();
+    assert_eq!(
+        rows.iter().map(|r| r.column()).collect::<Vec<_>>(),
+        [0, 1, 0, 0]
+    );
+    assert!(rows[0].is_merge() && !rows[0].is_fork());
+    assert_eq!(rows[0].edges(), &[edge(0, 0), edge(0, 1)]);
+    assert_eq!(rows[1].edges(), &[edge(0, 0), edge(1, 1)]);
+    assert_eq!(rows[2].edges(), &[edge(1, 0), edge(0, 0)]);
+    assert!(!rows[3].is_merge() && rows[3].is_fork());
+    assert!(rows[3].edges().is_empty());
+
+    // Pages line up with the full graph
+    let page = t.repo().commit_graph(None, None, 1, 2).unwrap();
+    assert!(page.has_more());
+    assert_eq!(page.rows().len(), 2);
+    for (row, expected) in page.rows().iter().zip(&rows[1..3]) {
+        assert_eq!(row.commit().commit().id(), expected.commit().commit().id());
+        assert_eq!(row.column(), expected.column());
+        assert_eq!(row.edges(), expected.edges());
+    }
+
+    // Parents hidden by the base do not get a lane
+    let base = a.id().to_string();
+    let graph = t.repo().commit_graph(Some(&base), None, 0, 10).unwrap();
+    assert_eq!(graph.rows().len(), 3);
+    assert!(graph.rows()[2].edges().is_empty());
+}
+
+#[test]
+fn test_pickaxe_commits() {
+    use git2_ox::Pickaxe;
+
+    let t = common::TempRepository::try_init().unwrap();
+    let repo = t.repo().repo();
+    let commit = |path: &str, content: &str| {
+        std::fs::write(t.path().join(path), content).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(std::path::Path::new(path)).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        let parent = repo.head().ok().map(|h| h.peel_to_commit().unwrap());
+        let parents: Vec<_> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &signature, &signature, path, &tree, &parents)
+            .unwrap()
+            .to_string()
+    };
+    let added = commit("a.txt", "let timeout = 5;\n");
+    // Changing the line keeps the number of occurrences of the string
+    let changed = commit("a.txt", "// config\nlet timeout = 10;\n");
+    let other_file = commit("b.txt", "timeout\n");
+    let removed = commit("a.txt", "// config\n");
+    let (added, changed, other_file, removed) = (&*added, &*changed, &*other_file, &*removed);
+
+    let ids = |pickaxe: Pickaxe, paths: &[&str]| {
+        let paths = paths.iter().map(|p| p.to_string()).collect();
+        t.repo()
+            .iter_pickaxe_commits(None, None, pickaxe, paths)
+            .unwrap()
+            .map(|c| c.unwrap().id().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    let string = || Pickaxe::String("timeout".to_string());
+    assert_eq!(ids(string(), &[]), [removed, other_file, added]);
+    assert_eq!(ids(string(), &["a.txt"]), [removed, added]);
+
+    // Unlike the string search, the regex search matches all changed lines
+    let regex = |r| Pickaxe::Regex(regex::Regex::new(r).unwrap());
+    assert_eq!(ids(regex(r"timeout = \d"), &[]), [removed, changed, added]);
+    assert_eq!(ids(regex("^// config"), &[]), [changed]);
+    assert!(ids(regex("interval"), &[]).is_empty());
+}
+
+#[test]
+fn test_blame() {
+    let t = common::TempRepository::try_init().unwrap();
+    let repo = t.repo().repo();
+    let commit = |content: &str, author: &str| {
+        std::fs::write(t.path().join("file.txt"), content).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(std::path::Path::new("file.txt")).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now(author, "test@example.com").unwrap();
+        let parent = repo.head().ok().map(|h| h.peel_to_commit().unwrap());
+        let parents: Vec<_> = parent.iter().collect();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            author,
+            &tree,
+            &parents,
+        )
+        .unwrap()
+        .to_string()
+    };
+    let first = commit("one\ntwo\nthree\n", "Alice");
+    let second = commit("one\nTWO\nthree\n", "Bob");
+
+    let spans = |revision: &str, range| {
+        t.repo()
+            .blame("file.txt", revision, range)
+            .unwrap()
+            .iter()
+            .map(|h| (h.commit().id().to_string(), h.start_line(), h.num_lines()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        spans("HEAD", None),
+        [
+            (first.clone(), 1, 1),
+            (second.clone(), 2, 1),
+            (first.clone(), 3, 1)
+        ]
+    );
+    assert_eq!(
+        spans("HEAD", Some(2..=3)),
+        [(second.clone(), 2, 1), (first.clone(), 3, 1)]
+    );
+    assert_eq!(spans(&first, None), [(first.clone(), 1, 3)]);
+
+    let hunks = t.repo().blame("file.txt", "HEAD", Some(2..=2)).unwrap();
+    assert_eq!(hunks[0].commit().author().name(), "Bob");
+    assert_eq!(hunks[0].orig_path(), "file.txt");
+    assert_eq!(hunks[0].orig_start_line(), 2);
+
+    // The range is cut at the end of the file
+    assert_eq!(spans("HEAD", Some(3..=10)), [(first.clone(), 3, 1)]);
+    assert!(matches!(
+        t.repo().blame("file.txt", "HEAD", Some(4..=10)),
+        Err(git2_ox::error::Error::Invalid(_))
+    ));
+    assert!(matches!(
+        t.repo().blame("missing.txt", "HEAD", None),
+        Err(git2_ox::error::Error::NotFound(_))
+    ));
+}
+
+#[test]
+fn test_tree_and_blob() {
+    use git2_ox::TreeEntryKind;
+
+    let t = common::TempRepository::try_init().unwrap();
+    let repo = t.repo().repo();
+    let files: [(&str, &[u8]); 3] = [
+        ("README.md", "Grüße\n".as_bytes()),
+        ("src/main.rs", b"fn main() {}\n"),
+        ("image.bin", b"\x89PNG\x00\x00\x01"),
+    ];
+    let mut index = repo.index().unwrap();
+    for (path, content) in files {
+        let file_path = t.path().join(path);
+        std::fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+        std::fs::write(&file_path, content).unwrap();
+        index.add_path(std::path::Path::new(path)).unwrap();
+    }
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = git2::Signature::now("test", "test@example.com").unwrap();
+    repo.commit(Some("HEAD"), &signature, &signature, "Files", &tree, &[])
+        .unwrap();
+
+    let entries = t.repo().list_tree("HEAD", "").unwrap();
+    let names: Vec<_> = entries.iter().map(|e| e.name()).collect();
+    assert_eq!(names, ["src", "README.md", "image.bin"]);
+    assert_eq!(entries[0].kind(), TreeEntryKind::Tree);
+    assert_eq!(entries[0].size(), None);
+    assert_eq!(entries[1].kind(), TreeEntryKind::Blob);
+    assert_eq!(entries[1].mode(), "100644");
+    assert_eq!(entries[1].size(), Some("Grüße\n".len()));
+
+    let entries = t.repo().list_tree("HEAD", "/src/").unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].path(), "src/main.rs");
+
+    let blob = t.repo().read_blob("HEAD", "README.md", None).unwrap();
+    assert_eq!(blob.content(), Some("Grüße\n"));
+    assert!(!blob.is_binary() && !blob.is_truncated());
+    let readme = t.repo().list_tree("HEAD", "").unwrap().remove(1);
+    assert_eq!(blob.id(), readme.id());
+
+    // A character cut at the limit is dropped
+    let blob = t.repo().read_blob("HEAD", "README.md", Some(3)).unwrap();
+    assert_eq!(blob.content(), Some("Gr"));
+    assert!(blob.is_truncated());
+    assert_eq!(blob.size(), "Grüße\n".len());
+
+    let blob = t.repo().read_blob("HEAD", "image.bin", None).unwrap();
+    assert!(blob.is_binary());
+    assert_eq!(blob.content(), None);
+
+    assert!(matches!(
+        t.repo().read_blob("HEAD", "src", None),
+        Err(git2_ox::error::Error::Invalid(_))
+    ));
+    assert!(matches!(
+        t.repo().list_tree("HEAD", "README.md"),
+        Err(git2_ox::error::Error::Invalid(_))
+    ));
+    assert!(matches!(
+        t.repo().list_tree("HEAD", "missing"),
+        Err(git2_ox::error::Error::NotFound(_))
+    ));
+}
+
+#[test]
+fn test_diff_options() {
+    use git2_ox::{DiffOptions, Whitespace};
+
+    let t = common::TempRepository::try_init().unwrap();
+    let repo = t.repo().repo();
+    let commit = |files: &[(&str, Option<&str>)]| {
+        let mut index = repo.index().unwrap();
+        for (path, content) in files {
+            let file_path = t.path().join(path);
+            match content {
+                Some(content) => {
+                    std::fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+                    std::fs::write(&file_path, content).unwrap();
+                    index.add_path(std::path::Path::new(path)).unwrap();
+                }
+                None => {
+                    std::fs::remove_file(&file_path).unwrap();
+                    index.remove_path(std::path::Path::new(path)).unwrap();
+                }
+            }
+        }
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        let parent = repo.head().ok().map(|h| h.peel_to_commit().unwrap());
+        let parents: Vec<_> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &signature, &signature, "msg", &tree, &parents)
+            .unwrap()
+            .to_string()
+    };
+    let content: String = (1..=10).map(|i| format!("line {i}\n")).collect();
+    let base = commit(&[
+        ("src/a.txt", Some(&content)),
+        ("old_name.txt", Some("some content\nwhich is moved\n")),
+    ]);
+    commit(&[
+        (
+            "src/a.txt",
+            Some(&content.replace("line 5\n", "line  5 \n")),
+        ),
+        ("old_name.txt", None),
+        ("docs/new_name.txt", Some("some content\nwhich is moved\n")),
+    ]);
+
+    let diff = |options: DiffOptions| t.repo().diff(Some(&base), None, options).unwrap();
+
+    let default = diff(DiffOptions::new());
+    assert_eq!(default.options(), &DiffOptions::new());
+    assert!(default.patch().contains("rename from old_name.txt"));
+    assert!(default.patch().contains("+line  5 "));
+    assert!(default.patch().contains(" line 2\n"));
+
+    let no_renames = diff(DiffOptions::new().renames(false));
+    assert!(!no_renames.patch().contains("rename from"));
+    assert_eq!(no_renames.stats().files_changed(), 3);
+
+    let ignore_whitespace = diff(DiffOptions::new().whitespace(Whitespace::IgnoreChange));
+    assert!(!ignore_whitespace.patch().contains("line  5"));
+    assert!(
+        diff(DiffOptions::new().whitespace(Whitespace::IgnoreAtEol))
+            .patch()
+            .contains("+line  5 ")
+    );
+
+    let less_context = diff(DiffOptions::new().context_lines(1));
+    assert!(!less_context.patch().contains(" line 2\n"));
+    assert!(less_context.patch().contains(" line 4\n"));
+
+    let options = DiffOptions::new().paths(vec!["src".to_string()]);
+    let only_src = diff(options.clone());
+    assert_eq!(only_src.stats().files_changed(), 1);
+    assert_eq!(only_src.options(), &options);
+}
+
+#[test]
+fn test_status_and_working_tree_diff() {
+    use git2_ox::{DiffOptions, FileChange, WorkingTreeDiff};
+
+    let t = common::TempRepository::try_init().unwrap();
+    let repo = t.repo().repo();
+    let (base_file, base) = t.create_and_commit_random_file();
+    let (modified, _) = t.create_and_commit_random_file();
+    let (staged, _) = t.create_and_commit_random_file();
+    assert!(t.repo().status().unwrap().is_clean());
+    assert!(t.repo().status().unwrap().upstream().is_none());
+
+    std::fs::write(t.path().join(&modified), "unstaged change\n").unwrap();
+    std::fs::write(t.path().join(&staged), "staged change\n").unwrap();
+    std::fs::write(t.path().join("untracked.txt"), "untracked\n").unwrap();
+    std::fs::rename(t.path().join(&base_file), t.path().join("renamed.txt")).unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(std::path::Path::new(&staged)).unwrap();
+    index.remove_path(std::path::Path::new(&base_file)).unwrap();
+    index.add_path(std::path::Path::new("renamed.txt")).unwrap();
+    index.write().unwrap();
+
+    let status = t.repo().status().unwrap();
+    let file = |path: &str| {
+        status
+            .files()
+            .iter()
+            .find(|f| f.path() == path)
+            .unwrap_or_else(|| panic!("missing status of {path}"))
+    };
+    assert_eq!(status.files().len(), 4);
+    assert_eq!(file(&modified).unstaged(), Some(FileChange::Modified));
+    assert_eq!(file(&modified).staged(), None);
+    assert_eq!(file(&staged).staged(), Some(FileChange::Modified));
+    assert_eq!(file(&staged).unstaged(), None);
+    assert!(file("untracked.txt").is_untracked());
+    assert_eq!(file("renamed.txt").staged(), Some(FileChange::Renamed));
+    assert_eq!(file("renamed.txt").old_path(), Some(base_file.as_str()));
+    assert!(status.files().iter().all(|f| !f.is_conflicted()));
+
+    let diff = |target| {
+        t.repo()
+            .diff_working_tree(target, DiffOptions::new())
+            .unwrap()
+    };
+    let unstaged = diff(WorkingTreeDiff::Unstaged);
+    assert!(unstaged.patch().contains("+unstaged change"));
+    assert!(!unstaged.patch().contains("+staged change"));
+    let staged_diff = diff(WorkingTreeDiff::Staged);
+    assert!(staged_diff.patch().contains("+staged change"));
+    assert!(staged_diff.patch().contains("rename to renamed.txt"));
+    assert!(!staged_diff.patch().contains("+unstaged change"));
+    let all = diff(WorkingTreeDiff::All);
+    assert!(all.patch().contains("+staged change"));
+    assert!(all.patch().contains("+unstaged change"));
+
+    // Track a branch at the first commit, the current branch is two commits ahead
+    let head = repo.head().unwrap();
+    let branch_name = head.shorthand().unwrap().to_string();
+    repo.branch(
+        "upstream",
+        &repo.find_commit(base.parse().unwrap()).unwrap(),
+        false,
+    )
+    .unwrap();
+    repo.find_branch(&branch_name, git2::BranchType::Local)
+        .unwrap()
+        .set_upstream(Some("upstream"))
+        .unwrap();
+    let status = t.repo().status().unwrap();
+    let upstream = status.upstream().unwrap();
+    assert_eq!(upstream.name(), "upstream");
+    assert_eq!((upstream.ahead(), upstream.behind()), (2, 0));
+}
+
+#[test]
+fn test_checkout_modes() {
+    use git2_ox::CheckoutMode;
+
+    let mut t = common::TempRepository::try_init().unwrap();
+    let path = t.path().to_path_buf();
+    let write = |file: &str, content: &str| std::fs::write(path.join(file), content).unwrap();
+    let read = |file: &str| std::fs::read_to_string(path.join(file)).unwrap();
+    let commit = |t: &common::TempRepository, files: &[&str]| {
+        let repo = t.repo().repo();
+        let mut index = repo.index().unwrap();
+        for file in files {
+            index.add_path(std::path::Path::new(file)).unwrap();
+        }
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        let parent = repo.head().ok().map(|h| h.peel_to_commit().unwrap());
+        let parents: Vec<_> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &signature, &signature, "msg", &tree, &parents)
+            .unwrap()
+            .to_string()
+    };
+    write("a.txt", "a1\n");
+    write("b.txt", "b1\n");
+    let first = commit(&t, &["a.txt", "b.txt"]);
+    write("a.txt", "a2\n");
+    write("c.txt", "c2\n");
+    let second = commit(&t, &["a.txt", "c.txt"]);
+    let stashes = |t: &common::TempRepository| {
+        t.repo()
+            .repo()
+            .reflog("refs/stash")
+            .map_or(0, |reflog| reflog.len())
+    };
+    let conflict_paths = |result: Result<_, git2_ox::error::Error>| match result {
+        Err(git2_ox::error::Error::Conflict(conflict)) => conflict.paths,
+        other => panic!("expected a conflict, got {other:?}"),
+    };
+
+    // Changes which do not conflict with the checkout are still refused
+    write("b.txt", "local\n");
+    let paths = conflict_paths(t.repo_mut().checkout_revision(&first, CheckoutMode::Refuse));
+    assert_eq!(paths, ["b.txt"]);
+    assert_eq!(read("a.txt"), "a2\n");
+
+    // The changes are carried over when stashed
+    let head = t
+        .repo_mut()
+        .checkout_revision(&first, CheckoutMode::Stash)
+        .unwrap();
+    assert_eq!(head.commit().id(), first);
+    assert_eq!(
+        (read("a.txt"), read("b.txt")),
+        ("a1\n".into(), "local\n".into())
+    );
+    assert_eq!(stashes(&t), 0);
+
+    // Untracked files in the way are reported as conflicts
+    t.repo_mut()
+        .checkout_revision(&first, CheckoutMode::Force)
+        .unwrap();
+    write("c.txt", "untracked\n");
+    let paths = conflict_paths(
+        t.repo_mut()
+            .checkout_revision(&second, CheckoutMode::Refuse),
+    );
+    assert_eq!(paths, ["c.txt"]);
+    let paths = conflict_paths(t.repo_mut().checkout_revision(&second, CheckoutMode::Stash));
+    assert_eq!(paths, ["c.txt"]);
+    assert_eq!(
+        t.repo()
+            .get_commit_for_revision("HEAD")
+            .unwrap()
+            .commit()
+            .id(),
+        first
+    );
+    assert_eq!(read("c.txt"), "untracked\n");
+    assert_eq!(stashes(&t), 0);
+
+    // Conflicting stashed changes are kept in the stash after the checkout
+    std::fs::remove_file(path.join("c.txt")).unwrap();
+    write("a.txt", "local\n");
+    let paths = conflict_paths(t.repo_mut().checkout_revision(&second, CheckoutMode::Stash));
+    assert_eq!(paths, ["a.txt"]);
+    assert_eq!(
+        t.repo()
+            .get_commit_for_revision("HEAD")
+            .unwrap()
+            .commit()
+            .id(),
+        second
+    );
+    assert!(read("a.txt").contains("<<<<<<<"));
+    assert_eq!(stashes(&t), 1);
+
+    // Local changes are discarded when forced
+    write("a.txt", "local\n");
+    t.repo_mut()
+        .checkout_revision(&first, CheckoutMode::Force)
+        .unwrap();
+    assert_eq!(read("a.txt"), "a1\n");
+    assert!(t.repo().status().unwrap().is_clean());
+}
+
+#[test]
+fn test_snapshots() {
+    use git2_ox::{CheckoutMode, FileChange};
+
+    let mut t = common::TempRepository::try_init().unwrap();
+    let path = t.path().to_path_buf();
+    let write = |file: &str, content: &str| {
+        let file = path.join(file);
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::write(file, content).unwrap();
+    };
+    let read = |file: &str| std::fs::read_to_string(path.join(file)).unwrap();
+    let (a, _) = t.create_and_commit_random_file();
+    let (b, _) = t.create_and_commit_random_file();
+    let base_head = t.repo().get_commit_for_revision("HEAD").unwrap();
+    let base_head = base_head.commit().id().to_string();
+
+    write(&a, "unstaged\n");
+    write(&b, "staged\n");
+    let mut index = t.repo().repo().index().unwrap();
+    index.add_path(std::path::Path::new(&b)).unwrap();
+    index.write().unwrap();
+    write("untracked.txt", "untracked\n");
+    write(".debug-flow/flow.json", "{}\n");
+    let status_before = format!("{:?}", t.repo().status().unwrap());
+
+    let snapshot = t
+        .repo()
+        .create_snapshot(
+            "flow/node",
+            "Snapshot of node",
+            &[".debug-flow".to_string()],
+        )
+        .unwrap();
+    assert_eq!(snapshot.reference(), "refs/debug-flow/snapshots/flow/node");
+    assert_eq!(snapshot.base_id(), base_head);
+    assert_eq!(snapshot.commit().summary(), "Snapshot of node");
+    assert_eq!(format!("{:?}", t.repo().status().unwrap()), status_before);
+    let blob = |path: &str| {
+        t.repo()
+            .read_blob(snapshot.commit().id(), path, None)
+            .map(|blob| blob.content().unwrap().to_string())
+    };
+    assert_eq!(blob(&a).unwrap(), "unstaged\n");
+    assert_eq!(blob(&b).unwrap(), "staged\n");
+    assert_eq!(blob("untracked.txt").unwrap(), "untracked\n");
+    assert!(blob(".debug-flow/flow.json").is_err());
+
+    t.repo()
+        .create_snapshot("other/node", "Other", &[])
+        .unwrap();
+    let names = |t: &common::TempRepository, prefix: &str| -> Vec<String> {
+        t.repo()
+            .list_snapshots(prefix)
+            .unwrap()
+            .iter()
+            .map(|s| s.name().to_string())
+            .collect()
+    };
+    assert_eq!(names(&t, ""), ["flow/node", "other/node"]);
+    assert_eq!(names(&t, "flow/"), ["flow/node"]);
+    // Snapshots are neither branches nor tags
+    assert_eq!(t.repo().iter_branches().unwrap().count(), 1);
+
+    // Local changes are not overwritten
+    assert!(matches!(
+        t.repo_mut()
+            .restore_snapshot("flow/node", CheckoutMode::Refuse),
+        Err(git2_ox::error::Error::Conflict(_))
+    ));
+
+    t.repo_mut()
+        .checkout_revision(&base_head, CheckoutMode::Force)
+        .unwrap();
+    std::fs::remove_file(path.join("untracked.txt")).unwrap();
+    t.create_and_commit_random_file();
+    let restored = t
+        .repo_mut()
+        .restore_snapshot("flow/node", CheckoutMode::Refuse)
+        .unwrap();
+    assert_eq!(restored.commit().id(), snapshot.commit().id());
+    let head = t.repo().get_commit_for_revision("HEAD").unwrap();
+    assert_eq!(head.commit().id(), base_head);
+    assert_eq!(
+        (read(&a), read(&b)),
+        ("unstaged\n".into(), "staged\n".into())
+    );
+    assert_eq!(read("untracked.txt"), "untracked\n");
+    let status = t.repo().status().unwrap();
+    let file = |path: &str| status.files().iter().find(|f| f.path() == path).unwrap();
+    assert_eq!(file(&a).unstaged(), Some(FileChange::Modified));
+    assert_eq!(file(&a).staged(), None);
+    assert_eq!(file(&b).staged(), Some(FileChange::Modified));
+    assert!(file("untracked.txt").is_untracked());
+
+    t.repo().delete_snapshot("flow/node").unwrap();
+    assert_eq!(names(&t, ""), ["other/node"]);
+    assert!(matches!(
+        t.repo().delete_snapshot("flow/node"),
+        Err(git2_ox::error::Error::NotFound(_))
+    ));
+    assert!(matches!(
+        t.repo().create_snapshot("bad..name", "Invalid", &[]),
+        Err(git2_ox::error::Error::Invalid(_))
+    ));
+}
+
+#[test]
+fn test_stashes() {
+    use git2_ox::DiffOptions;
+
+    let mut t = common::TempRepository::try_init().unwrap();
+    let (file, first) = t.create_and_commit_random_file();
+    let path = t.path().to_path_buf();
+    let read = |file: &str| std::fs::read_to_string(path.join(file)).unwrap();
+    assert!(t.repo().list_stashes().unwrap().is_empty());
+    assert!(matches!(
+        t.repo_mut().save_stash(None, false),
+        Err(git2_ox::error::Error::Invalid(_))
+    ));
+
+    // Stash a staged change and an untracked file
+    std::fs::write(path.join(&file), "staged change\n").unwrap();
+    let mut index = t.repo().repo().index().unwrap();
+    index.add_path(std::path::Path::new(&file)).unwrap();
+    index.write().unwrap();
+    std::fs::write(path.join("untracked.txt"), "untracked\n").unwrap();
+    let stash = t.repo_mut().save_stash(Some("experiment"), true).unwrap();
+    assert_eq!(stash.index(), 0);
+    assert!(stash.message().ends_with("experiment"));
+    assert_eq!(stash.base_id(), first);
+    assert!(stash.has_untracked());
+    assert_eq!(read(&file), "random content");
+    assert!(!path.join("untracked.txt").exists());
+
+    let diff = t.repo().diff_stash(0, DiffOptions::new()).unwrap();
+    assert!(diff.patch().contains("+staged change"));
+    assert!(diff.patch().contains("+untracked"));
+    assert_eq!(diff.stats().files_changed(), 2);
+
+    // A second stash moves the first one down the list
+    std::fs::write(path.join(&file), "second\n").unwrap();
+    t.repo_mut().save_stash(None, false).unwrap();
+    let stashes = t.repo().list_stashes().unwrap();
+    assert_eq!(stashes.len(), 2);
+    assert_eq!(stashes[1].commit().id(), stash.commit().id());
+    let found = t.repo().find_stash(&stash.commit().id()[..8]).unwrap();
+    assert_eq!(found.index(), 1);
+    assert_eq!(t.repo().find_stash("stash@{1}").unwrap().index(), 1);
+    assert!(matches!(
+        t.repo().find_stash("2"),
+        Err(git2_ox::error::Error::NotFound(_))
+    ));
+
+    // Applying keeps the stash, popping restores the index and drops it
+    t.repo_mut().apply_stash(0).unwrap();
+    assert_eq!(read(&file), "second\n");
+    assert_eq!(t.repo().list_stashes().unwrap().len(), 2);
+    t.repo_mut().drop_stash(0).unwrap();
+    std::fs::write(path.join(&file), "random content").unwrap();
+    t.repo_mut().pop_stash(0).unwrap();
+    assert!(t.repo().list_stashes().unwrap().is_empty());
+    assert_eq!(read(&file), "staged change\n");
+    assert_eq!(read("untracked.txt"), "untracked\n");
+    let status = t.repo().status().unwrap();
+    let staged = status.files().iter().find(|f| f.path() == file).unwrap();
+    assert!(staged.staged().is_some());
+
+    // Conflicting changes are merged with conflict markers and the stash is kept
+    t.repo_mut().save_stash(None, true).unwrap();
+    std::fs::write(path.join(&file), "conflicting\n").unwrap();
+    {
+        let repo = t.repo().repo();
+        let mut index = repo.index().unwrap();
+        index.add_path(std::path::Path::new(&file)).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("test", "test@example.com").unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "msg", &tree, &[&head])
+            .unwrap();
+    }
+    match t.repo_mut().pop_stash(0) {
+        Err(git2_ox::error::Error::Conflict(conflict)) => {
+            assert_eq!(conflict.paths, [file.as_str()])
+        }
+        other => panic!("expected a conflict, got {other:?}"),
+    }
+    assert!(read(&file).contains("<<<<<<<"));
+    assert_eq!(t.repo().list_stashes().unwrap().len(), 1);
+}